@@ -0,0 +1,238 @@
+use ndarray::{concatenate, Array, Axis, IxDyn, NdFloat, Slice, SliceInfoElem};
+
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+pub struct Cat {
+    pub axis: usize,
+}
+
+impl<T> GradFn<T> for Cat
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        concatenate(Axis(self.axis), &[x.view(), y.view()]).unwrap()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let split = left_ref.borrow().data.shape()[self.axis] as isize;
+
+        [
+            grad.slice_axis(Axis(self.axis), Slice::from(..split))
+                .to_owned(),
+            grad.slice_axis(Axis(self.axis), Slice::from(split..))
+                .to_owned(),
+        ]
+    }
+}
+
+/// slice selecting `elem` along `axis` and everything along the other axes
+fn axis_slice_info(ndim: usize, axis: usize, elem: SliceInfoElem) -> Vec<SliceInfoElem> {
+    let mut info: Vec<SliceInfoElem> = (0..ndim).map(|_| (..).into()).collect();
+    info[axis] = elem;
+    info
+}
+
+/// concatenate the variables along an existing axis
+pub fn cat<T: NdFloat>(vars: &[VariableRef<T>], axis: usize) -> VariableRef<T> {
+    assert!(!vars.is_empty(), "cat expects at least one variable");
+
+    let mut out = vars[0].clone();
+    for var in vars[1..].iter() {
+        let grad_fn = Cat { axis };
+        out = grad_fn.subscribe(&out, var, Box::new(Cat { axis }));
+    }
+    out
+}
+
+/// join the variables along a new axis, they must all have the same shape. 0-d variables
+/// are stacked into a 1-d one
+pub fn stack<T: NdFloat>(vars: &[VariableRef<T>], axis: usize) -> VariableRef<T> {
+    assert!(!vars.is_empty(), "stack expects at least one variable");
+
+    let unsqueezed: Vec<VariableRef<T>> = vars
+        .iter()
+        .map(|var| {
+            let ndim = var.borrow().data.ndim();
+            assert!(
+                axis <= ndim,
+                "stack axis {} out of range for {}-d variables",
+                axis,
+                ndim
+            );
+            let mut info: Vec<SliceInfoElem> = (0..ndim).map(|_| (..).into()).collect();
+            info.insert(axis, SliceInfoElem::NewAxis);
+            var.clone().slice(info.as_slice())
+        })
+        .collect();
+
+    cat(&unsqueezed, axis)
+}
+
+impl<T> VariableRef<T>
+where
+    T: NdFloat,
+{
+    /// split into pieces of `split_size` along `axis`, the last one being smaller if needed.
+    /// All the pieces share a single node of the graph
+    pub fn split(&mut self, split_size: usize, axis: usize) -> Vec<VariableRef<T>> {
+        assert!(split_size > 0, "split_size must be positive");
+
+        let (ndim, len) = (self.borrow().data.ndim(), self.borrow().data.shape()[axis]);
+        let mut shared = self.identity();
+
+        (0..len)
+            .step_by(split_size)
+            .map(|start| {
+                let end = (start + split_size).min(len);
+                shared.slice(axis_slice_info(ndim, axis, (start..end).into()).as_slice())
+            })
+            .collect()
+    }
+
+    /// split into `chunks` pieces of the same size along `axis`, the last one being smaller if needed
+    pub fn chunk(&mut self, chunks: usize, axis: usize) -> Vec<VariableRef<T>> {
+        assert!(chunks > 0, "chunks must be positive");
+
+        let len = self.borrow().data.shape()[axis];
+        self.split(len.div_ceil(chunks).max(1), axis)
+    }
+
+    /// remove `axis` and return all the slices along it
+    pub fn unbind(&mut self, axis: usize) -> Vec<VariableRef<T>> {
+        let (ndim, len) = (self.borrow().data.ndim(), self.borrow().data.shape()[axis]);
+        let mut shared = self.identity();
+
+        (0..len)
+            .map(|i| shared.slice(axis_slice_info(ndim, axis, i.into()).as_slice()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn cat_check_backward() {
+        let x = Variable::new(array!([1.0, 2.0], [3.0, 4.0]).into_dyn());
+        let y = Variable::new(array!([5.0], [6.0]).into_dyn());
+        let z = Variable::new(array!([7.0, 8.0, 9.0], [10.0, 11.0, 12.0]).into_dyn());
+
+        let out = &cat(&[x.clone(), y.clone(), z.clone()], 1);
+        assert_eq!(
+            out.borrow().data,
+            array!(
+                [1.0, 2.0, 5.0, 7.0, 8.0, 9.0],
+                [3.0, 4.0, 6.0, 10.0, 11.0, 12.0]
+            )
+            .into_dyn()
+        );
+
+        let weight =
+            Variable::new(Array::from_shape_fn((2, 6), |(i, j)| (6 * i + j) as f64).into_dyn());
+        let mut loss = (out * &weight).sum();
+        loss.backward();
+
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([0.0, 1.0], [6.0, 7.0]).into_dyn()
+        );
+        assert_eq!(y.borrow().get_grad_f(), array!([2.0], [8.0]).into_dyn());
+        assert_eq!(
+            z.borrow().get_grad_f(),
+            array!([3.0, 4.0, 5.0], [9.0, 10.0, 11.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn stack_check_backward() {
+        let x = Variable::new(array!(1.0, 2.0).into_dyn());
+        let y = Variable::new(array!(3.0, 4.0).into_dyn());
+
+        let out = &stack(&[x.clone(), y.clone()], 1);
+        assert_eq!(out.borrow().data, array!([1.0, 3.0], [2.0, 4.0]).into_dyn());
+
+        let weight = Variable::new(array!([1.0, 2.0], [3.0, 4.0]).into_dyn());
+        let mut loss = (out * &weight).sum();
+        loss.backward();
+
+        assert_eq!(x.borrow().get_grad_f(), array!(1.0, 3.0).into_dyn());
+        assert_eq!(y.borrow().get_grad_f(), array!(2.0, 4.0).into_dyn());
+    }
+
+    #[test]
+    fn stack_scalars() {
+        let x = Variable::new(Array::from_elem(IxDyn(&[]), 1.0));
+        let y = Variable::new(Array::from_elem(IxDyn(&[]), 2.0));
+
+        let mut out = stack(&[x.clone(), y.clone()], 0);
+        assert_eq!(out.borrow().data, array!(1.0, 2.0).into_dyn());
+
+        out.backward();
+        assert_eq!(x.borrow().get_grad_f(), Array::from_elem(IxDyn(&[]), 1.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "stack expects at least one variable")]
+    fn stack_empty() {
+        stack::<f64>(&[], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "chunks must be positive")]
+    fn chunk_zero() {
+        Variable::new(array!(1.0, 2.0).into_dyn()).chunk(0, 0);
+    }
+
+    #[test]
+    fn split_check_backward() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+
+        let pieces = x.split(2, 1);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(
+            pieces[0].borrow().data,
+            array!([1.0, 2.0], [4.0, 5.0]).into_dyn()
+        );
+        assert_eq!(pieces[1].borrow().data, array!([3.0], [6.0]).into_dyn());
+
+        let mut loss = pieces[0].clone().sum() * pieces[1].clone().sum();
+        loss.backward();
+
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([9.0, 9.0, 12.0], [9.0, 9.0, 12.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn chunk_and_unbind() {
+        let mut x =
+            Variable::new(Array::from_shape_fn((5, 2), |(i, j)| (2 * i + j) as f64).into_dyn());
+
+        let chunks = x.chunk(2, 0);
+        assert_eq!(chunks[0].borrow().data.shape(), [3, 2]);
+        assert_eq!(chunks[1].borrow().data.shape(), [2, 2]);
+
+        let rows = x.unbind(0);
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[4].borrow().data, array!(8.0, 9.0).into_dyn());
+
+        let mut loss = &rows[1] * &rows[4];
+        loss.backward();
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([0.0, 0.0], [8.0, 9.0], [0.0, 0.0], [0.0, 0.0], [2.0, 3.0]).into_dyn()
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use ndarray::{s, Array, Array2, Array3, Array4, ArrayView4, Axis, Ix4, IxDyn, NdFloat};
+
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+/// length of the output of a convolution along one spatial axis
+pub fn conv_output_size(
+    len: usize,
+    kernel: usize,
+    stride: usize,
+    padding: usize,
+    dilation: usize,
+) -> usize {
+    assert!(
+        kernel > 0 && stride > 0,
+        "convolution with an empty kernel or a zero stride"
+    );
+    let span = dilation * (kernel - 1) + 1;
+    assert!(
+        len + 2 * padding >= span,
+        "kernel of span {} is larger than the padded input of length {}",
+        span,
+        len + 2 * padding
+    );
+    (len + 2 * padding - span) / stride + 1
+}
+
+/// spatial hyper parameters shared by the 2-D convolutions, as `(height, width)` pairs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConvParams {
+    pub stride: (usize, usize),
+    pub padding: (usize, usize),
+    pub dilation: (usize, usize),
+}
+
+impl Default for ConvParams {
+    fn default() -> ConvParams {
+        ConvParams {
+            stride: (1, 1),
+            padding: (0, 0),
+            dilation: (1, 1),
+        }
+    }
+}
+
+impl ConvParams {
+    pub fn output_size(&self, input: (usize, usize), kernel: (usize, usize)) -> (usize, usize) {
+        (
+            conv_output_size(
+                input.0,
+                kernel.0,
+                self.stride.0,
+                self.padding.0,
+                self.dilation.0,
+            ),
+            conv_output_size(
+                input.1,
+                kernel.1,
+                self.stride.1,
+                self.padding.1,
+                self.dilation.1,
+            ),
+        )
+    }
+
+    /// position in the unpadded input read by the output `out` for the kernel offset `k`
+    fn input_position(&self, out: (usize, usize), k: (usize, usize)) -> (isize, isize) {
+        (
+            (out.0 * self.stride.0 + k.0 * self.dilation.0) as isize - self.padding.0 as isize,
+            (out.1 * self.stride.1 + k.1 * self.dilation.1) as isize - self.padding.1 as isize,
+        )
+    }
+}
+
+/// unfold a `(N, C, H, W)` input into `(N, C * kh * kw, oh * ow)` columns, the rows being
+/// ordered by channel then kernel position
+pub fn im2col<T: NdFloat>(
+    x: &ArrayView4<T>,
+    kernel: (usize, usize),
+    params: &ConvParams,
+) -> Array3<T> {
+    let (n, c, h, w) = x.dim();
+    let (oh, ow) = params.output_size((h, w), kernel);
+    let mut cols = Array3::<T>::zeros((n, c * kernel.0 * kernel.1, oh * ow));
+
+    for ci in 0..c {
+        for ki in 0..kernel.0 {
+            for kj in 0..kernel.1 {
+                let row = (ci * kernel.0 + ki) * kernel.1 + kj;
+                for oi in 0..oh {
+                    for oj in 0..ow {
+                        let (hi, wj) = params.input_position((oi, oj), (ki, kj));
+                        if hi < 0 || wj < 0 || hi >= h as isize || wj >= w as isize {
+                            continue;
+                        }
+                        for b in 0..n {
+                            cols[[b, row, oi * ow + oj]] = x[[b, ci, hi as usize, wj as usize]];
+                        }
+                    }
+                }
+            }
+        }
+    }
+    cols
+}
+
+/// fold columns back into a `(N, C, H, W)` array, summing the overlapping positions. This is
+/// the adjoint of `im2col`
+pub fn col2im<T: NdFloat>(
+    cols: &Array3<T>,
+    shape: (usize, usize, usize, usize),
+    kernel: (usize, usize),
+    params: &ConvParams,
+) -> Array4<T> {
+    let (n, c, h, w) = shape;
+    let (oh, ow) = params.output_size((h, w), kernel);
+    let mut x = Array4::<T>::zeros(shape);
+
+    for ci in 0..c {
+        for ki in 0..kernel.0 {
+            for kj in 0..kernel.1 {
+                let row = (ci * kernel.0 + ki) * kernel.1 + kj;
+                for oi in 0..oh {
+                    for oj in 0..ow {
+                        let (hi, wj) = params.input_position((oi, oj), (ki, kj));
+                        if hi < 0 || wj < 0 || hi >= h as isize || wj >= w as isize {
+                            continue;
+                        }
+                        for b in 0..n {
+                            x[[b, ci, hi as usize, wj as usize]] += cols[[b, row, oi * ow + oj]];
+                        }
+                    }
+                }
+            }
+        }
+    }
+    x
+}
+
+/// `(N, C_in, H, W)` input convolved with a `(C_out, C_in / groups, kh, kw)` weight
+#[derive(Clone, Copy)]
+pub struct Conv2d {
+    pub params: ConvParams,
+    pub groups: usize,
+}
+
+/// view a dynamic array as 4-D, a `(N, C, L)` array is seen as `(N, C, 1, L)`
+pub(crate) fn view4<T: NdFloat>(x: &Array<T, IxDyn>) -> ArrayView4<'_, T> {
+    let x = if x.ndim() == 3 {
+        x.view().insert_axis(Axis(2))
+    } else {
+        x.view()
+    };
+    x.into_dimensionality::<Ix4>().unwrap()
+}
+
+/// weight reshaped to a `(rows, everything else)` matrix
+pub(crate) fn weight_matrix<T: NdFloat>(weight: &ArrayView4<T>) -> Array2<T> {
+    let (rows, c, kh, kw) = weight.dim();
+    weight
+        .as_standard_layout()
+        .into_owned()
+        .into_shape((rows, c * kh * kw))
+        .unwrap()
+}
+
+impl Conv2d {
+    fn convolve<T: NdFloat>(&self, x: &ArrayView4<T>, weight: &ArrayView4<T>) -> Array4<T> {
+        let (n, c, h, w) = x.dim();
+        let (oc, cg, kh, kw) = weight.dim();
+        assert_eq!(
+            c,
+            cg * self.groups,
+            "conv expects {} input channels, got {}",
+            cg * self.groups,
+            c
+        );
+
+        let (oh, ow) = self.params.output_size((h, w), (kh, kw));
+        let cols = im2col(x, (kh, kw), &self.params);
+        let weight = weight_matrix(weight);
+
+        let (ocg, kg) = (oc / self.groups, cg * kh * kw);
+        let mut out = Array3::<T>::zeros((n, oc, oh * ow));
+        for b in 0..n {
+            for g in 0..self.groups {
+                let w_g = weight.slice(s![g * ocg..(g + 1) * ocg, ..]);
+                let cols_g = cols.slice(s![b, g * kg..(g + 1) * kg, ..]);
+                out.slice_mut(s![b, g * ocg..(g + 1) * ocg, ..])
+                    .assign(&w_g.dot(&cols_g));
+            }
+        }
+
+        out.into_shape((n, oc, oh, ow)).unwrap()
+    }
+
+    /// gradients with respect to the input and the weight
+    fn gradients<T: NdFloat>(
+        &self,
+        grad: &ArrayView4<T>,
+        x: &ArrayView4<T>,
+        weight: &ArrayView4<T>,
+    ) -> (Array4<T>, Array4<T>) {
+        let (n, c, h, w) = x.dim();
+        let (oc, cg, kh, kw) = weight.dim();
+
+        let cols = im2col(x, (kh, kw), &self.params);
+        let weight_mat = weight_matrix(weight);
+        let grad = grad
+            .as_standard_layout()
+            .into_owned()
+            .into_shape((n, oc, cols.shape()[2]))
+            .unwrap();
+
+        let (ocg, kg) = (oc / self.groups, cg * kh * kw);
+        let mut grad_weight = Array2::<T>::zeros((oc, kg));
+        let mut grad_cols = Array3::<T>::zeros(cols.raw_dim());
+        for b in 0..n {
+            for g in 0..self.groups {
+                let grad_g = grad.slice(s![b, g * ocg..(g + 1) * ocg, ..]);
+                let cols_g = cols.slice(s![b, g * kg..(g + 1) * kg, ..]);
+                let w_g = weight_mat.slice(s![g * ocg..(g + 1) * ocg, ..]);
+
+                let mut grad_w_g = grad_weight.slice_mut(s![g * ocg..(g + 1) * ocg, ..]);
+                grad_w_g += &grad_g.dot(&cols_g.t());
+                grad_cols
+                    .slice_mut(s![b, g * kg..(g + 1) * kg, ..])
+                    .assign(&w_g.t().dot(&grad_g));
+            }
+        }
+
+        let grad_x = col2im(&grad_cols, (n, c, h, w), (kh, kw), &self.params);
+        let grad_weight = grad_weight.into_shape((oc, cg, kh, kw)).unwrap();
+
+        (grad_x, grad_weight)
+    }
+}
+
+impl<T> GradFn<T> for Conv2d
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        self.convolve(&view4(x), &view4(y)).into_dyn()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let weight = &right_ref.borrow().data;
+
+        let (grad_x, grad_weight) = self.gradients(&view4(grad), &view4(x), &view4(weight));
+
+        [grad_x.into_dyn(), grad_weight.into_dyn()]
+    }
+}
+
+/// hyper parameters of the 1-D convolutions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConvParams1d {
+    pub stride: usize,
+    pub padding: usize,
+    pub dilation: usize,
+}
+
+impl Default for ConvParams1d {
+    fn default() -> ConvParams1d {
+        ConvParams1d {
+            stride: 1,
+            padding: 0,
+            dilation: 1,
+        }
+    }
+}
+
+impl ConvParams1d {
+    pub fn output_size(&self, input: usize, kernel: usize) -> usize {
+        conv_output_size(input, kernel, self.stride, self.padding, self.dilation)
+    }
+
+    /// same parameters for a 2-D convolution over a height of 1
+    pub fn to_2d(self) -> ConvParams {
+        ConvParams {
+            stride: (1, self.stride),
+            padding: (0, self.padding),
+            dilation: (1, self.dilation),
+        }
+    }
+}
+
+/// `(N, C_in, L)` input convolved with a `(C_out, C_in / groups, k)` weight, computed as a
+/// `Conv2d` over a height of 1
+#[derive(Clone, Copy)]
+pub struct Conv1d {
+    pub conv: Conv2d,
+}
+
+impl<T> GradFn<T> for Conv1d
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        self.conv
+            .convolve(&view4(x), &view4(y))
+            .index_axis_move(Axis(2), 0)
+            .into_dyn()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let weight = &right_ref.borrow().data;
+
+        let (grad_x, grad_weight) = self.conv.gradients(&view4(grad), &view4(x), &view4(weight));
+
+        [
+            grad_x.index_axis_move(Axis(2), 0).into_dyn(),
+            grad_weight.index_axis_move(Axis(2), 0).into_dyn(),
+        ]
+    }
+}
+
+/// panic unless the input and output channels split evenly into `groups`
+/// panic unless the channels split evenly into a positive number of groups
+pub(crate) fn check_groups(in_channels: usize, out_channels: usize, groups: usize) {
+    assert!(
+        groups > 0 && in_channels % groups == 0 && out_channels % groups == 0,
+        "{} input and {} output channels can not be split into {} groups",
+        in_channels,
+        out_channels,
+        groups
+    );
+}
+
+/// 2-D convolution of a `(N, C_in, H, W)` input with a `(C_out, C_in / groups, kh, kw)` weight,
+/// computed with im2col and `dot`
+pub fn conv2d<T: NdFloat>(
+    input: &VariableRef<T>,
+    weight: &VariableRef<T>,
+    params: ConvParams,
+    groups: usize,
+) -> VariableRef<T> {
+    check_groups(
+        input.borrow().data.shape()[1],
+        weight.borrow().data.shape()[0],
+        groups,
+    );
+    let grad_fn = Conv2d { params, groups };
+    grad_fn.subscribe(input, weight, Box::new(grad_fn))
+}
+
+/// 1-D convolution of a `(N, C_in, L)` input with a `(C_out, C_in / groups, k)` weight
+pub fn conv1d<T: NdFloat>(
+    input: &VariableRef<T>,
+    weight: &VariableRef<T>,
+    params: ConvParams1d,
+    groups: usize,
+) -> VariableRef<T> {
+    check_groups(
+        input.borrow().data.shape()[1],
+        weight.borrow().data.shape()[0],
+        groups,
+    );
+    let grad_fn = Conv1d {
+        conv: Conv2d {
+            params: params.to_2d(),
+            groups,
+        },
+    };
+    grad_fn.subscribe(input, weight, Box::new(grad_fn))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::grad_fn::gradcheck::{arange, gradcheck};
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn check_method() {
+        let x = Variable::new(
+            Array::from_shape_vec((1, 1, 3, 3), (1..10).map(|a| a as f64).collect())
+                .unwrap()
+                .into_dyn(),
+        );
+        let weight = Variable::new(
+            Array::from_shape_vec((1, 1, 2, 2), vec![1.0, 0.0, 0.0, -1.0])
+                .unwrap()
+                .into_dyn(),
+        );
+
+        let z = conv2d(&x, &weight, ConvParams::default(), 1);
+        assert_eq!(
+            z.borrow().data,
+            Array::from_elem((1, 1, 2, 2), -4.0).into_dyn()
+        );
+
+        let params = ConvParams {
+            stride: (2, 2),
+            padding: (1, 1),
+            dilation: (1, 1),
+        };
+        let z = conv2d(&x, &weight, params, 1);
+        assert_eq!(
+            z.borrow().data,
+            Array::from_shape_vec((1, 1, 2, 2), vec![-1.0, -3.0, -7.0, -4.0])
+                .unwrap()
+                .into_dyn()
+        );
+    }
+
+    #[test]
+    fn output_size() {
+        let params = ConvParams {
+            stride: (2, 1),
+            padding: (1, 0),
+            dilation: (1, 2),
+        };
+        assert_eq!(params.output_size((7, 7), (3, 3)), (4, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "zero stride")]
+    fn output_size_zero_stride() {
+        conv_output_size(5, 3, 0, 0, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty kernel")]
+    fn output_size_empty_kernel() {
+        conv_output_size(5, 0, 1, 0, 1);
+    }
+
+    #[test]
+    fn check_backward() {
+        let params = ConvParams::default();
+        gradcheck(
+            |v| conv2d(&v[0], &v[1], params, 1),
+            &[arange(&[2, 2, 4, 5]), arange(&[3, 2, 3, 2])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn check_backward_stride_padding_dilation() {
+        let params = ConvParams {
+            stride: (2, 1),
+            padding: (1, 2),
+            dilation: (2, 1),
+        };
+        gradcheck(
+            |v| conv2d(&v[0], &v[1], params, 1),
+            &[arange(&[2, 2, 6, 5]), arange(&[3, 2, 2, 3])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn check_backward_groups() {
+        let params = ConvParams {
+            stride: (1, 2),
+            padding: (1, 1),
+            dilation: (1, 1),
+        };
+        gradcheck(
+            |v| conv2d(&v[0], &v[1], params, 2),
+            &[arange(&[1, 4, 4, 4]), arange(&[6, 2, 3, 3])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "can not be split into 2 groups")]
+    fn uneven_groups() {
+        let x = Variable::new(arange(&[1, 4, 4, 4]));
+        let weight = Variable::new(arange(&[3, 2, 3, 3]));
+        conv2d(&x, &weight, ConvParams::default(), 2);
+    }
+
+    #[test]
+    fn conv1d_check_method() {
+        let x = Variable::new(array!([[1.0, 2.0, 3.0, 4.0, 5.0]]).into_dyn());
+        let weight = Variable::new(array!([[1.0, -1.0]]).into_dyn());
+
+        let params = ConvParams1d {
+            stride: 2,
+            padding: 1,
+            dilation: 1,
+        };
+        assert_eq!(params.output_size(5, 2), 3);
+
+        let z = conv1d(&x, &weight, params, 1);
+        assert_eq!(z.borrow().data, array!([[-1.0, -1.0, -1.0]]).into_dyn());
+    }
+
+    #[test]
+    fn conv1d_check_backward() {
+        let params = ConvParams1d {
+            stride: 2,
+            padding: 2,
+            dilation: 2,
+        };
+        gradcheck(
+            |v| conv1d(&v[0], &v[1], params, 2),
+            &[arange(&[2, 4, 9]), arange(&[4, 2, 3])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+}
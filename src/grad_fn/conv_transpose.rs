@@ -0,0 +1,343 @@
+use ndarray::{s, Array, Array2, Array3, Array4, ArrayView4, Axis, IxDyn, NdFloat};
+
+use crate::grad_fn::conv::{col2im, im2col, view4, weight_matrix, ConvParams, ConvParams1d};
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+/// length of the output of a transposed convolution along one spatial axis
+pub fn conv_transpose_output_size(
+    len: usize,
+    kernel: usize,
+    stride: usize,
+    padding: usize,
+    dilation: usize,
+    output_padding: usize,
+) -> usize {
+    assert!(
+        len > 0 && kernel > 0,
+        "transposed convolution of an empty input or with an empty kernel"
+    );
+    assert!(
+        output_padding < stride,
+        "output_padding must be smaller than the stride"
+    );
+    let full = (len - 1) * stride + dilation * (kernel - 1) + output_padding + 1;
+    assert!(
+        full > 2 * padding,
+        "padding {} removes the whole output of length {}",
+        padding,
+        full
+    );
+    full - 2 * padding
+}
+
+/// `(N, C_in, H, W)` input through the transposed convolution of a
+/// `(C_in, C_out / groups, kh, kw)` weight. The forward pass is the backward pass of `Conv2d`
+/// with respect to its input: columns built with `dot` are folded back with `col2im`
+#[derive(Clone, Copy)]
+pub struct ConvTranspose2d {
+    pub params: ConvParams,
+    pub output_padding: (usize, usize),
+    pub groups: usize,
+}
+
+impl ConvTranspose2d {
+    pub fn output_size(&self, input: (usize, usize), kernel: (usize, usize)) -> (usize, usize) {
+        let p = &self.params;
+        (
+            conv_transpose_output_size(
+                input.0,
+                kernel.0,
+                p.stride.0,
+                p.padding.0,
+                p.dilation.0,
+                self.output_padding.0,
+            ),
+            conv_transpose_output_size(
+                input.1,
+                kernel.1,
+                p.stride.1,
+                p.padding.1,
+                p.dilation.1,
+                self.output_padding.1,
+            ),
+        )
+    }
+
+    fn convolve<T: NdFloat>(&self, x: &ArrayView4<T>, weight: &ArrayView4<T>) -> Array4<T> {
+        let (n, c, h, w) = x.dim();
+        let (ic, ocg, kh, kw) = weight.dim();
+        assert_eq!(
+            c, ic,
+            "conv_transpose expects {} input channels, got {}",
+            ic, c
+        );
+
+        let (oh, ow) = self.output_size((h, w), (kh, kw));
+        let x = x
+            .as_standard_layout()
+            .into_owned()
+            .into_shape((n, c, h * w))
+            .unwrap();
+        let weight = weight_matrix(weight);
+
+        let (icg, kg) = (c / self.groups, ocg * kh * kw);
+        let mut cols = Array3::<T>::zeros((n, self.groups * kg, h * w));
+        for b in 0..n {
+            for g in 0..self.groups {
+                let w_g = weight.slice(s![g * icg..(g + 1) * icg, ..]);
+                let x_g = x.slice(s![b, g * icg..(g + 1) * icg, ..]);
+                cols.slice_mut(s![b, g * kg..(g + 1) * kg, ..])
+                    .assign(&w_g.t().dot(&x_g));
+            }
+        }
+
+        col2im(
+            &cols,
+            (n, self.groups * ocg, oh, ow),
+            (kh, kw),
+            &self.params,
+        )
+    }
+
+    /// gradients with respect to the input and the weight
+    fn gradients<T: NdFloat>(
+        &self,
+        grad: &ArrayView4<T>,
+        x: &ArrayView4<T>,
+        weight: &ArrayView4<T>,
+    ) -> (Array4<T>, Array4<T>) {
+        let (n, c, h, w) = x.dim();
+        let (ic, ocg, kh, kw) = weight.dim();
+
+        let grad_cols = im2col(grad, (kh, kw), &self.params);
+        let x = x
+            .as_standard_layout()
+            .into_owned()
+            .into_shape((n, c, h * w))
+            .unwrap();
+        let weight_mat = weight_matrix(weight);
+
+        let (icg, kg) = (c / self.groups, ocg * kh * kw);
+        let mut grad_x = Array3::<T>::zeros(x.raw_dim());
+        let mut grad_weight = Array2::<T>::zeros((ic, kg));
+        for b in 0..n {
+            for g in 0..self.groups {
+                let grad_g = grad_cols.slice(s![b, g * kg..(g + 1) * kg, ..]);
+                let x_g = x.slice(s![b, g * icg..(g + 1) * icg, ..]);
+                let w_g = weight_mat.slice(s![g * icg..(g + 1) * icg, ..]);
+
+                grad_x
+                    .slice_mut(s![b, g * icg..(g + 1) * icg, ..])
+                    .assign(&w_g.dot(&grad_g));
+                let mut grad_w_g = grad_weight.slice_mut(s![g * icg..(g + 1) * icg, ..]);
+                grad_w_g += &x_g.dot(&grad_g.t());
+            }
+        }
+
+        (
+            grad_x.into_shape((n, c, h, w)).unwrap(),
+            grad_weight.into_shape((ic, ocg, kh, kw)).unwrap(),
+        )
+    }
+}
+
+impl<T> GradFn<T> for ConvTranspose2d
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        self.convolve(&view4(x), &view4(y)).into_dyn()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let weight = &right_ref.borrow().data;
+
+        let (grad_x, grad_weight) = self.gradients(&view4(grad), &view4(x), &view4(weight));
+
+        [grad_x.into_dyn(), grad_weight.into_dyn()]
+    }
+}
+
+/// `(N, C_in, L)` input through the transposed convolution of a `(C_in, C_out / groups, k)`
+/// weight, computed as a `ConvTranspose2d` over a height of 1
+#[derive(Clone, Copy)]
+pub struct ConvTranspose1d {
+    pub conv: ConvTranspose2d,
+}
+
+impl<T> GradFn<T> for ConvTranspose1d
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        self.conv
+            .convolve(&view4(x), &view4(y))
+            .index_axis_move(Axis(2), 0)
+            .into_dyn()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let weight = &right_ref.borrow().data;
+
+        let (grad_x, grad_weight) = self.conv.gradients(&view4(grad), &view4(x), &view4(weight));
+
+        [
+            grad_x.index_axis_move(Axis(2), 0).into_dyn(),
+            grad_weight.index_axis_move(Axis(2), 0).into_dyn(),
+        ]
+    }
+}
+
+/// transposed 2-D convolution of a `(N, C_in, H, W)` input with a
+/// `(C_in, C_out / groups, kh, kw)` weight
+pub fn conv_transpose2d<T: NdFloat>(
+    input: &VariableRef<T>,
+    weight: &VariableRef<T>,
+    params: ConvParams,
+    output_padding: (usize, usize),
+    groups: usize,
+) -> VariableRef<T> {
+    let grad_fn = ConvTranspose2d {
+        params,
+        output_padding,
+        groups,
+    };
+    grad_fn.subscribe(input, weight, Box::new(grad_fn))
+}
+
+/// transposed 1-D convolution of a `(N, C_in, L)` input with a `(C_in, C_out / groups, k)`
+/// weight
+pub fn conv_transpose1d<T: NdFloat>(
+    input: &VariableRef<T>,
+    weight: &VariableRef<T>,
+    params: ConvParams1d,
+    output_padding: usize,
+    groups: usize,
+) -> VariableRef<T> {
+    let grad_fn = ConvTranspose1d {
+        conv: ConvTranspose2d {
+            params: params.to_2d(),
+            output_padding: (0, output_padding),
+            groups,
+        },
+    };
+    grad_fn.subscribe(input, weight, Box::new(grad_fn))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::grad_fn::conv::conv2d;
+    use crate::grad_fn::gradcheck::{arange, gradcheck};
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn output_size() {
+        assert_eq!(conv_transpose_output_size(3, 3, 2, 1, 1, 1), 6);
+        assert_eq!(conv_transpose_output_size(4, 2, 1, 0, 3, 0), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty input")]
+    fn output_size_empty() {
+        conv_transpose_output_size(0, 3, 2, 0, 1, 0);
+    }
+
+    #[test]
+    fn conv_transpose1d_check_method() {
+        let x = Variable::new(array!([[1.0, 2.0, 3.0]]).into_dyn());
+        let weight = Variable::new(array!([[1.0, 10.0]]).into_dyn());
+
+        let params = ConvParams1d {
+            stride: 2,
+            padding: 0,
+            dilation: 1,
+        };
+        let z = conv_transpose1d(&x, &weight, params, 1, 1);
+        assert_eq!(
+            z.borrow().data,
+            array!([[1.0, 10.0, 2.0, 20.0, 3.0, 30.0, 0.0]]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn conv_transpose2d_is_adjoint_of_conv2d() {
+        // <conv(x), y> == <x, conv_transpose(y)> for the same weight
+        let params = ConvParams {
+            stride: (2, 3),
+            padding: (1, 0),
+            dilation: (1, 2),
+        };
+        let x = arange(&[2, 4, 7, 8]);
+        let weight = arange(&[6, 2, 3, 2]);
+
+        let conv = conv2d(
+            &Variable::new(x.clone()),
+            &Variable::new(weight.clone()),
+            params,
+            2,
+        );
+        let y = arange(conv.borrow().data.shape()).mapv(|a| a.cos());
+
+        // output_padding recovers the rows and columns dropped by the stride
+        let transposed = conv_transpose2d(
+            &Variable::new(y.clone()),
+            &Variable::new(weight),
+            params,
+            (0, 2),
+            2,
+        );
+        assert_eq!(transposed.borrow().data.shape(), x.shape());
+
+        let lhs = (&conv.borrow().data * &y).sum();
+        let rhs = (&transposed.borrow().data * &x).sum();
+        assert!((lhs - rhs).abs() < 1e-10);
+    }
+
+    #[test]
+    fn conv_transpose2d_check_backward() {
+        let params = ConvParams {
+            stride: (2, 1),
+            padding: (1, 1),
+            dilation: (1, 2),
+        };
+        gradcheck(
+            |v| conv_transpose2d(&v[0], &v[1], params, (1, 0), 2),
+            &[arange(&[2, 4, 3, 4]), arange(&[4, 3, 3, 2])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn conv_transpose1d_check_backward() {
+        let params = ConvParams1d {
+            stride: 3,
+            padding: 1,
+            dilation: 1,
+        };
+        gradcheck(
+            |v| conv_transpose1d(&v[0], &v[1], params, 2, 1),
+            &[arange(&[2, 3, 5]), arange(&[3, 2, 4])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+}
@@ -2,6 +2,7 @@ use ndarray::{stack, ArrayView, Axis};
 use ndarray::{Array, Ix2, IxDyn, NdFloat};
 use ndarray::{Dimension, RemoveAxis, ShapeError};
 
+use crate::serialize::onnx::Op;
 use crate::variable::GradFn;
 use crate::variable::VariableRef;
 
@@ -24,10 +25,8 @@ where
         left_ref: &'a VariableRef<T>,
         right_ref: &'a VariableRef<T>,
     ) -> [Array<T, IxDyn>; 2] {
-        // Still in dev : need to implement the right_grad as well and to do for the full matrix product
-
-        let ref x = left_ref.borrow().data;
-        let ref y = right_ref.borrow().data;
+        let x = &left_ref.borrow().data;
+        let y = &right_ref.borrow().data;
 
         let x = x.clone().into_dimensionality::<Ix2>().unwrap();
         let y = y.clone().into_dimensionality::<Ix2>().unwrap();
@@ -37,22 +36,20 @@ where
             Dot::backward_ix2(&grad_view.into_dimensionality::<Ix2>().unwrap(), &x, &y);
         [grad_x.into_dyn(), grad_y.into_dyn()]
     }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::binary("MatMul")])
+    }
 }
 
 impl Dot {
+    /// gradients of `x . y`, `grad . y^T` for `x` and `x^T . grad` for `y`
     fn backward_ix2<T: NdFloat>(
         grad: &ArrayView<T, Ix2>,
         x: &Array<T, Ix2>,
         y: &Array<T, Ix2>,
     ) -> [Array<T, Ix2>; 2] {
-        let grad_x = y.sum_axis(Axis(1));
-        let grad_x = repeat(Axis(0), &grad_x, x.shape()[0]).unwrap();
-
-        let grad_y = (x * grad).sum_axis(Axis(0));
-        let ax = if y.shape()[1] == 1 { 1 } else { 0 };
-        let grad_y = repeat(Axis(ax), &grad_y, y.shape()[1]).unwrap();
-
-        [grad * grad_x, grad_y]
+        [grad.dot(&y.t()), x.t().dot(grad)]
     }
 }
 
@@ -121,7 +118,27 @@ mod tests {
         );
         assert_eq!(
             y.borrow().get_grad_f(),
-            array!([4.0, 6.0], [4.0, 6.0]).into_dyn()
+            array!([4.0, 4.0], [6.0, 6.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn dot_check_backward_weighted() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+        let y = Variable::new(array!([1.0, 0.0], [0.0, 1.0], [1.0, 1.0]).into_dyn());
+        let weight = Variable::new(array!([1.0, 2.0], [3.0, 4.0]).into_dyn());
+
+        let mut z = (&x.dot(&y) * &weight).sum();
+        z.backward();
+
+        // weight . y^T and x^T . weight
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([1.0, 2.0, 3.0], [3.0, 4.0, 7.0]).into_dyn()
+        );
+        assert_eq!(
+            y.borrow().get_grad_f(),
+            array!([13.0, 18.0], [17.0, 24.0], [21.0, 30.0]).into_dyn()
         );
     }
 
@@ -0,0 +1,102 @@
+use ndarray::{Array, IxDyn, NdFloat};
+use rand::Rng;
+
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+/// element-wise product with a fixed mask, kept for the backward pass
+pub struct Dropout<T> {
+    pub mask: Array<T, IxDyn>,
+}
+
+impl<T> GradFn<T> for Dropout<T>
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x * &self.mask
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        _left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let grad = grad * &self.mask;
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [grad, zero]
+    }
+}
+
+/// inverted dropout mask of the given shape: each element is zero with probability `p` and
+/// `1 / (1 - p)` otherwise, so that the expectation of the masked input is unchanged
+pub fn dropout_mask<T: NdFloat, R: Rng>(shape: &[usize], p: f64, rng: &mut R) -> Array<T, IxDyn> {
+    assert!(
+        (0. ..=1.).contains(&p),
+        "dropout probability must be in [0, 1]"
+    );
+    let scale = if p < 1. {
+        T::from(1. / (1. - p)).unwrap()
+    } else {
+        T::zero()
+    };
+    Array::from_shape_simple_fn(shape, || {
+        if rng.gen::<f64>() < p {
+            T::zero()
+        } else {
+            scale
+        }
+    })
+}
+
+/// multiply `input` by `mask`, which must be broadcastable to its shape
+pub fn dropout<T: NdFloat>(input: &VariableRef<T>, mask: &Array<T, IxDyn>) -> VariableRef<T> {
+    let shape = input.borrow().data.raw_dim();
+    let mask = mask
+        .broadcast(shape)
+        .expect("the dropout mask does not broadcast to the input")
+        .to_owned();
+
+    let grad_fn = Dropout { mask: mask.clone() };
+    grad_fn.subscribe(input, input, Box::new(Dropout { mask }))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::variable::Variable;
+    use ndarray::array;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn check_method() {
+        let x = Variable::new(array!([1.0, 2.0], [3.0, 4.0]).into_dyn());
+        let mask = array!([2.0], [0.0]).into_dyn();
+
+        let mut z = dropout(&x, &mask);
+        assert_eq!(z.borrow().data, array!([2.0, 4.0], [0.0, 0.0]).into_dyn());
+
+        z.sum().backward();
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([2.0, 2.0], [0.0, 0.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn mask_statistics() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mask: Array<f64, IxDyn> = dropout_mask(&[100, 100], 0.25, &mut rng);
+
+        let dropped = mask.iter().filter(|&&m| m == 0.).count() as f64 / 1e4;
+        assert!((dropped - 0.25).abs() < 0.02);
+        assert!(mask.iter().all(|&m| m == 0. || (m - 4. / 3.).abs() < 1e-12));
+
+        let mask: Array<f64, IxDyn> = dropout_mask(&[3], 1., &mut rng);
+        assert_eq!(mask, array!(0.0, 0.0, 0.0).into_dyn());
+    }
+}
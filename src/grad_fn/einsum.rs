@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+
+use ndarray::{Array, Array3, Axis, IxDyn, NdFloat};
+
+use crate::serialize::onnx::{Attribute, Op};
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+/// parsed einstein summation spec such as `"bij,bjk->bik"`
+pub struct EinsumSpec {
+    pub inputs: Vec<Vec<char>>,
+    pub output: Vec<char>,
+}
+
+impl EinsumSpec {
+    /// parse a spec, without `->` the output holds the subscripts appearing only once, in
+    /// alphabetical order like numpy
+    pub fn parse(spec: &str) -> EinsumSpec {
+        let spec: String = spec.chars().filter(|c| !c.is_whitespace()).collect();
+        assert!(
+            spec.chars()
+                .all(|c| c.is_ascii_alphabetic() || ",->".contains(c)),
+            "einsum spec {} can only contain letters, ',' and '->'",
+            spec
+        );
+
+        let (lhs, output) = match spec.split_once("->") {
+            Some((lhs, rhs)) => (lhs, Some(rhs.chars().collect::<Vec<char>>())),
+            None => (spec.as_str(), None),
+        };
+        let inputs: Vec<Vec<char>> = lhs.split(',').map(|s| s.chars().collect()).collect();
+
+        let output = output.unwrap_or_else(|| {
+            let mut once: Vec<char> = inputs
+                .iter()
+                .flatten()
+                .copied()
+                .filter(|c| inputs.iter().flatten().filter(|d| *d == c).count() == 1)
+                .collect();
+            once.sort_unstable();
+            once
+        });
+
+        for (i, c) in output.iter().enumerate() {
+            assert!(
+                !output[..i].contains(c),
+                "subscript {} appears twice in the output of {}",
+                c,
+                spec
+            );
+            assert!(
+                inputs.iter().flatten().any(|d| d == c),
+                "output subscript {} does not appear in the inputs of {}",
+                c,
+                spec
+            );
+        }
+
+        EinsumSpec { inputs, output }
+    }
+}
+
+fn has_repeated(idx: &[char]) -> bool {
+    idx.iter().enumerate().any(|(i, c)| idx[..i].contains(c))
+}
+
+fn record_sizes<T: NdFloat>(sizes: &mut HashMap<char, usize>, x: &Array<T, IxDyn>, idx: &[char]) {
+    assert_eq!(
+        x.ndim(),
+        idx.len(),
+        "einsum subscripts {:?} do not match an operand of shape {:?}",
+        idx,
+        x.shape()
+    );
+    for (c, &len) in idx.iter().zip(x.shape()) {
+        let known = *sizes.entry(*c).or_insert(len);
+        assert_eq!(known, len, "inconsistent sizes for subscript {}", c);
+    }
+}
+
+/// sum `x` over the subscripts that are not in `keep`
+fn sum_out<T: NdFloat>(
+    x: &Array<T, IxDyn>,
+    idx: &[char],
+    keep: &[char],
+) -> (Array<T, IxDyn>, Vec<char>) {
+    let mut x = x.clone();
+    let mut idx = idx.to_vec();
+
+    for i in (0..idx.len()).rev() {
+        if !keep.contains(&idx[i]) {
+            x = x.sum_axis(Axis(i));
+            idx.remove(i);
+        }
+    }
+    (x, idx)
+}
+
+/// move the axes of `x` so that they follow the `order` subscripts
+fn permute<T: NdFloat>(x: Array<T, IxDyn>, idx: &[char], order: &[char]) -> Array<T, IxDyn> {
+    let axes: Vec<usize> = order
+        .iter()
+        .map(|c| idx.iter().position(|d| d == c).unwrap())
+        .collect();
+    x.permuted_axes(axes)
+}
+
+/// reshape `x` whose axes follow `groups` into one axis per group
+fn group_axes<T: NdFloat>(
+    x: Array<T, IxDyn>,
+    groups: &[&[char]],
+    sizes: &HashMap<char, usize>,
+) -> Array3<T> {
+    let shape: Vec<usize> = groups
+        .iter()
+        .map(|g| g.iter().map(|c| sizes[c]).product())
+        .collect();
+    x.as_standard_layout()
+        .into_owned()
+        .into_shape((shape[0], shape[1], shape[2]))
+        .unwrap()
+}
+
+/// evaluate a one or two operands einsum whose output subscripts may also contain subscripts
+/// absent from the operands (the result is then broadcast along them, using `sizes`) or
+/// repeated ones (the result is then written on the diagonal), as needed by the backward pass
+pub fn einsum_arrays<T: NdFloat>(
+    operands: &[(&Array<T, IxDyn>, &[char])],
+    output: &[char],
+    sizes: &HashMap<char, usize>,
+) -> Array<T, IxDyn> {
+    if has_repeated(output) || operands.iter().any(|(_, idx)| has_repeated(idx)) {
+        return einsum_naive(operands, output, sizes);
+    }
+
+    let present: Vec<char> = output
+        .iter()
+        .copied()
+        .filter(|c| operands.iter().any(|(_, idx)| idx.contains(c)))
+        .collect();
+
+    let out = match operands {
+        [(x, x_idx)] => {
+            let (x, x_idx) = sum_out(x, x_idx, &present);
+            permute(x, &x_idx, &present)
+        }
+        [(x, x_idx), (y, y_idx)] => {
+            let keep_x: Vec<char> = x_idx
+                .iter()
+                .copied()
+                .filter(|c| y_idx.contains(c) || present.contains(c))
+                .collect();
+            let keep_y: Vec<char> = y_idx
+                .iter()
+                .copied()
+                .filter(|c| x_idx.contains(c) || present.contains(c))
+                .collect();
+            let (x, x_idx) = sum_out(x, x_idx, &keep_x);
+            let (y, y_idx) = sum_out(y, y_idx, &keep_y);
+
+            let batch: Vec<char> = present
+                .iter()
+                .copied()
+                .filter(|c| x_idx.contains(c) && y_idx.contains(c))
+                .collect();
+            let contract: Vec<char> = x_idx
+                .iter()
+                .copied()
+                .filter(|c| y_idx.contains(c) && !present.contains(c))
+                .collect();
+            let free_x: Vec<char> = x_idx
+                .iter()
+                .copied()
+                .filter(|c| !y_idx.contains(c))
+                .collect();
+            let free_y: Vec<char> = y_idx
+                .iter()
+                .copied()
+                .filter(|c| !x_idx.contains(c))
+                .collect();
+
+            let x_order = [batch.clone(), free_x.clone(), contract.clone()].concat();
+            let y_order = [batch.clone(), contract.clone(), free_y.clone()].concat();
+            let x = group_axes(
+                permute(x, &x_idx, &x_order),
+                &[&batch, &free_x, &contract],
+                sizes,
+            );
+            let y = group_axes(
+                permute(y, &y_idx, &y_order),
+                &[&batch, &contract, &free_y],
+                sizes,
+            );
+
+            // batched matrix product, one dot per batch element
+            let mut prod = Array3::<T>::zeros((x.shape()[0], x.shape()[1], y.shape()[2]));
+            for (mut p, (a, b)) in prod
+                .outer_iter_mut()
+                .zip(x.outer_iter().zip(y.outer_iter()))
+            {
+                p.assign(&a.dot(&b));
+            }
+
+            let prod_idx = [batch, free_x, free_y].concat();
+            let prod_shape: Vec<usize> = prod_idx.iter().map(|c| sizes[c]).collect();
+            let prod = prod.into_shape(prod_shape).unwrap().into_dyn();
+            permute(prod, &prod_idx, &present)
+        }
+        _ => panic!("einsum_arrays expects one or two operands"),
+    };
+
+    if present.len() == output.len() {
+        return out;
+    }
+
+    // broadcast along the output subscripts absent from the operands
+    let mut out = out;
+    for (i, c) in output.iter().enumerate() {
+        if !present.contains(c) {
+            out = out.insert_axis(Axis(i));
+        }
+    }
+    let shape: Vec<usize> = output.iter().map(|c| sizes[c]).collect();
+    out.broadcast(shape).unwrap().to_owned()
+}
+
+/// reference evaluation looping over every assignment of the subscripts, used when a subscript
+/// is repeated (diagonals, traces)
+fn einsum_naive<T: NdFloat>(
+    operands: &[(&Array<T, IxDyn>, &[char])],
+    output: &[char],
+    sizes: &HashMap<char, usize>,
+) -> Array<T, IxDyn> {
+    let mut all: Vec<char> = vec![];
+    for c in operands
+        .iter()
+        .flat_map(|(_, idx)| idx.iter())
+        .chain(output)
+    {
+        if !all.contains(c) {
+            all.push(*c);
+        }
+    }
+    let lens: Vec<usize> = all.iter().map(|c| sizes[c]).collect();
+    let position = |idx: &[char], assignment: &[usize]| -> IxDyn {
+        let pos: Vec<usize> = idx
+            .iter()
+            .map(|c| assignment[all.iter().position(|d| d == c).unwrap()])
+            .collect();
+        IxDyn(&pos)
+    };
+
+    let out_shape: Vec<usize> = output.iter().map(|c| sizes[c]).collect();
+    let mut out = Array::<T, IxDyn>::zeros(out_shape);
+    if lens.contains(&0) {
+        return out;
+    }
+
+    let mut assignment = vec![0; all.len()];
+    loop {
+        let mut prod = T::one();
+        for (x, idx) in operands.iter() {
+            prod *= x[position(idx, &assignment)];
+        }
+        out[position(output, &assignment)] += prod;
+
+        // odometer increment
+        let mut axis = all.len();
+        loop {
+            if axis == 0 {
+                return out;
+            }
+            axis -= 1;
+            assignment[axis] += 1;
+            if assignment[axis] < lens[axis] {
+                break;
+            }
+            assignment[axis] = 0;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Einsum {
+    pub lhs: Vec<char>,
+    /// `None` for an einsum over a single operand
+    pub rhs: Option<Vec<char>>,
+    pub out: Vec<char>,
+}
+
+impl Einsum {
+    fn sizes<T: NdFloat>(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> HashMap<char, usize> {
+        let mut sizes = HashMap::new();
+        record_sizes(&mut sizes, x, &self.lhs);
+        if let Some(rhs) = &self.rhs {
+            record_sizes(&mut sizes, y, rhs);
+        }
+        sizes
+    }
+}
+
+impl<T> GradFn<T> for Einsum
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let sizes = self.sizes(x, y);
+
+        match &self.rhs {
+            Some(rhs) => einsum_arrays(&[(x, &self.lhs), (y, rhs)], &self.out, &sizes),
+            None => einsum_arrays(&[(x, &self.lhs)], &self.out, &sizes),
+        }
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let y = &right_ref.borrow().data;
+        let sizes = self.sizes(x, y);
+
+        // the gradient of an operand is the einsum of the output gradient with the other
+        // operands, back to the subscripts of that operand
+        match &self.rhs {
+            Some(rhs) => [
+                einsum_arrays(&[(grad, &self.out), (y, rhs)], &self.lhs, &sizes),
+                einsum_arrays(&[(grad, &self.out), (x, &self.lhs)], rhs, &sizes),
+            ],
+            None => {
+                let grad_x = einsum_arrays(&[(grad, &self.out)], &self.lhs, &sizes);
+                let zero = Array::<T, IxDyn>::zeros(grad_x.raw_dim());
+                [grad_x, zero]
+            }
+        }
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        let mut equation: String = self.lhs.iter().collect();
+        if let Some(rhs) = &self.rhs {
+            equation.push(',');
+            equation.extend(rhs.iter());
+        }
+        equation.push_str("->");
+        equation.extend(self.out.iter());
+
+        let op = match self.rhs {
+            Some(_) => Op::binary("Einsum"),
+            None => Op::unary("Einsum"),
+        };
+        Some(vec![
+            op.with_attribute("equation", Attribute::String(equation))
+        ])
+    }
+}
+
+/// einstein summation over variables, e.g. `einsum("bij,bjk->bik", &[a, b])`.
+///
+/// The operands are contracted two by two from left to right, each contraction being a node of
+/// the graph whose products are computed with `dot`.
+pub fn einsum<T: NdFloat>(spec: &str, operands: &[VariableRef<T>]) -> VariableRef<T> {
+    let spec = EinsumSpec::parse(spec);
+    assert_eq!(
+        spec.inputs.len(),
+        operands.len(),
+        "einsum spec expects {} operands, got {}",
+        spec.inputs.len(),
+        operands.len()
+    );
+
+    if operands.len() == 1 {
+        let grad_fn = Einsum {
+            lhs: spec.inputs[0].clone(),
+            rhs: None,
+            out: spec.output.clone(),
+        };
+        return grad_fn.subscribe(&operands[0], &operands[0], Box::new(grad_fn.clone()));
+    }
+
+    let mut acc = operands[0].clone();
+    let mut acc_idx = spec.inputs[0].clone();
+    for i in 1..operands.len() {
+        let rhs = spec.inputs[i].clone();
+
+        // keep the subscripts still needed by the next operands or by the output
+        let out = if i == operands.len() - 1 {
+            spec.output.clone()
+        } else {
+            let mut out: Vec<char> = vec![];
+            for c in acc_idx.iter().chain(rhs.iter()) {
+                let needed = spec.output.contains(c)
+                    || spec.inputs[i + 1..].iter().flatten().any(|d| d == c);
+                if needed && !out.contains(c) {
+                    out.push(*c);
+                }
+            }
+            out
+        };
+
+        let grad_fn = Einsum {
+            lhs: acc_idx,
+            rhs: Some(rhs),
+            out,
+        };
+        acc = grad_fn.subscribe(&acc, &operands[i], Box::new(grad_fn.clone()));
+        acc_idx = grad_fn.out;
+    }
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::grad_fn::gradcheck::{arange, gradcheck};
+    use crate::variable::Variable;
+    use ndarray::{arr0, array};
+
+    fn check_gradients(spec: &str, shapes: &[&[usize]]) {
+        let inputs: Vec<Array<f64, IxDyn>> = shapes.iter().map(|s| arange(s)).collect();
+        if let Err(msg) = gradcheck(|vars| einsum(spec, vars), &inputs, 1e-6, 1e-6) {
+            panic!("{}: {}", spec, msg);
+        }
+    }
+
+    #[test]
+    fn parse_implicit_output() {
+        let spec = EinsumSpec::parse("ij, jk");
+        assert_eq!(spec.inputs, vec![vec!['i', 'j'], vec!['j', 'k']]);
+        assert_eq!(spec.output, vec!['i', 'k']);
+    }
+
+    #[test]
+    fn check_method() {
+        let a = Variable::new(array!([1.0, 2.0], [3.0, 4.0]).into_dyn());
+        let b = Variable::new(array!([5.0, 6.0], [7.0, 8.0]).into_dyn());
+
+        let prod = einsum("ij,jk->ik", &[a.clone(), b.clone()]);
+        assert_eq!(
+            prod.borrow().data,
+            array!([19.0, 22.0], [43.0, 50.0]).into_dyn()
+        );
+
+        let transposed = einsum("ij->ji", std::slice::from_ref(&a));
+        assert_eq!(
+            transposed.borrow().data,
+            array!([1.0, 3.0], [2.0, 4.0]).into_dyn()
+        );
+
+        let trace = einsum("ii->", std::slice::from_ref(&a));
+        assert_eq!(trace.borrow().data, arr0(5.0).into_dyn());
+
+        let outer = einsum(
+            "i,j->ij",
+            &[
+                Variable::new(array!(1.0, 2.0).into_dyn()),
+                Variable::new(array!(3.0, 4.0, 5.0).into_dyn()),
+            ],
+        );
+        assert_eq!(
+            outer.borrow().data,
+            array!([3.0, 4.0, 5.0], [6.0, 8.0, 10.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn matches_dot() {
+        let mut a = Variable::new(arange(&[3, 4]));
+        let b = Variable::new(arange(&[4, 2]));
+
+        let ein = einsum("ij,jk->ik", &[a.clone(), b.clone()]);
+        assert_eq!(ein.borrow().data, a.dot(&b).borrow().data);
+    }
+
+    #[test]
+    fn check_backward_batched_matmul() {
+        check_gradients("bij,bjk->bik", &[&[2, 3, 4], &[2, 4, 5]]);
+    }
+
+    #[test]
+    fn check_backward_unary() {
+        check_gradients("ijk->kj", &[&[2, 3, 4]]);
+        check_gradients("ii->i", &[&[3, 3]]);
+        check_gradients("ii", &[&[3, 3]]);
+    }
+
+    #[test]
+    fn check_backward_pairs() {
+        check_gradients("ij,j->i", &[&[3, 4], &[4]]);
+        check_gradients("i,j->ij", &[&[3], &[4]]);
+        check_gradients("ij,ij->", &[&[3, 4], &[3, 4]]);
+        check_gradients("bhqd,bhkd->bhqk", &[&[2, 2, 3, 4], &[2, 2, 5, 4]]);
+        check_gradients("ijk,jl->il", &[&[2, 3, 4], &[3, 5]]);
+        check_gradients("ii,ij->j", &[&[3, 3], &[3, 2]]);
+    }
+
+    #[test]
+    fn check_backward_three_operands() {
+        check_gradients("ij,jk,kl->il", &[&[2, 3], &[3, 4], &[4, 2]]);
+        check_gradients("i,ij,j", &[&[3], &[3, 4], &[4]]);
+    }
+}
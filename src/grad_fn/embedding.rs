@@ -0,0 +1,214 @@
+use ndarray::{Array, Axis, Dimension, IxDyn, NdFloat};
+
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+/// lookup of the rows of a `(num_embeddings, dim)` weight. The embedding axis is inserted
+/// before the last axis of the indices: a `(batch,)` array gives a `(dim, batch)` output and a
+/// `(seq, batch)` one a `(seq, dim, batch)` output
+#[derive(Clone)]
+pub struct Embedding {
+    pub indices: Array<usize, IxDyn>,
+    /// row which never receives gradient
+    pub padding_idx: Option<usize>,
+}
+
+impl Embedding {
+    fn output_shape(&self, dim: usize) -> Vec<usize> {
+        let mut shape = self.indices.shape().to_vec();
+        let batch_axis = shape.len().saturating_sub(1);
+        shape.insert(batch_axis, dim);
+        shape
+    }
+
+    /// position of the index read by the output element at `pos`, and its feature
+    fn lookup(&self, pos: &IxDyn) -> (IxDyn, usize) {
+        let ndim = pos.ndim();
+        let feature_axis = ndim.saturating_sub(2);
+
+        let mut idx: Vec<usize> = pos.slice().to_vec();
+        let feature = idx.remove(feature_axis);
+        (IxDyn(&idx), feature)
+    }
+}
+
+impl<T> GradFn<T> for Embedding
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let (num_embeddings, dim) = (x.shape()[0], x.shape()[1]);
+        if let Some(&bad) = self.indices.iter().find(|&&i| i >= num_embeddings) {
+            panic!(
+                "index {} out of range for {} embeddings",
+                bad, num_embeddings
+            );
+        }
+
+        Array::from_shape_fn(self.output_shape(dim), |pos| {
+            let (idx, feature) = self.lookup(&pos);
+            x[[self.indices[idx], feature]]
+        })
+    }
+
+    /// the gradient of a leaf weight is added in place to the rows looked up, the other rows
+    /// of its grad are left alone. A computed weight gets a dense gradient, as any other root
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let no_grad = || Array::<T, IxDyn>::zeros(IxDyn(&[]));
+        let add_rows = |weight_grad: &mut Array<T, IxDyn>| {
+            for (pos, &g) in grad.indexed_iter() {
+                let (idx, feature) = self.lookup(&pos);
+                let row = self.indices[idx];
+                if Some(row) != self.padding_idx {
+                    weight_grad[[row, feature]] += g;
+                }
+            }
+        };
+
+        if left_ref.borrow().is_leaf() {
+            let mut weight = left_ref.clone();
+            if let Some(weight_grad) = &mut weight.borrow_mut().grad {
+                add_rows(weight_grad);
+            }
+            return [no_grad(), no_grad()];
+        }
+
+        let mut new_grad = Array::<T, IxDyn>::zeros(left_ref.borrow().data.raw_dim());
+        add_rows(&mut new_grad);
+        // the right root is the weight again
+        [new_grad, no_grad()]
+    }
+}
+
+/// rows of `weight` selected by `indices`, the gradient is added only to the selected rows
+pub fn embedding<T: NdFloat>(
+    weight: &VariableRef<T>,
+    indices: &Array<usize, IxDyn>,
+    padding_idx: Option<usize>,
+) -> VariableRef<T> {
+    let grad_fn = Embedding {
+        indices: indices.clone(),
+        padding_idx,
+    };
+    grad_fn.subscribe(weight, weight, Box::new(grad_fn.clone()))
+}
+
+/// scale in place the rows of a `(num_embeddings, dim)` array selected by `indices` whose
+/// euclidean norm is larger than `max_norm` down to that norm
+pub fn renorm_rows<T: NdFloat>(
+    weight: &mut Array<T, IxDyn>,
+    indices: &Array<usize, IxDyn>,
+    max_norm: T,
+) {
+    let mut rows: Vec<usize> = indices.iter().copied().collect();
+    rows.sort_unstable();
+    rows.dedup();
+
+    for row in rows {
+        let mut lane = weight.index_axis_mut(Axis(0), row);
+        let norm = lane.fold(T::zero(), |acc, &a| acc + a * a).sqrt();
+        if norm > max_norm {
+            // same small offset as pytorch to stay under the bound
+            let scale = max_norm / (norm + T::from(1e-7).unwrap());
+            lane.mapv_inplace(|a| a * scale);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn check_method() {
+        let weight = Variable::new(array!([0.0, 1.0], [2.0, 3.0], [4.0, 5.0]).into_dyn());
+
+        let z = embedding(&weight, &array!(2, 0, 2).into_dyn(), None);
+        assert_eq!(
+            z.borrow().data,
+            array!([4.0, 0.0, 4.0], [5.0, 1.0, 5.0]).into_dyn()
+        );
+
+        let z = embedding(&weight, &array!([1, 2], [0, 1]).into_dyn(), None);
+        assert_eq!(z.borrow().data.shape(), &[2, 2, 2]);
+        assert_eq!(
+            z.borrow().data.index_axis(Axis(0), 1),
+            array!([0.0, 2.0], [1.0, 3.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn check_backward() {
+        let weight = Variable::new(Array::<f64, _>::zeros((4, 2)).into_dyn());
+        let indices = array!([1, 3, 1], [0, 0, 1]).into_dyn();
+
+        let out = embedding(&weight, &indices, Some(0));
+        let scale = Variable::new(array!([[1.0, 2.0, 3.0]], [[4.0, 5.0, 6.0]]).into_dyn());
+        let mut loss = (&out * &scale).sum();
+        loss.backward();
+
+        // row 1 is read three times, row 0 is the padding and row 2 is never read
+        assert_eq!(
+            weight.borrow().get_grad_f(),
+            array!(
+                [0.0, 0.0],
+                [1.0 + 3.0 + 6.0, 1.0 + 3.0 + 6.0],
+                [0.0, 0.0],
+                [2.0, 2.0]
+            )
+            .into_dyn()
+        );
+    }
+
+    #[test]
+    fn check_backward_leaves_other_rows() {
+        let weight = Variable::new(Array::<f64, _>::zeros((3, 2)).into_dyn());
+        // -0.0 + 0.0 is +0.0, the sign shows whether a row of the grad has been written
+        weight.clone().borrow_mut().grad = Some(Array::from_elem((3, 2), -0.0).into_dyn());
+
+        let mut loss = embedding(&weight, &array!(1, 1).into_dyn(), None).sum();
+        loss.backward();
+
+        let grad = weight.borrow().get_grad_f();
+        assert_eq!(grad.index_axis(Axis(0), 1), array!(2.0, 2.0).into_dyn());
+        for &row in [0, 2].iter() {
+            let lane = grad.index_axis(Axis(0), row);
+            assert!(lane.iter().all(|g| g.is_sign_negative()));
+        }
+    }
+
+    #[test]
+    fn check_backward_computed_weight() {
+        let weight = Variable::new(array!([0.0, 1.0], [2.0, 3.0]).into_dyn());
+        let scaled = &weight * &Variable::new(array!(2.0, 3.0).into_dyn());
+
+        let mut loss = embedding(&scaled, &array!(1, 1).into_dyn(), None).sum();
+        loss.backward();
+
+        assert_eq!(
+            weight.borrow().get_grad_f(),
+            array!([0.0, 0.0], [4.0, 6.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn renorm_check_method() {
+        let mut weight = array!([3.0, 4.0], [0.3, 0.4], [6.0, 8.0_f64]).into_dyn();
+
+        renorm_rows(&mut weight, &array!(0, 1, 0).into_dyn(), 1.0);
+
+        assert!((weight[[0, 0]] - 0.6).abs() < 1e-6);
+        assert!((weight[[0, 1]] - 0.8).abs() < 1e-6);
+        // rows under the bound or not looked up are left untouched
+        assert_eq!(weight[[1, 0]], 0.3);
+        assert_eq!(weight[[2, 1]], 8.0);
+    }
+}
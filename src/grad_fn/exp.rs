@@ -1,5 +1,6 @@
 use ndarray::{Array, IxDyn, NdFloat};
 
+use crate::serialize::onnx::{Op, Tensor};
 use crate::variable::GradFn;
 use crate::variable::VariableRef;
 
@@ -27,6 +28,10 @@ where
 
         [grad, zero]
     }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::unary("Exp")])
+    }
 }
 
 impl<T> VariableRef<T>
@@ -37,6 +42,51 @@ where
         let grad_fn = Exp {};
         grad_fn.subscribe(self, self, Box::new(Exp {}))
     }
+
+    pub fn powf(&mut self, exponent: T) -> VariableRef<T> {
+        let grad_fn = Powf { exponent };
+        grad_fn.subscribe(self, self, Box::new(Powf { exponent }))
+    }
+
+    pub fn sqrt(&mut self) -> VariableRef<T> {
+        self.powf(T::from(0.5).unwrap())
+    }
+}
+
+/// element-wise power with a constant exponent
+pub struct Powf<T> {
+    pub exponent: T,
+}
+
+impl<T> GradFn<T> for Powf<T>
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x.mapv(|a| a.powf(self.exponent))
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let data = &left_ref.borrow().data;
+        let exponent = self.exponent;
+
+        let grad = grad * &data.mapv(|a| exponent * a.powf(exponent - T::one()));
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [grad, zero]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        let exponent = Array::from_elem(IxDyn(&[]), self.exponent);
+        Some(vec![
+            Op::unary("Pow").with_constant(Tensor::from_array("", &exponent))
+        ])
+    }
 }
 
 #[cfg(test)]
@@ -71,4 +121,18 @@ mod tests {
         assert_eq!(z.borrow().data, res.into_dyn());
         assert_eq!(z.borrow().data, x.borrow().get_grad_f());
     }
+
+    #[test]
+    fn powf_check_backward() {
+        let mut x = Variable::new(array!([4.0], [9.0_f64]).into_dyn());
+
+        let mut z = x.sqrt();
+        assert_eq!(z.borrow().data, array!([2.0], [3.0]).into_dyn());
+        z.backward();
+
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([0.25], [1.0 / 6.0]).into_dyn()
+        );
+    }
 }
@@ -0,0 +1,89 @@
+use ndarray::{Array, IxDyn};
+
+use crate::variable::{Variable, VariableRef};
+
+/// deterministic weights used to project the output of the checked function on a scalar, so
+/// that gradients which would be mixed up by a plain sum are told apart
+fn projection(shape: &[usize]) -> Array<f64, IxDyn> {
+    let len = shape.iter().product::<usize>();
+    Array::from_shape_vec(
+        shape,
+        (0..len).map(|i| 1. + (i as f64 * 0.7).sin()).collect(),
+    )
+    .unwrap()
+}
+
+/// deterministic and irregular test input, without the ties that would make max pooling or
+/// relu non differentiable at the checked point
+#[cfg(test)]
+pub(crate) fn arange(shape: &[usize]) -> Array<f64, IxDyn> {
+    let len = shape.iter().product::<usize>();
+    Array::from_shape_vec(shape, (0..len).map(|i| (i as f64 * 0.37).sin()).collect()).unwrap()
+}
+
+/// compare the gradients computed by the autograd for the function `f` with central finite
+/// differences, at the point given by `inputs`. Return an error describing the first mismatch.
+pub fn gradcheck<F>(f: F, inputs: &[Array<f64, IxDyn>], eps: f64, tol: f64) -> Result<(), String>
+where
+    F: Fn(&[VariableRef<f64>]) -> VariableRef<f64>,
+{
+    let vars: Vec<VariableRef<f64>> = inputs.iter().map(|x| Variable::new(x.clone())).collect();
+    let out = f(&vars);
+    let weight = projection(out.borrow().data.shape());
+
+    let mut loss = &out * &Variable::new_no_retain_grad(weight.clone());
+    loss = loss.sum();
+    loss.backward();
+
+    let eval = |inputs: &[Array<f64, IxDyn>]| -> f64 {
+        let vars: Vec<VariableRef<f64>> = inputs
+            .iter()
+            .map(|x| Variable::new_no_retain_grad(x.clone()))
+            .collect();
+        let out = f(&vars);
+        let value = (&out.borrow().data * &weight).sum();
+        value
+    };
+
+    for (k, var) in vars.iter().enumerate() {
+        let grad = var.borrow().get_grad_f();
+        for (pos, &g) in grad.indexed_iter() {
+            let mut plus = inputs.to_vec();
+            plus[k][&pos] += eps;
+            let mut minus = inputs.to_vec();
+            minus[k][&pos] -= eps;
+
+            let numeric = (eval(&plus) - eval(&minus)) / (2. * eps);
+            if (numeric - g).abs() > tol * (1. + numeric.abs()) {
+                return Err(format!(
+                    "input {} at {:?}: autograd gives {} but finite differences give {}",
+                    k, pos, g, numeric
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn detects_wrong_gradient() {
+        let x = array!([1.0, 2.0], [3.0, 4.0]).into_dyn();
+
+        assert!(gradcheck(|v| &v[0] * &v[0], std::slice::from_ref(&x), 1e-6, 1e-6).is_ok());
+
+        // the shift hidden in the data of an identity node is not seen by the autograd
+        let wrong = |v: &[VariableRef<f64>]| {
+            let mut copy = v[0].clone().identity();
+            let shifted = copy.borrow().data.mapv(|a| a * a);
+            copy.borrow_mut().data = shifted;
+            copy
+        };
+        assert!(gradcheck(wrong, &[x], 1e-6, 1e-6).is_err());
+    }
+}
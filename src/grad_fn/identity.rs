@@ -1,5 +1,6 @@
 use ndarray::{Array, IxDyn, NdFloat};
 
+use crate::serialize::onnx::Op;
 use crate::variable::GradFn;
 use crate::variable::VariableRef;
 
@@ -24,6 +25,10 @@ where
 
         [grad, zero]
     }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::unary("Identity")])
+    }
 }
 
 impl<T> VariableRef<T>
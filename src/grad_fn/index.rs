@@ -0,0 +1,495 @@
+use ndarray::{Array, Axis, Dimension, IxDyn, NdFloat, SliceArg, SliceInfoElem, Zip};
+
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+// every op of this file routes the incoming gradient back into a zero-initialized
+// array of the parent shape
+
+#[derive(Clone)]
+pub struct Slice {
+    pub info: Vec<SliceInfoElem>,
+}
+
+impl<T> GradFn<T> for Slice
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x.slice(self.info.as_slice()).to_owned()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let data = &left_ref.borrow().data;
+
+        let mut new_grad = Array::<T, IxDyn>::zeros(data.raw_dim());
+        new_grad.slice_mut(self.info.as_slice()).assign(grad);
+        let zero = Array::<T, IxDyn>::zeros(new_grad.raw_dim());
+
+        [new_grad, zero]
+    }
+}
+
+#[derive(Clone)]
+pub struct IndexSelect {
+    pub axis: usize,
+    pub indices: Vec<usize>,
+}
+
+impl<T> GradFn<T> for IndexSelect
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x.select(Axis(self.axis), &self.indices)
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let data = &left_ref.borrow().data;
+
+        let mut new_grad = Array::<T, IxDyn>::zeros(data.raw_dim());
+        for (i, &idx) in self.indices.iter().enumerate() {
+            let mut lane = new_grad.index_axis_mut(Axis(self.axis), idx);
+            lane += &grad.index_axis(Axis(self.axis), i);
+        }
+        let zero = Array::<T, IxDyn>::zeros(new_grad.raw_dim());
+
+        [new_grad, zero]
+    }
+}
+
+/// position in the source array targeted by the element `pos` of an index array along `axis`
+fn source_position(pos: &IxDyn, axis: usize, idx: usize) -> IxDyn {
+    let mut src = pos.clone();
+    src[axis] = idx;
+    src
+}
+
+/// panic naming the first element of `index` pointing outside of `shape` along `axis`
+fn check_index(index: &Array<usize, IxDyn>, axis: usize, shape: &[usize]) {
+    assert!(
+        axis < shape.len() && index.ndim() == shape.len(),
+        "index of shape {:?} along axis {} does not match an input of shape {:?}",
+        index.shape(),
+        axis,
+        shape
+    );
+    for (pos, &idx) in index.indexed_iter() {
+        let src = source_position(&pos, axis, idx);
+        assert!(
+            src.slice()
+                .iter()
+                .zip(shape.iter())
+                .all(|(&i, &len)| i < len),
+            "index {} at {:?} is out of range for an input of shape {:?}",
+            idx,
+            pos.slice(),
+            shape
+        );
+    }
+}
+
+#[derive(Clone)]
+pub struct Gather {
+    pub axis: usize,
+    pub index: Array<usize, IxDyn>,
+}
+
+impl<T> GradFn<T> for Gather
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        Array::from_shape_fn(self.index.raw_dim(), |pos| {
+            x[source_position(&pos, self.axis, self.index[&pos])]
+        })
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let data = &left_ref.borrow().data;
+
+        let mut new_grad = Array::<T, IxDyn>::zeros(data.raw_dim());
+        for (pos, &idx) in self.index.indexed_iter() {
+            new_grad[source_position(&pos, self.axis, idx)] += grad[pos];
+        }
+        let zero = Array::<T, IxDyn>::zeros(new_grad.raw_dim());
+
+        [new_grad, zero]
+    }
+}
+
+#[derive(Clone)]
+pub struct ScatterAdd {
+    pub axis: usize,
+    pub index: Array<usize, IxDyn>,
+}
+
+impl<T> GradFn<T> for ScatterAdd
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let mut out = x.clone();
+
+        for (pos, &idx) in self.index.indexed_iter() {
+            out[source_position(&pos, self.axis, idx)] += y[pos];
+        }
+
+        out
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        _left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let src = &right_ref.borrow().data;
+
+        let mut grad_src = Array::<T, IxDyn>::zeros(src.raw_dim());
+        for (pos, &idx) in self.index.indexed_iter() {
+            grad_src[pos.clone()] = grad[source_position(&pos, self.axis, idx)];
+        }
+
+        [grad.clone(), grad_src]
+    }
+}
+
+fn check_mask(mask: &Array<bool, IxDyn>, shape: &[usize]) {
+    assert!(
+        mask.shape() == shape,
+        "mask of shape {:?} for an input of shape {:?}",
+        mask.shape(),
+        shape
+    );
+}
+
+#[derive(Clone)]
+pub struct MaskedSelect {
+    pub mask: Array<bool, IxDyn>,
+}
+
+impl<T> GradFn<T> for MaskedSelect
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let selected: Vec<T> = x
+            .iter()
+            .zip(self.mask.iter())
+            .filter(|(_, &m)| m)
+            .map(|(&a, _)| a)
+            .collect();
+
+        Array::from_vec(selected).into_dyn()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let data = &left_ref.borrow().data;
+
+        let mut new_grad = Array::<T, IxDyn>::zeros(data.raw_dim());
+        let mut grad_iter = grad.iter();
+        for (g, &m) in new_grad.iter_mut().zip(self.mask.iter()) {
+            if m {
+                *g = *grad_iter.next().unwrap();
+            }
+        }
+        let zero = Array::<T, IxDyn>::zeros(new_grad.raw_dim());
+
+        [new_grad, zero]
+    }
+}
+
+#[derive(Clone)]
+pub struct MaskedFill<T: NdFloat> {
+    pub mask: Array<bool, IxDyn>,
+    pub value: T,
+}
+
+impl<T> GradFn<T> for MaskedFill<T>
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let mut out = x.clone();
+
+        Zip::from(&mut out).and(&self.mask).for_each(|a, &m| {
+            if m {
+                *a = self.value;
+            }
+        });
+
+        out
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        _left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let mut grad = grad.clone();
+
+        Zip::from(&mut grad).and(&self.mask).for_each(|g, &m| {
+            if m {
+                *g = T::zero();
+            }
+        });
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [grad, zero]
+    }
+}
+
+impl<T> VariableRef<T>
+where
+    T: NdFloat,
+{
+    /// slice following the `ndarray::s![]` semantics
+    pub fn slice<I: SliceArg<IxDyn>>(&mut self, info: I) -> VariableRef<T> {
+        let grad_fn = Slice {
+            info: info.as_ref().to_vec(),
+        };
+        grad_fn.subscribe(self, self, Box::new(grad_fn.clone()))
+    }
+
+    pub fn index_select(&mut self, axis: usize, indices: &[usize]) -> VariableRef<T> {
+        let grad_fn = IndexSelect {
+            axis,
+            indices: indices.to_vec(),
+        };
+        grad_fn.subscribe(self, self, Box::new(grad_fn.clone()))
+    }
+
+    /// `out[i][j] = self[index[i][j]][j]` for `axis = 0`, the output has the shape of `index`
+    pub fn gather(&mut self, axis: usize, index: &Array<usize, IxDyn>) -> VariableRef<T> {
+        check_index(index, axis, self.borrow().data.shape());
+        let grad_fn = Gather {
+            axis,
+            index: index.clone(),
+        };
+        grad_fn.subscribe(self, self, Box::new(grad_fn.clone()))
+    }
+
+    /// `out[index[i][j]][j] += src[i][j]` for `axis = 0`, starting from a copy of `self`
+    pub fn scatter_add(
+        &mut self,
+        axis: usize,
+        index: &Array<usize, IxDyn>,
+        src: &VariableRef<T>,
+    ) -> VariableRef<T> {
+        check_index(index, axis, self.borrow().data.shape());
+        let src_shape = src.borrow().data.shape().to_vec();
+        assert!(
+            index.ndim() == src_shape.len()
+                && index
+                    .shape()
+                    .iter()
+                    .zip(src_shape.iter())
+                    .all(|(i, s)| i <= s),
+            "index of shape {:?} is larger than src of shape {:?}",
+            index.shape(),
+            src_shape
+        );
+        let grad_fn = ScatterAdd {
+            axis,
+            index: index.clone(),
+        };
+        grad_fn.subscribe(self, src, Box::new(grad_fn.clone()))
+    }
+
+    /// 1-D variable holding the elements where `mask` is true, in row-major order
+    pub fn masked_select(&mut self, mask: &Array<bool, IxDyn>) -> VariableRef<T> {
+        check_mask(mask, self.borrow().data.shape());
+        let grad_fn = MaskedSelect { mask: mask.clone() };
+        grad_fn.subscribe(self, self, Box::new(grad_fn.clone()))
+    }
+
+    pub fn masked_fill(&mut self, mask: &Array<bool, IxDyn>, value: T) -> VariableRef<T> {
+        check_mask(mask, self.borrow().data.shape());
+        let grad_fn = MaskedFill {
+            mask: mask.clone(),
+            value,
+        };
+        grad_fn.subscribe(self, self, Box::new(grad_fn.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::variable::Variable;
+    use ndarray::{array, s};
+
+    #[test]
+    fn slice_check_method() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+
+        let z = x.slice(s![.., 1..]);
+        assert_eq!(z.borrow().data, array!([2.0, 3.0], [5.0, 6.0]).into_dyn());
+
+        let z = x.slice(s![1, ..;2]);
+        assert_eq!(z.borrow().data, array!(4.0, 6.0).into_dyn());
+    }
+
+    #[test]
+    fn slice_check_backward() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+
+        let mut z = x.slice(s![.., 1..]).sum();
+        z.backward();
+
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([0.0, 1.0, 1.0], [0.0, 1.0, 1.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn index_select_check_backward() {
+        let mut x = Variable::new(array!([1.0, 2.0], [3.0, 4.0], [5.0, 6.0]).into_dyn());
+
+        let mut z = x.index_select(0, &[2, 0, 2]);
+        assert_eq!(
+            z.borrow().data,
+            array!([5.0, 6.0], [1.0, 2.0], [5.0, 6.0]).into_dyn()
+        );
+
+        z.backward();
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([1.0, 1.0], [0.0, 0.0], [2.0, 2.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn gather_check_backward() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+        let index = array!([2, 2], [0, 1]).into_dyn();
+
+        let mut z = x.gather(1, &index);
+        assert_eq!(z.borrow().data, array!([3.0, 3.0], [4.0, 5.0]).into_dyn());
+
+        z.backward();
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([0.0, 0.0, 2.0], [1.0, 1.0, 0.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn scatter_add_check_backward() {
+        let mut x = Variable::new(array!([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]).into_dyn());
+        let src = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+        let index = array!([1, 0, 0]).into_dyn();
+
+        let z = &x.scatter_add(0, &index, &src);
+        assert_eq!(
+            z.borrow().data,
+            array!([0.0, 2.0, 3.0], [1.0, 0.0, 0.0]).into_dyn()
+        );
+
+        let weight = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+        let mut out = (z * &weight).sum();
+        out.backward();
+
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn()
+        );
+        assert_eq!(
+            src.borrow().get_grad_f(),
+            array!([4.0, 2.0, 3.0], [0.0, 0.0, 0.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "index 3 at [1, 0] is out of range")]
+    fn gather_out_of_range() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+        x.gather(1, &array!([2, 2], [3, 1]).into_dyn());
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 at [0, 1] is out of range")]
+    fn scatter_add_out_of_range() {
+        let mut x = Variable::new(array!([0.0, 0.0], [0.0, 0.0]).into_dyn());
+        let src = Variable::new(array!([1.0, 2.0]).into_dyn());
+        x.scatter_add(0, &array!([1, 2]).into_dyn(), &src);
+    }
+
+    #[test]
+    #[should_panic(expected = "larger than src")]
+    fn scatter_add_larger_index() {
+        let mut x = Variable::new(array!([0.0, 0.0], [0.0, 0.0]).into_dyn());
+        let src = Variable::new(array!([1.0]).into_dyn());
+        x.scatter_add(0, &array!([1, 0]).into_dyn(), &src);
+    }
+
+    #[test]
+    fn masked_select_check_backward() {
+        let mut x = Variable::new(array!([1.0, -2.0], [-3.0, 4.0]).into_dyn());
+        let mask = x.borrow().data.mapv(|a| a > 0.0);
+
+        let z = &x.masked_select(&mask);
+        assert_eq!(z.borrow().data, array!(1.0, 4.0).into_dyn());
+
+        let weight = Variable::new(array!(10.0, 20.0).into_dyn());
+        let mut out = (z * &weight).sum();
+        out.backward();
+
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([10.0, 0.0], [0.0, 20.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "mask of shape [4] for an input of shape [2, 2]")]
+    fn masked_select_wrong_shape() {
+        let mut x = Variable::new(array!([1.0, -2.0], [-3.0, 4.0]).into_dyn());
+        x.masked_select(&array!(true, false, false, true).into_dyn());
+    }
+
+    #[test]
+    #[should_panic(expected = "mask of shape")]
+    fn masked_fill_wrong_shape() {
+        let mut x = Variable::new(array!([1.0, -2.0], [-3.0, 4.0]).into_dyn());
+        x.masked_fill(&array!([true, false]).into_dyn(), 0.5);
+    }
+
+    #[test]
+    fn masked_fill_check_backward() {
+        let mut x = Variable::new(array!([1.0, -2.0], [-3.0, 4.0]).into_dyn());
+        let mask = x.borrow().data.mapv(|a| a < 0.0);
+
+        let mut z = x.masked_fill(&mask, 0.5);
+        assert_eq!(z.borrow().data, array!([1.0, 0.5], [0.5, 4.0]).into_dyn());
+
+        z.backward();
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([1.0, 0.0], [0.0, 1.0]).into_dyn()
+        );
+    }
+}
@@ -1,8 +1,22 @@
+pub mod concat;
+pub mod conv;
+pub mod conv_transpose;
 pub mod dot;
+pub mod dropout;
+pub mod einsum;
+pub mod embedding;
 pub mod exp;
 pub mod functional;
+pub mod gradcheck;
 pub mod identity;
+pub mod index;
+pub mod norm;
 pub mod operator;
+pub mod piecewise;
+pub mod pool;
 pub mod relu;
+pub mod shape;
+pub mod sigmoid;
 pub mod softmax;
 pub mod sum;
+pub mod trigo;
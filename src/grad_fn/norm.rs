@@ -0,0 +1,425 @@
+use ndarray::{Array, Axis, IxDyn, NdFloat};
+
+use crate::grad_fn::operator::sum_to_shape;
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+/// mean over `axes`, keeping them with length 1
+fn mean_over<T: NdFloat>(x: &Array<T, IxDyn>, axes: &[usize]) -> Array<T, IxDyn> {
+    let mut mean = x.clone();
+    let mut len = 1;
+    for &axis in axes {
+        len *= x.shape()[axis];
+        mean = mean.sum_axis(Axis(axis)).insert_axis(Axis(axis));
+    }
+    mean / T::from(len).unwrap()
+}
+
+/// the `k` axes just before the last (batch) one
+fn normalized_axes(ndim: usize, k: usize) -> Vec<usize> {
+    ((ndim - 1 - k)..(ndim - 1)).collect()
+}
+
+/// mean over the `k` axes just before the last (batch) one, keeping them with length 1
+fn normalized_mean<T: NdFloat>(x: &Array<T, IxDyn>, k: usize) -> Array<T, IxDyn> {
+    mean_over(x, &normalized_axes(x.ndim(), k))
+}
+
+/// `x` centered and scaled to unit variance over `axes`, and the inverse standard deviation
+fn standardize<T: NdFloat>(
+    x: &Array<T, IxDyn>,
+    axes: &[usize],
+    eps: T,
+) -> (Array<T, IxDyn>, Array<T, IxDyn>) {
+    let centered = x - &mean_over(x, axes);
+    let var = mean_over(&centered.mapv(|a| a * a), axes);
+    let rstd = var.mapv(|v| T::one() / (v + eps).sqrt());
+    (centered * &rstd, rstd)
+}
+
+/// gradient of `standardize` with respect to `x`, given the one of its output `g`
+fn standardize_backward<T: NdFloat>(
+    g: Array<T, IxDyn>,
+    x_hat: &Array<T, IxDyn>,
+    rstd: &Array<T, IxDyn>,
+    axes: &[usize],
+) -> Array<T, IxDyn> {
+    let mean_g = mean_over(&g, axes);
+    let mean_gx = mean_over(&(&g * x_hat), axes);
+    (g - &mean_g - x_hat * &mean_gx) * rstd
+}
+
+/// check that `x` ends with the normalized shape of `gamma` followed by a batch axis
+fn check_shape<T: NdFloat>(x: &Array<T, IxDyn>, gamma: &Array<T, IxDyn>, k: usize) {
+    let ndim = x.ndim();
+    assert!(
+        ndim > k && x.shape()[ndim - 1 - k..ndim - 1] == gamma.shape()[..k],
+        "input of shape {:?} does not match normalized shape {:?}",
+        x.shape(),
+        &gamma.shape()[..k]
+    );
+}
+
+/// fused `(x - mean) / sqrt(var + eps) * gamma` where the statistics are taken over the
+/// `normalized_ndim` axes before the last (batch) axis. `gamma` has the normalized shape
+/// followed by a batch axis of length 1
+pub struct LayerNorm<T> {
+    pub normalized_ndim: usize,
+    pub eps: T,
+}
+
+impl<T> GradFn<T> for LayerNorm<T>
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        check_shape(x, y, self.normalized_ndim);
+        let axes = normalized_axes(x.ndim(), self.normalized_ndim);
+        standardize(x, &axes, self.eps).0 * y
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let gamma = &right_ref.borrow().data;
+        let axes = normalized_axes(x.ndim(), self.normalized_ndim);
+        let (x_hat, rstd) = standardize(x, &axes, self.eps);
+
+        let grad_x = standardize_backward(grad * gamma, &x_hat, &rstd, &axes);
+        let grad_gamma = sum_to_shape(&(grad * &x_hat), gamma.shape());
+
+        [grad_x, grad_gamma]
+    }
+}
+
+/// fused `x / sqrt(mean(x²) + eps) * gamma`, with the same axes convention as `LayerNorm`
+pub struct RMSNorm<T> {
+    pub normalized_ndim: usize,
+    pub eps: T,
+}
+
+impl<T: NdFloat> RMSNorm<T> {
+    fn normalize(&self, x: &Array<T, IxDyn>) -> (Array<T, IxDyn>, Array<T, IxDyn>) {
+        let mean_square = normalized_mean(&x.mapv(|a| a * a), self.normalized_ndim);
+        let rstd = mean_square.mapv(|v| T::one() / (v + self.eps).sqrt());
+        (x * &rstd, rstd)
+    }
+}
+
+impl<T> GradFn<T> for RMSNorm<T>
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        check_shape(x, y, self.normalized_ndim);
+        self.normalize(x).0 * y
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let gamma = &right_ref.borrow().data;
+        let (x_hat, rstd) = self.normalize(x);
+
+        let g = grad * gamma;
+        let mean_gx = normalized_mean(&(&g * &x_hat), self.normalized_ndim);
+
+        let grad_x = (g - x_hat.clone() * &mean_gx) * &rstd;
+        let grad_gamma = sum_to_shape(&(grad * &x_hat), gamma.shape());
+
+        [grad_x, grad_gamma]
+    }
+}
+
+/// fused `(x - mean) / sqrt(var + eps) * gamma` where the statistics are taken over all the
+/// axes but `channel_axis`, with the biased variance. `gamma` has length 1 on every axis but
+/// the channel one
+pub struct BatchNorm<T> {
+    pub channel_axis: usize,
+    pub eps: T,
+}
+
+impl<T: NdFloat> BatchNorm<T> {
+    fn axes(&self, ndim: usize) -> Vec<usize> {
+        (0..ndim)
+            .filter(|&axis| axis != self.channel_axis)
+            .collect()
+    }
+}
+
+impl<T> GradFn<T> for BatchNorm<T>
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        standardize(x, &self.axes(x.ndim()), self.eps).0 * y
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let gamma = &right_ref.borrow().data;
+        let axes = self.axes(x.ndim());
+        let (x_hat, rstd) = standardize(x, &axes, self.eps);
+
+        let grad_x = standardize_backward(grad * gamma, &x_hat, &rstd, &axes);
+        let grad_gamma = sum_to_shape(&(grad * &x_hat), gamma.shape());
+
+        [grad_x, grad_gamma]
+    }
+}
+
+/// layer normalization of `input` over the `normalized_ndim` axes before the batch axis,
+/// scaled by `gamma`
+pub fn layer_norm<T: NdFloat>(
+    input: &VariableRef<T>,
+    gamma: &VariableRef<T>,
+    normalized_ndim: usize,
+    eps: T,
+) -> VariableRef<T> {
+    let grad_fn = LayerNorm {
+        normalized_ndim,
+        eps,
+    };
+    grad_fn.subscribe(
+        input,
+        gamma,
+        Box::new(LayerNorm {
+            normalized_ndim,
+            eps,
+        }),
+    )
+}
+
+/// root mean square normalization of `input` over the `normalized_ndim` axes before the batch
+/// axis, scaled by `gamma`
+pub fn rms_norm<T: NdFloat>(
+    input: &VariableRef<T>,
+    gamma: &VariableRef<T>,
+    normalized_ndim: usize,
+    eps: T,
+) -> VariableRef<T> {
+    let grad_fn = RMSNorm {
+        normalized_ndim,
+        eps,
+    };
+    grad_fn.subscribe(
+        input,
+        gamma,
+        Box::new(RMSNorm {
+            normalized_ndim,
+            eps,
+        }),
+    )
+}
+
+/// batch normalization of `input` with the statistics of the batch, computed over all the
+/// axes but `channel_axis`, scaled by `gamma`
+pub fn batch_norm<T: NdFloat>(
+    input: &VariableRef<T>,
+    gamma: &VariableRef<T>,
+    channel_axis: usize,
+    eps: T,
+) -> VariableRef<T> {
+    let grad_fn = BatchNorm { channel_axis, eps };
+    grad_fn.subscribe(input, gamma, Box::new(BatchNorm { channel_axis, eps }))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::grad_fn::einsum::einsum;
+    use crate::grad_fn::gradcheck::gradcheck;
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    /// mean over the normalized axes built from primitive ops
+    fn composed_mean(x: &VariableRef<f64>, k: usize) -> VariableRef<f64> {
+        let shape = x.borrow().data.shape().to_vec();
+        let ndim = shape.len();
+        let letters: String = "abcdefgh"[..ndim].to_string();
+        let kept: String = letters
+            .chars()
+            .enumerate()
+            .filter(|&(i, _)| i < ndim - 1 - k || i == ndim - 1)
+            .map(|(_, c)| c)
+            .collect();
+
+        let mut keep_shape = shape.clone();
+        let mut len = 1;
+        for s in keep_shape[ndim - 1 - k..ndim - 1].iter_mut() {
+            len *= *s;
+            *s = 1;
+        }
+
+        let mut sum = einsum(&format!("{}->{}", letters, kept), std::slice::from_ref(x));
+        let scale = Variable::new_no_retain_grad(array!(1.0 / len as f64).into_dyn());
+        sum.reshape(&keep_shape) * scale
+    }
+
+    fn composed_layer_norm(
+        x: &VariableRef<f64>,
+        gamma: &VariableRef<f64>,
+        k: usize,
+        eps: f64,
+    ) -> VariableRef<f64> {
+        let eps = Variable::new_no_retain_grad(array!(eps).into_dyn());
+        let centered = x - &composed_mean(x, k);
+        let var = composed_mean(&(&centered * &centered), k);
+        let mut std = (var + eps).sqrt();
+        &(centered / std.identity()) * gamma
+    }
+
+    fn composed_rms_norm(
+        x: &VariableRef<f64>,
+        gamma: &VariableRef<f64>,
+        k: usize,
+        eps: f64,
+    ) -> VariableRef<f64> {
+        let eps = Variable::new_no_retain_grad(array!(eps).into_dyn());
+        let mean_square = composed_mean(&(x * x), k);
+        let rms = (mean_square + eps).sqrt();
+        &(x / &rms) * gamma
+    }
+
+    fn inputs_3d() -> Vec<Array<f64, IxDyn>> {
+        let x = Array::from_shape_fn((2, 3, 4), |(i, j, b)| {
+            ((i * 12 + j * 4 + b) as f64 * 0.37).sin() * (1. + b as f64)
+        })
+        .into_dyn();
+        let gamma = array!([[0.5], [1.5], [-1.0]], [[2.0], [0.25], [1.0]]).into_dyn();
+        vec![x, gamma]
+    }
+
+    #[test]
+    fn layer_norm_check_method() {
+        let x = Variable::new(array!([1.0, 0.0], [2.0, 0.0], [3.0, 3.0]).into_dyn());
+        let gamma = Variable::new(array!([1.0], [1.0], [2.0]).into_dyn());
+
+        let z = layer_norm(&x, &gamma, 1, 0.0);
+
+        // columns [1, 2, 3] and [0, 0, 3] have variances 2/3 and 2
+        let s = 1.5_f64.sqrt();
+        let r = 0.5_f64.sqrt();
+        let res = array!([-s, -r], [0.0, -r], [2.0 * s, 4.0 * r]);
+        let diff = &z.borrow().data - &res.into_dyn();
+        assert!(diff.iter().all(|d| d.abs() < 1e-12));
+    }
+
+    #[test]
+    fn layer_norm_matches_composed() {
+        let inputs = inputs_3d();
+        for k in 1..3 {
+            let gamma = if k == 1 {
+                array!([1.0], [-2.0], [0.5]).into_dyn()
+            } else {
+                inputs[1].clone()
+            };
+            let x_fused = Variable::new(inputs[0].clone());
+            let x_ref = Variable::new(inputs[0].clone());
+            let gamma_fused = Variable::new(gamma.clone());
+            let gamma_ref = Variable::new(gamma);
+
+            let mut fused = layer_norm(&x_fused, &gamma_fused, k, 1e-5);
+            let mut reference = composed_layer_norm(&x_ref, &gamma_ref, k, 1e-5);
+
+            let diff = &fused.borrow().data - &reference.borrow().data;
+            assert!(diff.iter().all(|d| d.abs() < 1e-10));
+
+            fused.sum().backward();
+            reference.sum().backward();
+
+            let diff = &x_fused.borrow().get_grad_f() - &x_ref.borrow().get_grad_f();
+            assert!(diff.iter().all(|d| d.abs() < 1e-10));
+            let diff = &gamma_fused.borrow().get_grad_f() - &gamma_ref.borrow().get_grad_f();
+            assert!(diff.iter().all(|d| d.abs() < 1e-10));
+        }
+    }
+
+    #[test]
+    fn layer_norm_gradcheck() {
+        let f = |v: &[VariableRef<f64>]| layer_norm(&v[0], &v[1], 2, 1e-5);
+        gradcheck(f, &inputs_3d(), 1e-6, 1e-5).unwrap();
+
+        let f = |v: &[VariableRef<f64>]| composed_layer_norm(&v[0], &v[1], 2, 1e-5);
+        gradcheck(f, &inputs_3d(), 1e-6, 1e-5).unwrap();
+    }
+
+    #[test]
+    fn rms_norm_check_method() {
+        let x = Variable::new(array!([3.0], [4.0]).into_dyn());
+        let gamma = Variable::new(array!([1.0], [2.0]).into_dyn());
+
+        let z = rms_norm(&x, &gamma, 1, 0.0);
+
+        let rms = 12.5_f64.sqrt();
+        assert_eq!(z.borrow().data, array!([3.0 / rms], [8.0 / rms]).into_dyn());
+    }
+
+    #[test]
+    fn rms_norm_matches_composed() {
+        let inputs = inputs_3d();
+        let x_fused = Variable::new(inputs[0].clone());
+        let x_ref = Variable::new(inputs[0].clone());
+        let gamma_fused = Variable::new(inputs[1].clone());
+        let gamma_ref = Variable::new(inputs[1].clone());
+
+        let mut fused = rms_norm(&x_fused, &gamma_fused, 2, 1e-6);
+        let mut reference = composed_rms_norm(&x_ref, &gamma_ref, 2, 1e-6);
+
+        let diff = &fused.borrow().data - &reference.borrow().data;
+        assert!(diff.iter().all(|d| d.abs() < 1e-10));
+
+        fused.sum().backward();
+        reference.sum().backward();
+
+        let diff = &x_fused.borrow().get_grad_f() - &x_ref.borrow().get_grad_f();
+        assert!(diff.iter().all(|d| d.abs() < 1e-10));
+        let diff = &gamma_fused.borrow().get_grad_f() - &gamma_ref.borrow().get_grad_f();
+        assert!(diff.iter().all(|d| d.abs() < 1e-10));
+    }
+
+    #[test]
+    fn rms_norm_gradcheck() {
+        let f = |v: &[VariableRef<f64>]| rms_norm(&v[0], &v[1], 2, 1e-6);
+        gradcheck(f, &inputs_3d(), 1e-6, 1e-5).unwrap();
+
+        let f = |v: &[VariableRef<f64>]| composed_rms_norm(&v[0], &v[1], 2, 1e-6);
+        gradcheck(f, &inputs_3d(), 1e-6, 1e-5).unwrap();
+    }
+
+    #[test]
+    fn batch_norm_check_method() {
+        let x = Variable::new(array!([1.0, 3.0], [0.0, 4.0]).into_dyn());
+        let gamma = Variable::new(array!([1.0], [2.0]).into_dyn());
+
+        let z = batch_norm(&x, &gamma, 0, 0.0);
+
+        assert_eq!(z.borrow().data, array!([-1.0, 1.0], [-2.0, 2.0]).into_dyn());
+    }
+
+    #[test]
+    fn batch_norm_gradcheck() {
+        let x = Array::from_shape_fn((3, 2, 2, 2), |(n, c, h, w)| {
+            ((n * 8 + c * 4 + h * 2 + w) as f64 * 0.61).cos() * (1. + n as f64)
+        })
+        .into_dyn();
+        let gamma = Array::from_shape_vec(vec![1, 2, 1, 1], vec![0.5, -1.5]).unwrap();
+
+        let f = |v: &[VariableRef<f64>]| batch_norm(&v[0], &v[1], 1, 1e-5);
+        gradcheck(f, &[x, gamma], 1e-6, 1e-5).unwrap();
+    }
+}
@@ -1,10 +1,52 @@
 use std::ops;
 
-use ndarray::{Array, IxDyn, NdFloat};
+use ndarray::{Array, Axis, IxDyn, NdFloat};
 
+use crate::serialize::onnx::Op;
 use crate::variable::GradFn;
 use crate::variable::VariableRef;
 
+/// shape resulting from broadcasting two shapes together, following the numpy rules
+pub fn broadcast_shape(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let ndim = a.len().max(b.len());
+    let dim = |s: &[usize], i: usize| {
+        if i + s.len() < ndim {
+            1
+        } else {
+            s[i + s.len() - ndim]
+        }
+    };
+
+    (0..ndim)
+        .map(|i| match (dim(a, i), dim(b, i)) {
+            (x, y) if x == y || y == 1 => x,
+            (1, y) => y,
+            (x, y) => panic!(
+                "shapes {:?} and {:?} cannot be broadcast ({} vs {})",
+                a, b, x, y
+            ),
+        })
+        .collect()
+}
+
+/// sum a gradient over the axes along which an operand of `shape` has been broadcast
+pub fn sum_to_shape<T: NdFloat>(grad: &Array<T, IxDyn>, shape: &[usize]) -> Array<T, IxDyn> {
+    if grad.shape() == shape {
+        return grad.clone();
+    }
+
+    let mut grad = grad.clone();
+    while grad.ndim() > shape.len() {
+        grad = grad.sum_axis(Axis(0));
+    }
+    for (i, &len) in shape.iter().enumerate() {
+        if len == 1 && grad.shape()[i] != 1 {
+            grad = grad.sum_axis(Axis(i)).insert_axis(Axis(i));
+        }
+    }
+    grad
+}
+
 pub struct Add {}
 
 impl<T> GradFn<T> for Add
@@ -18,10 +60,17 @@ where
     fn backward<'a>(
         &self,
         grad: &'a Array<T, IxDyn>,
-        _left_ref: &'a VariableRef<T>,
-        _right_ref: &'a VariableRef<T>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
     ) -> [Array<T, IxDyn>; 2] {
-        [grad.clone(), grad.clone()]
+        [
+            sum_to_shape(grad, left_ref.borrow().data.shape()),
+            sum_to_shape(grad, right_ref.borrow().data.shape()),
+        ]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::binary("Add")])
     }
 }
 
@@ -38,10 +87,17 @@ where
     fn backward<'a>(
         &self,
         grad: &'a Array<T, IxDyn>,
-        _left_ref: &'a VariableRef<T>,
-        _right_ref: &'a VariableRef<T>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
     ) -> [Array<T, IxDyn>; 2] {
-        [grad.clone(), -grad.clone()]
+        [
+            sum_to_shape(grad, left_ref.borrow().data.shape()),
+            -sum_to_shape(grad, right_ref.borrow().data.shape()),
+        ]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::binary("Sub")])
     }
 }
 
@@ -64,7 +120,14 @@ where
         let left_var = left_ref.borrow();
         let right_var = right_ref.borrow();
 
-        [right_var.data.clone() * grad, left_var.data.clone() * grad]
+        [
+            sum_to_shape(&(&right_var.data * grad), left_var.data.shape()),
+            sum_to_shape(&(&left_var.data * grad), right_var.data.shape()),
+        ]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::binary("Mul")])
     }
 }
 
@@ -88,10 +151,17 @@ where
         let right_data = right_ref.borrow().data.clone();
 
         [
-            grad / right_data.clone(),
-            -(grad * left_data) / (right_data.mapv(|a| a.powi(2))),
+            sum_to_shape(&(grad / &right_data), left_data.shape()),
+            sum_to_shape(
+                &(-(grad * &left_data) / (right_data.mapv(|a| a.powi(2)))),
+                right_data.shape(),
+            ),
         ]
     }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::binary("Div")])
+    }
 }
 
 #[macro_export]
@@ -245,4 +315,40 @@ mod tests {
         assert_eq!(x.borrow().get_grad_f(), array!([1.0 / 3.0]).into_dyn());
         assert_eq!(y.borrow().get_grad_f(), array!([-2.0 / 9.0]).into_dyn());
     }
+
+    #[test]
+    fn broadcast_check_backward() {
+        let x = &Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+        let bias = &Variable::new(array!([1.0], [2.0]).into_dyn());
+        let scale = &Variable::new(array!(1.0, 2.0, 3.0).into_dyn());
+
+        let mut z = (x + bias) * scale;
+        z.backward();
+
+        assert_eq!(bias.borrow().get_grad_f(), array!([6.0], [6.0]).into_dyn());
+        assert_eq!(
+            scale.borrow().get_grad_f(),
+            array!(8.0, 10.0, 12.0).into_dyn()
+        );
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([1.0, 2.0, 3.0], [1.0, 2.0, 3.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn broadcast_sub_div_check_backward() {
+        let x = &Variable::new(array!([1.0, 2.0], [3.0, 4.0]).into_dyn());
+        let y = &Variable::new(array!([1.0], [2.0]).into_dyn());
+
+        let mut z = &(x / y) - y;
+        z.backward();
+
+        // -x / y^2 summed over each row, minus one per column
+        assert_eq!(y.borrow().get_grad_f(), array!([-5.0], [-3.75]).into_dyn());
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([1.0, 1.0], [0.5, 0.5]).into_dyn()
+        );
+    }
 }
@@ -0,0 +1,392 @@
+use ndarray::{Array, IxDyn, NdFloat, Zip};
+
+use crate::grad_fn::operator::{broadcast_shape, sum_to_shape};
+use crate::serialize::onnx::{Op, Tensor};
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+// subgradient conventions at the kinks follow pytorch:
+// abs'(0) = 0, clamp lets the gradient through on the bounds and
+// maximum/minimum split it in two halves when both operands are equal
+
+pub struct Abs {}
+
+impl<T> GradFn<T> for Abs
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x.mapv(|a| a.abs())
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let data = &left_ref.borrow().data;
+
+        let grad = grad * &data.mapv(sign);
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [grad, zero]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::unary("Abs")])
+    }
+}
+
+fn sign<T: NdFloat>(a: T) -> T {
+    if a > T::zero() {
+        T::one()
+    } else if a < T::zero() {
+        -T::one()
+    } else {
+        T::zero()
+    }
+}
+
+pub struct Sign {}
+
+impl<T> GradFn<T> for Sign
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x.mapv(sign)
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        _left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [zero.clone(), zero]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::unary("Sign")])
+    }
+}
+
+pub struct Clamp<T: NdFloat> {
+    pub min: T,
+    pub max: T,
+}
+
+impl<T> GradFn<T> for Clamp<T>
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x.mapv(|a| a.max(self.min).min(self.max))
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let mut grad = grad.clone();
+        let data = &left_ref.borrow().data;
+
+        Zip::from(&mut grad).and(data).for_each(|g, &d| {
+            if d < self.min || d > self.max {
+                *g = T::zero();
+            }
+        });
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [grad, zero]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        let bound = |value: T| Tensor::from_array("", &Array::from_elem(IxDyn(&[]), value));
+        Some(vec![Op::unary("Clip")
+            .with_constant(bound(self.min))
+            .with_constant(bound(self.max))])
+    }
+}
+
+/// share of the gradient going to `x` when comparing it to `y`
+fn select_weight<T: NdFloat>(x: T, y: T, keep_greater: bool) -> T {
+    if x == y {
+        T::from(0.5).unwrap()
+    } else if (x > y) == keep_greater {
+        T::one()
+    } else {
+        T::zero()
+    }
+}
+
+pub struct Maximum {}
+
+pub struct Minimum {}
+
+macro_rules! impl_select_op {
+    ($trt:ident, $onnx:expr, $keep_greater:expr) => {
+        impl<T> GradFn<T> for $trt
+        where
+            T: NdFloat,
+        {
+            fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+                let shape = broadcast_shape(x.shape(), y.shape());
+                let mut out = Array::<T, IxDyn>::zeros(shape);
+
+                Zip::from(&mut out)
+                    .and_broadcast(x)
+                    .and_broadcast(y)
+                    .for_each(|o, &a, &b| {
+                        *o = if (a > b) == $keep_greater { a } else { b };
+                    });
+
+                out
+            }
+
+            fn backward<'a>(
+                &self,
+                grad: &'a Array<T, IxDyn>,
+                left_ref: &'a VariableRef<T>,
+                right_ref: &'a VariableRef<T>,
+            ) -> [Array<T, IxDyn>; 2] {
+                let x = &left_ref.borrow().data;
+                let y = &right_ref.borrow().data;
+
+                let mut grad_x = grad.clone();
+                let mut grad_y = grad.clone();
+                Zip::from(&mut grad_x)
+                    .and(&mut grad_y)
+                    .and_broadcast(x)
+                    .and_broadcast(y)
+                    .for_each(|gx, gy, &a, &b| {
+                        *gx *= select_weight(a, b, $keep_greater);
+                        *gy *= select_weight(b, a, $keep_greater);
+                    });
+
+                [
+                    sum_to_shape(&grad_x, x.shape()),
+                    sum_to_shape(&grad_y, y.shape()),
+                ]
+            }
+
+            fn onnx(&self) -> Option<Vec<Op>> {
+                Some(vec![Op::binary($onnx)])
+            }
+        }
+    };
+}
+
+impl_select_op!(Maximum, "Max", true);
+impl_select_op!(Minimum, "Min", false);
+
+pub struct Where {
+    pub cond: Array<bool, IxDyn>,
+}
+
+impl<T> GradFn<T> for Where
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let shape = broadcast_shape(&broadcast_shape(self.cond.shape(), x.shape()), y.shape());
+        let mut out = Array::<T, IxDyn>::zeros(shape);
+
+        Zip::from(&mut out)
+            .and_broadcast(&self.cond)
+            .and_broadcast(x)
+            .and_broadcast(y)
+            .for_each(|o, &c, &a, &b| {
+                *o = if c { a } else { b };
+            });
+
+        out
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let mut grad_x = grad.clone();
+        let mut grad_y = grad.clone();
+
+        Zip::from(&mut grad_x)
+            .and(&mut grad_y)
+            .and_broadcast(&self.cond)
+            .for_each(|gx, gy, &c| {
+                if c {
+                    *gy = T::zero();
+                } else {
+                    *gx = T::zero();
+                }
+            });
+
+        [
+            sum_to_shape(&grad_x, left_ref.borrow().data.shape()),
+            sum_to_shape(&grad_y, right_ref.borrow().data.shape()),
+        ]
+    }
+}
+
+/// elementwise `if cond { a } else { b }`, `cond`, `a` and `b` are broadcast together
+pub fn where_<T: NdFloat>(
+    cond: &Array<bool, IxDyn>,
+    a: &VariableRef<T>,
+    b: &VariableRef<T>,
+) -> VariableRef<T> {
+    let grad_fn = Where { cond: cond.clone() };
+    grad_fn.subscribe(a, b, Box::new(Where { cond: cond.clone() }))
+}
+
+impl<T> VariableRef<T>
+where
+    T: NdFloat,
+{
+    pub fn abs(&mut self) -> VariableRef<T> {
+        let grad_fn = Abs {};
+        grad_fn.subscribe(self, self, Box::new(Abs {}))
+    }
+
+    /// elementwise sign, its gradient is zero everywhere
+    pub fn sign(&mut self) -> VariableRef<T> {
+        let grad_fn = Sign {};
+        grad_fn.subscribe(self, self, Box::new(Sign {}))
+    }
+
+    pub fn clamp(&mut self, min: T, max: T) -> VariableRef<T> {
+        let grad_fn = Clamp { min, max };
+        grad_fn.subscribe(self, self, Box::new(Clamp { min, max }))
+    }
+
+    pub fn maximum(&mut self, other: &VariableRef<T>) -> VariableRef<T> {
+        let grad_fn = Maximum {};
+        grad_fn.subscribe(self, other, Box::new(Maximum {}))
+    }
+
+    pub fn minimum(&mut self, other: &VariableRef<T>) -> VariableRef<T> {
+        let grad_fn = Minimum {};
+        grad_fn.subscribe(self, other, Box::new(Minimum {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn abs_check_backward() {
+        let mut x = Variable::new(array!(-2.0, 0.0, 3.0).into_dyn());
+
+        let mut z = x.abs();
+        assert_eq!(z.borrow().data, array!(2.0, 0.0, 3.0).into_dyn());
+
+        z.backward();
+        assert_eq!(x.borrow().get_grad_f(), array!(-1.0, 0.0, 1.0).into_dyn());
+    }
+
+    #[test]
+    fn sign_check_backward() {
+        let mut x = Variable::new(array!(-2.0, 0.0, 3.0).into_dyn());
+
+        let mut z = x.sign();
+        assert_eq!(z.borrow().data, array!(-1.0, 0.0, 1.0).into_dyn());
+
+        z.backward();
+        assert_eq!(x.borrow().get_grad_f(), array!(0.0, 0.0, 0.0).into_dyn());
+    }
+
+    #[test]
+    fn clamp_check_backward() {
+        let mut x = Variable::new(array!(-2.0, -1.0, 0.5, 1.0, 3.0).into_dyn());
+
+        let mut z = x.clamp(-1.0, 1.0);
+        assert_eq!(
+            z.borrow().data,
+            array!(-1.0, -1.0, 0.5, 1.0, 1.0).into_dyn()
+        );
+
+        z.backward();
+        // the bounds themselves let the gradient through
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!(0.0, 1.0, 1.0, 1.0, 0.0).into_dyn()
+        );
+    }
+
+    #[test]
+    fn maximum_check_backward() {
+        let mut x = Variable::new(array!(1.0, 2.0, 3.0).into_dyn());
+        let y = Variable::new(array!(3.0, 2.0, 1.0).into_dyn());
+
+        let mut z = x.maximum(&y);
+        assert_eq!(z.borrow().data, array!(3.0, 2.0, 3.0).into_dyn());
+
+        z.backward();
+        // ties split the gradient evenly
+        assert_eq!(x.borrow().get_grad_f(), array!(0.0, 0.5, 1.0).into_dyn());
+        assert_eq!(y.borrow().get_grad_f(), array!(1.0, 0.5, 0.0).into_dyn());
+    }
+
+    #[test]
+    fn minimum_check_backward_broadcast() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+        let y = Variable::new(array!([2.5], [4.0]).into_dyn());
+
+        let mut z = x.minimum(&y);
+        assert_eq!(
+            z.borrow().data,
+            array!([1.0, 2.0, 2.5], [4.0, 4.0, 4.0]).into_dyn()
+        );
+
+        z.backward();
+        assert_eq!(
+            x.borrow().get_grad_f(),
+            array!([1.0, 1.0, 0.0], [0.5, 0.0, 0.0]).into_dyn()
+        );
+        assert_eq!(y.borrow().get_grad_f(), array!([1.0], [2.5]).into_dyn());
+    }
+
+    #[test]
+    fn where_check_backward() {
+        let x = Variable::new(array!(1.0, 2.0, 3.0).into_dyn());
+        let y = Variable::new(array!(-1.0).into_dyn());
+        let cond = array!(true, false, true).into_dyn();
+
+        let mut z = where_(&cond, &x, &y);
+        assert_eq!(z.borrow().data, array!(1.0, -1.0, 3.0).into_dyn());
+
+        z.backward();
+        assert_eq!(x.borrow().get_grad_f(), array!(1.0, 0.0, 1.0).into_dyn());
+        assert_eq!(y.borrow().get_grad_f(), array!(1.0).into_dyn());
+    }
+
+    #[test]
+    fn where_broadcasts_cond() {
+        let x = Variable::new(array!([1.0], [2.0]).into_dyn());
+        let y = Variable::new(Array::zeros((2, 3)).into_dyn());
+        let cond = array!(true, false, true).into_dyn();
+
+        let mut z = where_(&cond, &x, &y);
+        assert_eq!(
+            z.borrow().data,
+            array!([1.0, 0.0, 1.0], [2.0, 0.0, 2.0]).into_dyn()
+        );
+
+        z.backward();
+        assert_eq!(x.borrow().get_grad_f(), array!([2.0], [2.0]).into_dyn());
+        assert_eq!(
+            y.borrow().get_grad_f(),
+            array!([0.0, 1.0, 0.0], [0.0, 1.0, 0.0]).into_dyn()
+        );
+    }
+}
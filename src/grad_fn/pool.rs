@@ -0,0 +1,418 @@
+use ndarray::{Array, Array4, ArrayView4, Axis, IxDyn, NdFloat};
+
+use crate::grad_fn::conv::{view4, ConvParams, ConvParams1d};
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+// the pooling ops work on (N, C, H, W) arrays, a (N, C, L) input is pooled as (N, C, 1, L)
+// and the output keeps the number of axes of the input
+
+fn restore_ndim<T: NdFloat>(x: Array4<T>, ndim: usize) -> Array<T, IxDyn> {
+    if ndim == 3 {
+        x.index_axis_move(Axis(2), 0).into_dyn()
+    } else {
+        x.into_dyn()
+    }
+}
+
+/// in bounds input positions read by the window of the output `(oi, oj)`
+fn window(
+    out: (usize, usize),
+    kernel: (usize, usize),
+    params: &ConvParams,
+    input: (usize, usize),
+) -> Vec<(usize, usize)> {
+    let mut positions = Vec::with_capacity(kernel.0 * kernel.1);
+    for ki in 0..kernel.0 {
+        for kj in 0..kernel.1 {
+            let hi = (out.0 * params.stride.0 + ki * params.dilation.0) as isize
+                - params.padding.0 as isize;
+            let wj = (out.1 * params.stride.1 + kj * params.dilation.1) as isize
+                - params.padding.1 as isize;
+            if hi >= 0 && wj >= 0 && (hi as usize) < input.0 && (wj as usize) < input.1 {
+                positions.push((hi as usize, wj as usize));
+            }
+        }
+    }
+    positions
+}
+
+fn check_padding(kernel: (usize, usize), params: &ConvParams) {
+    assert!(
+        params.padding.0 <= kernel.0 / 2 && params.padding.1 <= kernel.1 / 2,
+        "padding should be at most half of the kernel size"
+    );
+}
+
+pub struct MaxPool2d {
+    pub kernel: (usize, usize),
+    pub params: ConvParams,
+}
+
+impl MaxPool2d {
+    /// position of the first maximum of every window, in the `(oh, ow)` output
+    fn argmax<T: NdFloat>(&self, x: &ArrayView4<T>) -> Array4<(usize, usize)> {
+        let (n, c, h, w) = x.dim();
+        let (oh, ow) = self.params.output_size((h, w), self.kernel);
+
+        Array4::from_shape_fn((n, c, oh, ow), |(b, ci, oi, oj)| {
+            let positions = window((oi, oj), self.kernel, &self.params, (h, w));
+            let mut best = positions[0];
+            for &pos in positions.iter().skip(1) {
+                if x[[b, ci, pos.0, pos.1]] > x[[b, ci, best.0, best.1]] {
+                    best = pos;
+                }
+            }
+            best
+        })
+    }
+}
+
+impl<T> GradFn<T> for MaxPool2d
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        check_padding(self.kernel, &self.params);
+        let input = view4(x);
+        let argmax = self.argmax(&input);
+
+        let mut out = Array4::<T>::zeros(argmax.raw_dim());
+        for ((b, ci, oi, oj), &(hi, wj)) in argmax.indexed_iter() {
+            out[[b, ci, oi, oj]] = input[[b, ci, hi, wj]];
+        }
+        restore_ndim(out, x.ndim())
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let input = view4(x);
+        let grad_out = view4(grad);
+
+        let mut grad_x = Array4::<T>::zeros(input.raw_dim());
+        for ((b, ci, oi, oj), &(hi, wj)) in self.argmax(&input).indexed_iter() {
+            grad_x[[b, ci, hi, wj]] += grad_out[[b, ci, oi, oj]];
+        }
+        let grad_x = restore_ndim(grad_x, x.ndim());
+        let zero = Array::<T, IxDyn>::zeros(grad_x.raw_dim());
+
+        [grad_x, zero]
+    }
+}
+
+/// average over the windows, the zero padding is counted in the average as in pytorch
+pub struct AvgPool2d {
+    pub kernel: (usize, usize),
+    pub params: ConvParams,
+}
+
+impl<T> GradFn<T> for AvgPool2d
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        check_padding(self.kernel, &self.params);
+        let input = view4(x);
+        let (n, c, h, w) = input.dim();
+        let (oh, ow) = self.params.output_size((h, w), self.kernel);
+        let area = T::from(self.kernel.0 * self.kernel.1).unwrap();
+
+        let out = Array4::from_shape_fn((n, c, oh, ow), |(b, ci, oi, oj)| {
+            window((oi, oj), self.kernel, &self.params, (h, w))
+                .iter()
+                .fold(T::zero(), |acc, &(hi, wj)| acc + input[[b, ci, hi, wj]])
+                / area
+        });
+        restore_ndim(out, x.ndim())
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let input = view4(x);
+        let grad_out = view4(grad);
+        let (_, _, h, w) = input.dim();
+        let area = T::from(self.kernel.0 * self.kernel.1).unwrap();
+
+        let mut grad_x = Array4::<T>::zeros(input.raw_dim());
+        for ((b, ci, oi, oj), &g) in grad_out.indexed_iter() {
+            for (hi, wj) in window((oi, oj), self.kernel, &self.params, (h, w)) {
+                grad_x[[b, ci, hi, wj]] += g / area;
+            }
+        }
+        let grad_x = restore_ndim(grad_x, x.ndim());
+        let zero = Array::<T, IxDyn>::zeros(grad_x.raw_dim());
+
+        [grad_x, zero]
+    }
+}
+
+/// bounds `[start, end)` of the `i`-th of `out` adaptive bins over `len` positions
+fn adaptive_bin(i: usize, out: usize, len: usize) -> (usize, usize) {
+    (i * len / out, ((i + 1) * len).div_ceil(out))
+}
+
+/// average over `output_size` bins of nearly equal sizes, which may overlap
+pub struct AdaptiveAvgPool2d {
+    pub output_size: (usize, usize),
+}
+
+impl AdaptiveAvgPool2d {
+    /// bins of the output `(oi, oj)`, with the number of positions they hold
+    fn bins(
+        &self,
+        out: (usize, usize),
+        input: (usize, usize),
+    ) -> ((usize, usize), (usize, usize), usize) {
+        let rows = adaptive_bin(out.0, self.output_size.0, input.0);
+        let cols = adaptive_bin(out.1, self.output_size.1, input.1);
+        (rows, cols, (rows.1 - rows.0) * (cols.1 - cols.0))
+    }
+}
+
+impl<T> GradFn<T> for AdaptiveAvgPool2d
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let input = view4(x);
+        let (n, c, h, w) = input.dim();
+        let (oh, ow) = self.output_size;
+
+        let out = Array4::from_shape_fn((n, c, oh, ow), |(b, ci, oi, oj)| {
+            let (rows, cols, count) = self.bins((oi, oj), (h, w));
+            let mut acc = T::zero();
+            for hi in rows.0..rows.1 {
+                for wj in cols.0..cols.1 {
+                    acc += input[[b, ci, hi, wj]];
+                }
+            }
+            acc / T::from(count).unwrap()
+        });
+        restore_ndim(out, x.ndim())
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let x = &left_ref.borrow().data;
+        let input = view4(x);
+        let grad_out = view4(grad);
+        let (_, _, h, w) = input.dim();
+
+        let mut grad_x = Array4::<T>::zeros(input.raw_dim());
+        for ((b, ci, oi, oj), &g) in grad_out.indexed_iter() {
+            let (rows, cols, count) = self.bins((oi, oj), (h, w));
+            let g = g / T::from(count).unwrap();
+            for hi in rows.0..rows.1 {
+                for wj in cols.0..cols.1 {
+                    grad_x[[b, ci, hi, wj]] += g;
+                }
+            }
+        }
+        let grad_x = restore_ndim(grad_x, x.ndim());
+        let zero = Array::<T, IxDyn>::zeros(grad_x.raw_dim());
+
+        [grad_x, zero]
+    }
+}
+
+impl<T> VariableRef<T>
+where
+    T: NdFloat,
+{
+    /// max over the `kernel` windows of a `(N, C, H, W)` input, the gradient goes to the first
+    /// maximum of each window
+    pub fn max_pool2d(&mut self, kernel: (usize, usize), params: ConvParams) -> VariableRef<T> {
+        let grad_fn = MaxPool2d { kernel, params };
+        grad_fn.subscribe(self, self, Box::new(MaxPool2d { kernel, params }))
+    }
+
+    /// average over the `kernel` windows of a `(N, C, H, W)` input
+    pub fn avg_pool2d(&mut self, kernel: (usize, usize), params: ConvParams) -> VariableRef<T> {
+        assert_eq!(
+            params.dilation,
+            (1, 1),
+            "avg_pool2d does not support dilation"
+        );
+        let grad_fn = AvgPool2d { kernel, params };
+        grad_fn.subscribe(self, self, Box::new(AvgPool2d { kernel, params }))
+    }
+
+    /// average a `(N, C, H, W)` input into a `(N, C, output_size.0, output_size.1)` one
+    pub fn adaptive_avg_pool2d(&mut self, output_size: (usize, usize)) -> VariableRef<T> {
+        let grad_fn = AdaptiveAvgPool2d { output_size };
+        grad_fn.subscribe(self, self, Box::new(AdaptiveAvgPool2d { output_size }))
+    }
+
+    pub fn max_pool1d(&mut self, kernel: usize, params: ConvParams1d) -> VariableRef<T> {
+        self.max_pool2d((1, kernel), params.to_2d())
+    }
+
+    pub fn avg_pool1d(&mut self, kernel: usize, params: ConvParams1d) -> VariableRef<T> {
+        self.avg_pool2d((1, kernel), params.to_2d())
+    }
+
+    pub fn adaptive_avg_pool1d(&mut self, output_size: usize) -> VariableRef<T> {
+        self.adaptive_avg_pool2d((1, output_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::grad_fn::gradcheck::{arange, gradcheck};
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    fn square(size: usize) -> Array<f64, IxDyn> {
+        Array::from_shape_vec(
+            (1, 1, size, size),
+            (0..size * size).map(|a| a as f64).collect(),
+        )
+        .unwrap()
+        .into_dyn()
+    }
+
+    fn pooling(stride: usize, padding: usize) -> ConvParams {
+        ConvParams {
+            stride: (stride, stride),
+            padding: (padding, padding),
+            dilation: (1, 1),
+        }
+    }
+
+    #[test]
+    fn max_pool2d_check_backward() {
+        let mut x = Variable::new(square(4));
+
+        let mut z = x.max_pool2d((2, 2), pooling(2, 0));
+        assert_eq!(
+            z.borrow().data,
+            Array::from_shape_vec((1, 1, 2, 2), vec![5.0, 7.0, 13.0, 15.0])
+                .unwrap()
+                .into_dyn()
+        );
+
+        z.backward();
+        let mut expected = Array::<f64, _>::zeros((1, 1, 4, 4));
+        for &(i, j) in [(1, 1), (1, 3), (3, 1), (3, 3)].iter() {
+            expected[[0, 0, i, j]] = 1.;
+        }
+        assert_eq!(x.borrow().get_grad_f(), expected.into_dyn());
+    }
+
+    #[test]
+    fn max_pool2d_overlapping_windows() {
+        // a maximum shared by overlapping windows receives the gradient of each of them
+        let mut input = square(3);
+        input[[0, 0, 1, 1]] = 100.;
+        let mut x = Variable::new(input);
+
+        let mut z = x.max_pool2d((2, 2), pooling(1, 0));
+        z.backward();
+
+        let mut expected = Array::<f64, _>::zeros((1, 1, 3, 3));
+        expected[[0, 0, 1, 1]] = 4.;
+        assert_eq!(x.borrow().get_grad_f(), expected.into_dyn());
+    }
+
+    #[test]
+    fn avg_pool2d_check_method() {
+        let mut x = Variable::new(square(3));
+
+        // zero padding is counted in the average
+        let z = x.avg_pool2d((2, 2), pooling(2, 1));
+        assert_eq!(
+            z.borrow().data,
+            Array::from_shape_vec((1, 1, 2, 2), vec![0.0, 0.75, 2.25, 6.0])
+                .unwrap()
+                .into_dyn()
+        );
+    }
+
+    #[test]
+    fn adaptive_avg_pool_check_method() {
+        let mut x = Variable::new(array!([[1.0, 2.0, 3.0, 4.0, 5.0]]).into_dyn());
+
+        // bins [0, 2), [1, 4) and [3, 5)
+        let z = x.adaptive_avg_pool1d(3);
+        assert_eq!(z.borrow().data, array!([[1.5, 3.0, 4.5]]).into_dyn());
+
+        let z = x.adaptive_avg_pool1d(1);
+        assert_eq!(z.borrow().data, array!([[3.0]]).into_dyn());
+    }
+
+    #[test]
+    fn check_backward() {
+        let params = ConvParams {
+            stride: (2, 1),
+            padding: (1, 1),
+            dilation: (1, 2),
+        };
+        gradcheck(
+            |v| v[0].clone().max_pool2d((3, 2), params),
+            &[arange(&[2, 2, 6, 5])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+        gradcheck(
+            |v| v[0].clone().avg_pool2d((3, 2), pooling(2, 1)),
+            &[arange(&[2, 2, 6, 5])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+        gradcheck(
+            |v| v[0].clone().adaptive_avg_pool2d((4, 3)),
+            &[arange(&[2, 2, 6, 5])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn check_backward_1d() {
+        let params = ConvParams1d {
+            stride: 2,
+            padding: 1,
+            dilation: 1,
+        };
+        gradcheck(
+            |v| v[0].clone().max_pool1d(3, params),
+            &[arange(&[2, 3, 9])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+        gradcheck(
+            |v| v[0].clone().avg_pool1d(3, params),
+            &[arange(&[2, 3, 9])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+        gradcheck(
+            |v| v[0].clone().adaptive_avg_pool1d(4),
+            &[arange(&[2, 3, 9])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+}
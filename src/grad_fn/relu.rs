@@ -1,5 +1,6 @@
 use ndarray::{Array, IxDyn, NdFloat, Zip};
 
+use crate::serialize::onnx::Op;
 use crate::variable::GradFn;
 use crate::variable::VariableRef;
 
@@ -30,6 +31,10 @@ where
 
         [grad, zero]
     }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::unary("Relu")])
+    }
 }
 
 impl<T> VariableRef<T>
@@ -0,0 +1,211 @@
+use ndarray::{Array, IxDyn, NdFloat};
+
+use crate::serialize::onnx::{Attribute, Op, Tensor};
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+pub struct Reshape {
+    pub shape: Vec<usize>,
+}
+
+impl<T> GradFn<T> for Reshape
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        assert_eq!(
+            x.len(),
+            self.shape.iter().product::<usize>(),
+            "cannot reshape an array of shape {:?} into {:?}",
+            x.shape(),
+            self.shape
+        );
+        x.as_standard_layout()
+            .into_owned()
+            .into_shape(self.shape.clone())
+            .unwrap()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let shape = left_ref.borrow().data.raw_dim();
+
+        let grad = grad
+            .as_standard_layout()
+            .into_owned()
+            .into_shape(shape)
+            .unwrap();
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [grad, zero]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        let shape: Vec<i64> = self.shape.iter().map(|&len| len as i64).collect();
+        Some(vec![
+            Op::unary("Reshape").with_constant(Tensor::int64("", &shape))
+        ])
+    }
+}
+
+pub struct Permute {
+    pub axes: Vec<usize>,
+}
+
+impl<T> GradFn<T> for Permute
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x.view()
+            .permuted_axes(self.axes.clone())
+            .as_standard_layout()
+            .into_owned()
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        _left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let mut inverse = vec![0; self.axes.len()];
+        for (i, &axis) in self.axes.iter().enumerate() {
+            inverse[axis] = i;
+        }
+
+        let grad = grad
+            .view()
+            .permuted_axes(inverse)
+            .as_standard_layout()
+            .into_owned();
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [grad, zero]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        let perm = self.axes.iter().map(|&axis| axis as i64).collect();
+        Some(vec![
+            Op::unary("Transpose").with_attribute("perm", Attribute::Ints(perm))
+        ])
+    }
+}
+
+impl<T> VariableRef<T>
+where
+    T: NdFloat,
+{
+    pub fn reshape(&mut self, shape: &[usize]) -> VariableRef<T> {
+        let grad_fn = Reshape {
+            shape: shape.to_vec(),
+        };
+        grad_fn.subscribe(
+            self,
+            self,
+            Box::new(Reshape {
+                shape: shape.to_vec(),
+            }),
+        )
+    }
+
+    /// merge the axes from `start_axis` to `end_axis` included into a single one
+    pub fn flatten(&mut self, start_axis: usize, end_axis: usize) -> VariableRef<T> {
+        let shape = self.borrow().data.shape().to_vec();
+        assert!(start_axis <= end_axis && end_axis < shape.len());
+
+        let mut new_shape = shape[..start_axis].to_vec();
+        new_shape.push(shape[start_axis..=end_axis].iter().product());
+        new_shape.extend_from_slice(&shape[end_axis + 1..]);
+
+        self.reshape(&new_shape)
+    }
+
+    /// reorder the axes, the axis `i` of the output is the axis `axes[i]` of `self`
+    pub fn permute(&mut self, axes: &[usize]) -> VariableRef<T> {
+        let grad_fn = Permute {
+            axes: axes.to_vec(),
+        };
+        grad_fn.subscribe(
+            self,
+            self,
+            Box::new(Permute {
+                axes: axes.to_vec(),
+            }),
+        )
+    }
+
+    /// swap two axes
+    pub fn transpose(&mut self, axis_a: usize, axis_b: usize) -> VariableRef<T> {
+        let mut axes: Vec<usize> = (0..self.borrow().data.ndim()).collect();
+        axes.swap(axis_a, axis_b);
+
+        self.permute(&axes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::grad_fn::gradcheck::{arange, gradcheck};
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn reshape_check_method() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+
+        let z = x.reshape(&[3, 2]);
+        assert_eq!(
+            z.borrow().data,
+            array!([1.0, 2.0], [3.0, 4.0], [5.0, 6.0]).into_dyn()
+        );
+
+        let z = x.flatten(0, 1);
+        assert_eq!(
+            z.borrow().data,
+            array!(1.0, 2.0, 3.0, 4.0, 5.0, 6.0).into_dyn()
+        );
+    }
+
+    #[test]
+    fn transpose_check_method() {
+        let mut x = Variable::new(array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]).into_dyn());
+
+        let z = x.transpose(0, 1);
+        assert_eq!(
+            z.borrow().data,
+            array!([1.0, 4.0], [2.0, 5.0], [3.0, 6.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn check_backward() {
+        gradcheck(
+            |v| v[0].clone().flatten(1, 2),
+            &[arange(&[2, 3, 4, 2])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+        gradcheck(
+            |v| v[0].clone().permute(&[2, 0, 3, 1]),
+            &[arange(&[2, 3, 4, 2])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+        // the permuted gradient is read back through a transposed view of the input
+        gradcheck(
+            |v| &v[0].clone().transpose(0, 1) * &v[1],
+            &[arange(&[2, 3]), arange(&[3, 2])],
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+    }
+}
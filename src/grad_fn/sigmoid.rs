@@ -0,0 +1,72 @@
+use ndarray::{Array, IxDyn, NdFloat};
+
+use crate::serialize::onnx::Op;
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+pub struct Sigmoid {}
+
+impl<T> GradFn<T> for Sigmoid
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        x.mapv(|a| T::one() / (T::one() + (-a).exp()))
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let data = &left_ref.borrow().data;
+
+        let sigmoid = self.forward(data, data);
+        let grad = grad * &sigmoid.mapv(|s| s * (T::one() - s));
+        let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+        [grad, zero]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![Op::unary("Sigmoid")])
+    }
+}
+
+impl<T> VariableRef<T>
+where
+    T: NdFloat,
+{
+    pub fn sigmoid(&mut self) -> VariableRef<T> {
+        let grad_fn = Sigmoid {};
+        grad_fn.subscribe(self, self, Box::new(Sigmoid {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn check_method() {
+        let mut x = Variable::new(array!(0.0, 2.0_f64.ln()).into_dyn());
+
+        let z = x.sigmoid();
+        assert_eq!(z.borrow().data, array!(0.5, 2.0 / 3.0).into_dyn());
+    }
+
+    #[test]
+    fn check_backward() {
+        let mut x = Variable::new(array!(0.0, 2.0_f64.ln()).into_dyn());
+
+        let mut z = x.sigmoid();
+        z.backward();
+
+        let grad = x.borrow().get_grad_f();
+        assert_eq!(grad[0], 0.25);
+        assert!((grad[1] - 2.0 / 9.0).abs() < 1e-15);
+    }
+}
@@ -1,5 +1,7 @@
-use ndarray::{Array, Dimension, NdFloat};
+use ndarray::{Array, Axis, Dimension, IxDyn, NdFloat, Zip};
 
+use crate::serialize::onnx::{Attribute, Op};
+use crate::variable::GradFn;
 use crate::variable::VariableRef;
 
 pub fn max<T, D>(x: &Array<T, D>) -> T
@@ -21,6 +23,8 @@ impl<T> VariableRef<T>
 where
     T: NdFloat,
 {
+    /// softmax over all the elements. The maximum is subtracted in place from the data of an
+    /// identity node, so the graph can not be exported to ONNX, unlike `softmax_axis`
     pub fn softmax(&mut self) -> VariableRef<T> {
         let mut copy = self.identity();
         copy.borrow_mut().data -= max(&self.borrow().data);
@@ -30,6 +34,60 @@ where
 
         exp / exp_sum
     }
+
+    /// softmax over each lane along `axis`, the other axes are independent
+    pub fn softmax_axis(&mut self, axis: usize) -> VariableRef<T> {
+        let grad_fn = SoftmaxAxis { axis };
+        grad_fn.subscribe(self, self, Box::new(SoftmaxAxis { axis }))
+    }
+}
+
+/// fused softmax along one axis, the maximum of each lane is subtracted for stability
+pub struct SoftmaxAxis {
+    pub axis: usize,
+}
+
+impl<T> GradFn<T> for SoftmaxAxis
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let mut out = x.clone();
+        for mut lane in out.lanes_mut(Axis(self.axis)) {
+            let max = lane.fold(T::neg_infinity(), |m, &a| m.max(a));
+            lane.mapv_inplace(|a| (a - max).exp());
+            let sum = lane.sum();
+            lane.mapv_inplace(|a| a / sum);
+        }
+        out
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        _right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let data = &left_ref.borrow().data;
+        let softmax = self.forward(data, data);
+
+        let mut new_grad = grad * &softmax;
+        Zip::from(new_grad.lanes_mut(Axis(self.axis)))
+            .and(softmax.lanes(Axis(self.axis)))
+            .for_each(|mut g, s| {
+                let dot = g.sum();
+                Zip::from(&mut g).and(&s).for_each(|g, &s| *g -= s * dot);
+            });
+        let zero = Array::<T, IxDyn>::zeros(new_grad.raw_dim());
+
+        [new_grad, zero]
+    }
+
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![
+            Op::unary("Softmax").with_attribute("axis", Attribute::Int(self.axis as i64))
+        ])
+    }
 }
 
 #[cfg(test)]
@@ -50,4 +108,34 @@ mod tests {
 
         assert_eq!(x.softmax().borrow().data, res.into_dyn());
     }
+
+    #[test]
+    fn softmax_axis_check_method() {
+        let mut x = Variable::new(array!([0.0, 1.0], [0.0, 1.0], [2.0_f64.ln(), 1.0]).into_dyn());
+
+        let z = x.softmax_axis(0);
+        let diff =
+            &z.borrow().data - &array!([0.25, 1. / 3.], [0.25, 1. / 3.], [0.5, 1. / 3.]).into_dyn();
+        assert!(diff.iter().all(|d| d.abs() < 1e-12));
+
+        // large values do not overflow
+        let mut x = Variable::new(array!([1000.0, 1000.0]).into_dyn());
+        assert_eq!(
+            x.softmax_axis(1).borrow().data,
+            array!([0.5, 0.5]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn softmax_axis_gradcheck() {
+        use crate::grad_fn::gradcheck::gradcheck;
+        use ndarray::Array;
+
+        let x = Array::from_shape_fn((2, 3, 4), |(i, j, k)| ((i * 12 + j * 4 + k) as f64).sin())
+            .into_dyn();
+        for axis in 0..3 {
+            let f = |v: &[crate::variable::VariableRef<f64>]| v[0].clone().softmax_axis(axis);
+            gradcheck(f, std::slice::from_ref(&x), 1e-6, 1e-6).unwrap();
+        }
+    }
 }
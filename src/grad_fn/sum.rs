@@ -1,5 +1,6 @@
 use ndarray::{Array, Ix1, IxDyn, NdFloat};
 
+use crate::serialize::onnx::{Attribute, Op, Tensor};
 use crate::variable::GradFn;
 use crate::variable::VariableRef;
 
@@ -27,6 +28,14 @@ where
 
         [grad, zero]
     }
+
+    /// `ReduceSum` gives a scalar, reshaped to the `[1]` of `forward`
+    fn onnx(&self) -> Option<Vec<Op>> {
+        Some(vec![
+            Op::unary("ReduceSum").with_attribute("keepdims", Attribute::Int(0)),
+            Op::unary("Reshape").with_constant(Tensor::int64("", &[1])),
+        ])
+    }
 }
 
 impl<T> VariableRef<T>
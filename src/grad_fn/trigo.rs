@@ -0,0 +1,322 @@
+use ndarray::{Array, IxDyn, NdFloat, Zip};
+
+use crate::grad_fn::operator::{broadcast_shape, sum_to_shape};
+use crate::serialize::onnx::Op;
+use crate::variable::GradFn;
+use crate::variable::VariableRef;
+
+/// error function, computed in f64 with the rational Chebyshev approximations of W. J. Cody
+/// (Math. Comp. 1969) on `|x| <= 0.5`, `0.5 < |x| <= 4` and `|x| > 4`, accurate to about
+/// 1e-16 so that it agrees with its exact derivative
+// the coefficients are kept as published
+#[allow(clippy::excessive_precision)]
+pub fn erf<T: NdFloat>(x: T) -> T {
+    let x = x.to_f64().unwrap();
+    let y = x.abs();
+
+    if y <= 0.5 {
+        const A: [f64; 5] = [
+            3.16112374387056560e00,
+            1.13864154151050156e02,
+            3.77485237685302021e02,
+            3.20937758913846947e03,
+            1.85777706184603153e-1,
+        ];
+        const B: [f64; 4] = [
+            2.36012909523441209e01,
+            2.44024637934444173e02,
+            1.28261652607737228e03,
+            2.84423683343917062e03,
+        ];
+        let ysq = y * y;
+        let mut num = A[4] * ysq;
+        let mut den = ysq;
+        for i in 0..3 {
+            num = (num + A[i]) * ysq;
+            den = (den + B[i]) * ysq;
+        }
+        return T::from(x * (num + A[3]) / (den + B[3])).unwrap();
+    }
+
+    let erfc = if y <= 4. {
+        const C: [f64; 9] = [
+            5.64188496988670089e-1,
+            8.88314979438837594e00,
+            6.61191906371416295e01,
+            2.98635138197400131e02,
+            8.81952221241769090e02,
+            1.71204761263407058e03,
+            2.05107837782607147e03,
+            1.23033935479799725e03,
+            2.15311535474403846e-8,
+        ];
+        const D: [f64; 8] = [
+            1.57449261107098347e01,
+            1.17693950891312499e02,
+            5.37181101862009858e02,
+            1.62138957456669019e03,
+            3.29079923573345963e03,
+            4.36261909014324716e03,
+            3.43936767414372164e03,
+            1.23033935480374942e03,
+        ];
+        let mut num = C[8] * y;
+        let mut den = y;
+        for i in 0..7 {
+            num = (num + C[i]) * y;
+            den = (den + D[i]) * y;
+        }
+        (num + C[7]) / (den + D[7])
+    } else {
+        const P: [f64; 6] = [
+            3.05326634961232344e-1,
+            3.60344899949804439e-1,
+            1.25781726111229246e-1,
+            1.60837851487422766e-2,
+            6.58749161529837803e-4,
+            1.63153871373020978e-2,
+        ];
+        const Q: [f64; 5] = [
+            2.56852019228982242e00,
+            1.87295284992346725e00,
+            5.27905102951428412e-1,
+            6.05183413124413191e-2,
+            2.33520497626869185e-3,
+        ];
+        let ysq = 1. / (y * y);
+        let mut num = P[5] * ysq;
+        let mut den = ysq;
+        for i in 0..4 {
+            num = (num + P[i]) * ysq;
+            den = (den + Q[i]) * ysq;
+        }
+        let r = ysq * (num + P[4]) / (den + Q[4]);
+        (std::f64::consts::FRAC_2_SQRT_PI / 2. - r) / y
+    };
+    // exp(-y^2) as exp(-y_hi^2) exp(-(y - y_hi)(y + y_hi)) to keep the rounding of y^2 out
+    let y_hi = (y * 16.).trunc() / 16.;
+    let erfc = (-y_hi * y_hi).exp() * (-(y - y_hi) * (y + y_hi)).exp() * erfc;
+
+    T::from(if x >= 0. { 1. - erfc } else { erfc - 1. }).unwrap()
+}
+
+macro_rules! impl_unary_op {
+    ($trt:ident, $mth:ident, $forward:expr, $derivative:expr) => {
+        pub struct $trt {}
+
+        impl<T> GradFn<T> for $trt
+        where
+            T: NdFloat,
+        {
+            fn forward(&self, x: &Array<T, IxDyn>, _y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+                x.mapv($forward)
+            }
+
+            fn backward<'a>(
+                &self,
+                grad: &'a Array<T, IxDyn>,
+                left_ref: &'a VariableRef<T>,
+                _right_ref: &'a VariableRef<T>,
+            ) -> [Array<T, IxDyn>; 2] {
+                let data = &left_ref.borrow().data;
+
+                let grad = grad * &data.mapv($derivative);
+                let zero = Array::<T, IxDyn>::zeros(grad.raw_dim());
+
+                [grad, zero]
+            }
+
+            /// the ONNX operators have the same names
+            fn onnx(&self) -> Option<Vec<Op>> {
+                Some(vec![Op::unary(stringify!($trt))])
+            }
+        }
+
+        impl<T> VariableRef<T>
+        where
+            T: NdFloat,
+        {
+            pub fn $mth(&mut self) -> VariableRef<T> {
+                let grad_fn = $trt {};
+                grad_fn.subscribe(self, self, Box::new($trt {}))
+            }
+        }
+    };
+}
+
+impl_unary_op!(Sin, sin, |a| a.sin(), |a| a.cos());
+impl_unary_op!(Cos, cos, |a| a.cos(), |a| -a.sin());
+impl_unary_op!(Tan, tan, |a| a.tan(), |a| T::one() / a.cos().powi(2));
+impl_unary_op!(Asin, asin, |a| a.asin(), |a| (T::one() - a * a)
+    .sqrt()
+    .recip());
+impl_unary_op!(Acos, acos, |a| a.acos(), |a| -(T::one() - a * a)
+    .sqrt()
+    .recip());
+impl_unary_op!(Atan, atan, |a| a.atan(), |a| T::one() / (T::one() + a * a));
+impl_unary_op!(Sinh, sinh, |a| a.sinh(), |a| a.cosh());
+impl_unary_op!(Cosh, cosh, |a| a.cosh(), |a| a.sinh());
+impl_unary_op!(Tanh, tanh, |a| a.tanh(), |a| T::one() - a.tanh().powi(2));
+impl_unary_op!(Erf, erf, erf, |a| {
+    let two_over_sqrt_pi = T::from(std::f64::consts::FRAC_2_SQRT_PI).unwrap();
+    two_over_sqrt_pi * (-a * a).exp()
+});
+
+pub struct Atan2 {}
+
+impl<T> GradFn<T> for Atan2
+where
+    T: NdFloat,
+{
+    fn forward(&self, x: &Array<T, IxDyn>, y: &Array<T, IxDyn>) -> Array<T, IxDyn> {
+        let mut out = Array::<T, IxDyn>::zeros(broadcast_shape(x.shape(), y.shape()));
+        Zip::from(&mut out)
+            .and_broadcast(x)
+            .and_broadcast(y)
+            .for_each(|o, &a, &b| {
+                *o = a.atan2(b);
+            });
+        out
+    }
+
+    fn backward<'a>(
+        &self,
+        grad: &'a Array<T, IxDyn>,
+        left_ref: &'a VariableRef<T>,
+        right_ref: &'a VariableRef<T>,
+    ) -> [Array<T, IxDyn>; 2] {
+        let y = &left_ref.borrow().data;
+        let x = &right_ref.borrow().data;
+
+        let norm = &(y * y) + &(x * x);
+        let grad_y = grad * &(x / &norm);
+        let grad_x = grad * &(-(y / &norm));
+
+        [
+            sum_to_shape(&grad_y, y.shape()),
+            sum_to_shape(&grad_x, x.shape()),
+        ]
+    }
+}
+
+impl<T> VariableRef<T>
+where
+    T: NdFloat,
+{
+    /// four quadrant arctangent of `self / other`
+    pub fn atan2(&mut self, other: &VariableRef<T>) -> VariableRef<T> {
+        let grad_fn = Atan2 {};
+        grad_fn.subscribe(self, other, Box::new(Atan2 {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    fn check_unary(
+        op: fn(&mut VariableRef<f64>) -> VariableRef<f64>,
+        reference: fn(f64) -> f64,
+        derivative: fn(f64) -> f64,
+    ) {
+        let input = array!([-0.7, -0.2], [0.1, 0.6]).into_dyn();
+        let mut x = Variable::new(input.clone());
+
+        let mut z = op(&mut x);
+        z.backward();
+
+        for (i, &a) in input.iter().enumerate() {
+            let out = z.borrow().data.iter().nth(i).copied().unwrap();
+            let grad = x.borrow().get_grad_f().iter().nth(i).copied().unwrap();
+            assert!((out - reference(a)).abs() < 1e-12);
+            assert!((grad - derivative(a)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn sin_cos_tan_check_backward() {
+        check_unary(VariableRef::sin, f64::sin, f64::cos);
+        check_unary(VariableRef::cos, f64::cos, |a| -a.sin());
+        check_unary(VariableRef::tan, f64::tan, |a| 1. + a.tan().powi(2));
+    }
+
+    #[test]
+    fn inverse_check_backward() {
+        check_unary(VariableRef::asin, f64::asin, |a| 1. / (1. - a * a).sqrt());
+        check_unary(VariableRef::acos, f64::acos, |a| -1. / (1. - a * a).sqrt());
+        check_unary(VariableRef::atan, f64::atan, |a| 1. / (1. + a * a));
+    }
+
+    #[test]
+    fn hyperbolic_check_backward() {
+        check_unary(VariableRef::sinh, f64::sinh, f64::cosh);
+        check_unary(VariableRef::cosh, f64::cosh, f64::sinh);
+        check_unary(VariableRef::tanh, f64::tanh, |a| 1. / a.cosh().powi(2));
+    }
+
+    #[test]
+    fn erf_check_method() {
+        // reference values from scipy.special.erf
+        let values = [
+            (0.0, 0.0_f64),
+            (0.5, 0.5204998778130465),
+            (1.0, 0.8427007929497149),
+            (-1.5, -0.9661051464753108),
+            (3.0, 0.9999779095030014),
+            (0.1, 0.1124629160182849),
+            (-0.75, -0.7111556336535151),
+            (4.5, 0.9999999998033839),
+            (-6.0, -1.0),
+        ];
+        for &(x, expected) in values.iter() {
+            assert!((erf(x) - expected).abs() < 1e-15, "erf({}) = {}", x, erf(x));
+        }
+    }
+
+    #[test]
+    fn erf_matches_its_derivative() {
+        let h = 1e-5;
+        for &x in [-4.2_f64, -1.0, 0.3, 0.5, 2.0, 4.0].iter() {
+            let slope = (erf(x + h) - erf(x - h)) / (2. * h);
+            let derivative = std::f64::consts::FRAC_2_SQRT_PI * (-x * x).exp();
+            assert!((slope - derivative).abs() < 1e-9, "{}", x);
+        }
+    }
+
+    #[test]
+    fn erf_check_backward() {
+        let mut x = Variable::new(array!(-1.0, 0.0, 0.5).into_dyn());
+
+        let mut z = x.erf();
+        z.backward();
+
+        let expected = array!(-1.0, 0.0, 0.5_f64)
+            .mapv(|a| std::f64::consts::FRAC_2_SQRT_PI * (-a * a).exp())
+            .into_dyn();
+        assert_eq!(x.borrow().get_grad_f(), expected);
+    }
+
+    #[test]
+    fn atan2_check_backward() {
+        let mut y = Variable::new(array!(1.0, -1.0, 2.0).into_dyn());
+        let x = Variable::new(array!(-1.0, -1.0, 0.0).into_dyn());
+
+        let mut z = y.atan2(&x);
+        assert_eq!(
+            z.borrow().data,
+            array!(1.0, -1.0, 2.0_f64)
+                .iter()
+                .zip([-1.0, -1.0, 0.0_f64].iter())
+                .map(|(a, b)| a.atan2(*b))
+                .collect::<Array<f64, _>>()
+                .into_dyn()
+        );
+
+        z.backward();
+        assert_eq!(y.borrow().get_grad_f(), array!(-0.5, -0.5, 0.0).into_dyn());
+        assert_eq!(x.borrow().get_grad_f(), array!(-0.5, 0.5, -0.5).into_dyn());
+    }
+}
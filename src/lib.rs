@@ -2,6 +2,9 @@ pub mod grad_fn;
 pub mod module;
 pub mod nn;
 pub mod optim;
+pub mod serialize;
 pub mod variable;
 
 pub mod data;
+
+pub use grad_fn::einsum::einsum;
@@ -1,15 +1,223 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
 use crate::variable::VariableRef;
 
-use ndarray::NdFloat;
+use ndarray::{Array, IxDyn, NdFloat};
+
+/// named parameters or buffers of a module
+pub type Named<T> = Vec<(String, VariableRef<T>)>;
+
+/// arrays of a module keyed by the dotted path of the parameter or buffer they belong to
+pub type StateDict<T> = BTreeMap<String, Array<T, IxDyn>>;
+
+/// prefix the names of a child module with its name in the parent, `weight` of the child
+/// `layers.1` becomes `layers.1.weight`
+pub fn prefixed<T: NdFloat>(prefix: &str, named: Named<T>) -> Named<T> {
+    named
+        .into_iter()
+        .map(|(name, var)| (format!("{}.{}", prefix, name), var))
+        .collect()
+}
+
+/// keys which could not be loaded by `Module::load_state_dict`
+#[derive(Debug, Default, PartialEq)]
+pub struct LoadReport {
+    /// keys of the module absent from the state dict, left untouched
+    pub missing_keys: Vec<String>,
+    /// keys of the state dict unknown to the module, ignored
+    pub unexpected_keys: Vec<String>,
+    /// keys whose array does not have the shape of the module's one, as
+    /// `(key, expected, found)`. They are left untouched
+    pub shape_mismatches: Vec<(String, Vec<usize>, Vec<usize>)>,
+}
+
+impl LoadReport {
+    /// every key of the module has been loaded and every key of the state dict used
+    pub fn is_ok(&self) -> bool {
+        self.missing_keys.is_empty()
+            && self.unexpected_keys.is_empty()
+            && self.shape_mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "missing keys: {:?}, unexpected keys: {:?}",
+            self.missing_keys, self.unexpected_keys
+        )?;
+        for (key, expected, found) in self.shape_mismatches.iter() {
+            write!(
+                f,
+                ", shape mismatch for {}: expected {:?} found {:?}",
+                key, expected, found
+            )?;
+        }
+        Ok(())
+    }
+}
 
 pub trait Module<T: NdFloat> {
     fn params(&self) -> Vec<VariableRef<T>>;
 
     fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T>;
 
+    /// non trainable state, like running statistics, which is not returned by `params` and
+    /// so never updated by an optimizer but belongs to the saved state of the module
+    fn buffers(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    /// `params` with their dotted path in the module, in the same order. Containers prefix
+    /// the names of their children with `prefixed`. By default the parameters are numbered
+    /// `"0"`, `"1"`... in the order of `params`, and these numbers become the keys of the
+    /// state dict and the names of the ONNX initializers. Such keys break as soon as a
+    /// parameter is added or reordered, so every module of the crate overrides this method and
+    /// so should any module whose saved state has to outlive its code
+    fn named_parameters(&self) -> Named<T> {
+        numbered(self.params())
+    }
+
+    /// `buffers` with their dotted path in the module, in the same order, numbered by default
+    /// as `named_parameters`
+    fn named_buffers(&self) -> Named<T> {
+        numbered(self.buffers())
+    }
+
+    /// copy of the parameters and buffers of the module
+    fn state_dict(&self) -> StateDict<T> {
+        self.named_parameters()
+            .into_iter()
+            .chain(self.named_buffers())
+            .map(|(name, var)| (name, var.borrow().data.clone()))
+            .collect()
+    }
+
+    /// copy the arrays of `state` into the parameters and buffers with the same name and
+    /// shape, and report the keys which do not match
+    fn load_state_dict(&mut self, state: &StateDict<T>) -> LoadReport {
+        let mut report = LoadReport::default();
+        let named: Named<T> = self
+            .named_parameters()
+            .into_iter()
+            .chain(self.named_buffers())
+            .collect();
+
+        for (name, mut var) in named.iter().cloned() {
+            match state.get(&name) {
+                None => report.missing_keys.push(name),
+                Some(array) => {
+                    let expected = var.borrow().data.shape().to_vec();
+                    if array.shape() == expected.as_slice() {
+                        var.borrow_mut().data = array.clone();
+                    } else {
+                        report
+                            .shape_mismatches
+                            .push((name, expected, array.shape().to_vec()));
+                    }
+                }
+            }
+        }
+        report.unexpected_keys = state
+            .keys()
+            .filter(|key| !named.iter().any(|(name, _)| name == *key))
+            .cloned()
+            .collect();
+        report
+    }
+
+    /// switch between training and evaluation behaviour, for the modules which have one.
+    /// Containers must forward the mode to their children
+    fn train(&mut self, _mode: bool) {}
+
+    fn eval(&mut self) {
+        self.train(false);
+    }
+
     fn zero_grad(&mut self) {
         for p in self.params().iter_mut() {
             p.borrow_mut().zero_grad();
         }
     }
 }
+
+fn numbered<T: NdFloat>(vars: Vec<VariableRef<T>>) -> Named<T> {
+    vars.into_iter()
+        .enumerate()
+        .map(|(i, var)| (i.to_string(), var))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::linear::{Linear, MLP};
+    use ndarray::Array;
+
+    fn mlp() -> MLP<f32> {
+        MLP {
+            layers: vec![Linear::new(2, 3), Linear::new(3, 1)],
+        }
+    }
+
+    #[test]
+    fn mlp_named_parameters() {
+        let model = mlp();
+        let names: Vec<String> = model
+            .named_parameters()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "layers.0.weight",
+                "layers.0.bias",
+                "layers.1.weight",
+                "layers.1.bias"
+            ]
+        );
+
+        let state = model.state_dict();
+        assert_eq!(state["layers.0.weight"].shape(), &[3, 2]);
+        assert_eq!(state["layers.1.bias"], model.layers[1].bias.borrow().data);
+    }
+
+    #[test]
+    fn load_state_dict_round_trip() {
+        let source = mlp();
+        let mut target = mlp();
+
+        let report = target.load_state_dict(&source.state_dict());
+        assert!(report.is_ok());
+        assert_eq!(target.state_dict(), source.state_dict());
+    }
+
+    #[test]
+    fn load_state_dict_report() {
+        let mut model = mlp();
+        let before = model.state_dict();
+
+        let mut state = before.clone();
+        state.remove("layers.0.bias");
+        state.insert("layers.2.weight".to_string(), Array::zeros(vec![1]));
+        state.insert("layers.1.weight".to_string(), Array::zeros(vec![2, 2]));
+        state.insert("layers.0.weight".to_string(), Array::ones(vec![3, 2]));
+
+        let report = model.load_state_dict(&state);
+        assert!(!report.is_ok());
+        assert_eq!(report.missing_keys, vec!["layers.0.bias"]);
+        assert_eq!(report.unexpected_keys, vec!["layers.2.weight"]);
+        assert_eq!(
+            report.shape_mismatches,
+            vec![("layers.1.weight".to_string(), vec![1, 3], vec![2, 2])]
+        );
+
+        // the matching keys are loaded, the others are left untouched
+        let after = model.state_dict();
+        assert_eq!(after["layers.0.weight"], Array::<f32, _>::ones(vec![3, 2]));
+        assert_eq!(after["layers.1.weight"], before["layers.1.weight"]);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::module::Module;
+use crate::variable::{Variable, VariableRef};
+use ndarray::{Array, NdFloat};
+
+macro_rules! impl_activation {
+    ($name:ident, $doc:expr, |$x:ident| $body:expr) => {
+        #[doc = $doc]
+        #[derive(Clone, Copy, Debug, Default)]
+        pub struct $name;
+
+        impl<T: NdFloat> Module<T> for $name {
+            fn params(&self) -> Vec<VariableRef<T>> {
+                vec![]
+            }
+
+            fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+                let mut $x = input.clone();
+                $body
+            }
+        }
+    };
+}
+
+impl_activation!(ReLU, "`max(x, 0)` element-wise", |x| x.relu());
+impl_activation!(Tanh, "hyperbolic tangent element-wise", |x| x.tanh());
+impl_activation!(Sigmoid, "`1 / (1 + exp(-x))` element-wise", |x| x.sigmoid());
+/// gaussian error linear unit `x * (1 + erf(x / sqrt(2))) / 2`, without approximation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GELU;
+
+impl<T: NdFloat> Module<T> for GELU {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        let constant =
+            |a: f64| Variable::new_no_retain_grad(Array::from_elem(vec![1], T::from(a).unwrap()));
+        let mut scaled = input * &constant(std::f64::consts::FRAC_1_SQRT_2);
+        let cdf = (scaled.erf() + constant(1.)) * constant(0.5);
+        input * &cdf
+    }
+}
+
+/// softmax over the lanes along `axis`, the features axis 0 for `Linear` outputs
+#[derive(Clone, Copy, Debug)]
+pub struct Softmax {
+    pub axis: usize,
+}
+
+impl Softmax {
+    pub fn new(axis: usize) -> Softmax {
+        Softmax { axis }
+    }
+}
+
+impl<T: NdFloat> Module<T> for Softmax {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        input.clone().softmax_axis(self.axis)
+    }
+}
+
+/// one of the activations above, named in lowercase in configs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Activation {
+    #[default]
+    ReLU,
+    Tanh,
+    Sigmoid,
+    GELU,
+}
+
+impl<T: NdFloat> Module<T> for Activation {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        match self {
+            Activation::ReLU => ReLU.f(input),
+            Activation::Tanh => Tanh.f(input),
+            Activation::Sigmoid => Sigmoid.f(input),
+            Activation::GELU => GELU.f(input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grad_fn::trigo::erf;
+    use ndarray::array;
+
+    #[test]
+    fn activations_forward() {
+        let x = &Variable::new(array!([-1.0, 0.0], [0.5, 2.0]).into_dyn());
+
+        assert_eq!(
+            ReLU.f(x).borrow().data,
+            array!([0.0, 0.0], [0.5, 2.0]).into_dyn()
+        );
+        assert_eq!(Tanh.f(x).borrow().data, x.borrow().data.mapv(f64::tanh));
+        assert_eq!(
+            Sigmoid.f(x).borrow().data[[1, 0]],
+            1. / (1. + (-0.5f64).exp())
+        );
+
+        let gelu = GELU.f(x).borrow().data.clone();
+        let expected = x
+            .borrow()
+            .data
+            .mapv(|a| a * 0.5 * (1. + erf(a * std::f64::consts::FRAC_1_SQRT_2)));
+        assert!((&gelu - &expected).iter().all(|d| d.abs() < 1e-12));
+
+        let probs = Softmax::new(0).f(x);
+        assert!((probs.borrow().data[[0, 0]] + probs.borrow().data[[1, 0]] - 1.).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::grad_fn::einsum::einsum;
+use crate::module::{prefixed, Module, Named};
+use crate::nn::dropout::Dropout;
+use crate::nn::linear::Linear;
+use crate::variable::{Variable, VariableRef};
+use ndarray::{Array, IxDyn, NdFloat};
+
+/// `(len, len)` mask hiding from each position the ones after it
+pub fn causal_mask(len: usize) -> Array<bool, IxDyn> {
+    Array::from_shape_fn((len, len), |(t, s)| s > t).into_dyn()
+}
+
+/// scaled dot-product attention with `num_heads` heads over `(seq, embed_dim, batch)`
+/// sequences, like the recurrent layers
+pub struct MultiheadAttention<T: NdFloat> {
+    pub embed_dim: usize,
+    pub num_heads: usize,
+    pub q_proj: Linear<T>,
+    pub k_proj: Linear<T>,
+    pub v_proj: Linear<T>,
+    pub out_proj: Linear<T>,
+    /// applied to the attention weights
+    pub dropout: Dropout,
+}
+
+impl MultiheadAttention<f32> {
+    pub fn new(embed_dim: usize, num_heads: usize) -> MultiheadAttention<f32> {
+        assert!(num_heads > 0, "attention with no heads");
+        assert!(
+            embed_dim % num_heads == 0,
+            "embed_dim must be divisible by num_heads"
+        );
+        MultiheadAttention {
+            embed_dim,
+            num_heads,
+            q_proj: Linear::new(embed_dim, embed_dim),
+            k_proj: Linear::new(embed_dim, embed_dim),
+            v_proj: Linear::new(embed_dim, embed_dim),
+            out_proj: Linear::new(embed_dim, embed_dim),
+            dropout: Dropout::new(0.),
+        }
+    }
+
+    /// the generator of the dropout is kept, seeded or not
+    pub fn with_dropout(mut self, p: f64) -> MultiheadAttention<f32> {
+        self.dropout.set_p(p);
+        self
+    }
+}
+
+impl<T: NdFloat> MultiheadAttention<T> {
+    /// split a `(seq, embed_dim, batch)` projection into `(seq, num_heads, head_dim, batch)`
+    fn split_heads(&self, x: &mut VariableRef<T>) -> VariableRef<T> {
+        let shape = x.borrow().data.shape().to_vec();
+        let head_dim = self.embed_dim / self.num_heads;
+        x.reshape(&[shape[0], self.num_heads, head_dim, shape[2]])
+    }
+
+    /// attend from the `(tgt, embed_dim, batch)` query to the `(src, embed_dim, batch)` key
+    /// and value. Positions where the `(tgt, src)` `attn_mask` or the `(batch, src)`
+    /// `key_padding_mask` are true are not attended to, a query which can attend to no position
+    /// gets all zero weights. Return the `(tgt, embed_dim, batch)` output and the
+    /// `(batch, num_heads, tgt, src)` attention weights
+    pub fn forward(
+        &mut self,
+        query: &VariableRef<T>,
+        key: &VariableRef<T>,
+        value: &VariableRef<T>,
+        attn_mask: Option<&Array<bool, IxDyn>>,
+        key_padding_mask: Option<&Array<bool, IxDyn>>,
+    ) -> (VariableRef<T>, VariableRef<T>) {
+        let (tgt, batch) = {
+            let shape = query.borrow().data.shape().to_vec();
+            (shape[0], shape[2])
+        };
+        let src = key.borrow().data.shape()[0];
+        let head_dim = self.embed_dim / self.num_heads;
+
+        let mut q = self.q_proj.f_sequence(query);
+        let mut k = self.k_proj.f_sequence(key);
+        let mut v = self.v_proj.f_sequence(value);
+        let (q, k, v) = (
+            self.split_heads(&mut q),
+            self.split_heads(&mut k),
+            self.split_heads(&mut v),
+        );
+
+        let scale = T::one() / T::from(head_dim).unwrap().sqrt();
+        let scale = Variable::new_no_retain_grad(Array::from_elem(vec![1], scale));
+        let mut scores = einsum("thdb,shdb->bhts", &[q, k]) * scale;
+
+        let mut fully_masked = None;
+        if attn_mask.is_some() || key_padding_mask.is_some() {
+            let masked = |b: usize, t: usize, s: usize| {
+                attn_mask.is_some_and(|m| m[[t, s]]) || key_padding_mask.is_some_and(|m| m[[b, s]])
+            };
+            let shape = (batch, self.num_heads, tgt, src);
+            // the softmax of a row of -inf is NaN, these rows are left unmasked and zeroed after
+            let rows =
+                Array::from_shape_fn(shape, |(b, _, t, _)| (0..src).all(|s| masked(b, t, s)))
+                    .into_dyn();
+            let mask = Array::from_shape_fn(shape, |(b, _, t, s)| masked(b, t, s)).into_dyn();
+            let mask = &mask & &rows.mapv(|r| !r);
+            scores = scores.masked_fill(&mask, T::neg_infinity());
+            fully_masked = Some(rows);
+        }
+
+        let mut weights = scores.softmax_axis(3);
+        if let Some(rows) = fully_masked.filter(|rows| rows.iter().any(|&r| r)) {
+            weights = weights.masked_fill(&rows, T::zero());
+        }
+        let attended = self.dropout.f(&weights);
+
+        let mut output = einsum("bhts,shdb->thdb", &[attended, v]);
+        let output = output.reshape(&[tgt, self.embed_dim, batch]);
+        (self.out_proj.f_sequence(&output), weights)
+    }
+}
+
+impl<T: NdFloat> Module<T> for MultiheadAttention<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        [&self.q_proj, &self.k_proj, &self.v_proj, &self.out_proj]
+            .iter()
+            .flat_map(|proj| proj.params())
+            .collect()
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        let mut named = prefixed("q_proj", self.q_proj.named_parameters());
+        named.extend(prefixed("k_proj", self.k_proj.named_parameters()));
+        named.extend(prefixed("v_proj", self.v_proj.named_parameters()));
+        named.extend(prefixed("out_proj", self.out_proj.named_parameters()));
+        named
+    }
+
+    fn train(&mut self, mode: bool) {
+        Module::<T>::train(&mut self.dropout, mode);
+    }
+
+    /// self-attention over a `(seq, embed_dim, batch)` input without masks
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        self.forward(input, input, input, None, None).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Axis;
+
+    fn sequence(seq: usize, dim: usize, batch: usize) -> VariableRef<f32> {
+        let x = Array::from_shape_fn((seq, dim, batch), |(s, d, b)| {
+            ((s * 7 + d * 3 + b) as f32 * 0.3).sin()
+        });
+        Variable::new(x.into_dyn())
+    }
+
+    #[test]
+    fn attention_forward() {
+        let mut attn = MultiheadAttention::new(8, 2);
+        let query = &sequence(3, 8, 2);
+        let memory = &sequence(5, 8, 2);
+
+        let (mut output, weights) = attn.forward(query, memory, memory, None, None);
+        assert_eq!(output.borrow().data.shape(), &[3, 8, 2]);
+        assert_eq!(weights.borrow().data.shape(), &[2, 2, 3, 5]);
+        for row in weights.borrow().data.lanes(Axis(3)) {
+            assert!((row.sum() - 1.).abs() < 1e-5);
+        }
+
+        let mut loss = output.sum();
+        loss.backward();
+        for param in attn.params() {
+            assert!(param.borrow().get_grad_f().iter().any(|&g| g != 0.));
+        }
+    }
+
+    #[test]
+    fn attention_masks() {
+        let mut attn = MultiheadAttention::new(4, 1);
+        let x = &sequence(4, 4, 2);
+
+        let mut padding = Array::from_elem((2, 4), false).into_dyn();
+        padding[[1, 3]] = true;
+
+        let (_, weights) = attn.forward(x, x, x, Some(&causal_mask(4)), Some(&padding));
+        let weights = &weights.borrow().data;
+        for t in 0..4 {
+            for s in 0..4 {
+                if s > t {
+                    assert_eq!(weights[[0, 0, t, s]], 0.);
+                }
+                if s == 3 {
+                    assert_eq!(weights[[1, 0, t, s]], 0.);
+                }
+            }
+        }
+        // the first position only sees itself
+        assert_eq!(weights[[0, 0, 0, 0]], 1.);
+    }
+
+    #[test]
+    fn attention_causal_output() {
+        // with a causal mask the first outputs do not depend on later inputs
+        let mut attn = MultiheadAttention::new(4, 2);
+        let x = &sequence(3, 4, 1);
+        let mut y = sequence(3, 4, 1);
+        y.borrow_mut().data[[2, 0, 0]] += 1.;
+
+        let mask = causal_mask(3);
+        let (a, _) = attn.forward(x, x, x, Some(&mask), None);
+        let (b, _) = attn.forward(&y, &y, &y, Some(&mask), None);
+        let (a, b) = (&a.borrow().data, &b.borrow().data);
+        assert_eq!(a.index_axis(Axis(0), 1), b.index_axis(Axis(0), 1));
+        assert_ne!(a.index_axis(Axis(0), 2), b.index_axis(Axis(0), 2));
+    }
+
+    #[test]
+    fn attention_fully_masked_query() {
+        let mut attn = MultiheadAttention::new(4, 2);
+        let x = &sequence(3, 4, 2);
+
+        // the whole second sequence of the batch is padding
+        let mut padding = Array::from_elem((2, 3), false).into_dyn();
+        padding.index_axis_mut(Axis(0), 1).fill(true);
+
+        let (mut output, weights) = attn.forward(x, x, x, None, Some(&padding));
+        let weights = weights.borrow().data.clone();
+        assert!(weights.index_axis(Axis(0), 1).iter().all(|&w| w == 0.));
+        for row in weights.index_axis(Axis(0), 0).lanes(Axis(2)) {
+            assert!((row.sum() - 1.).abs() < 1e-5);
+        }
+
+        output.sum().backward();
+        for param in attn.params() {
+            assert!(param.borrow().get_grad_f().iter().all(|g| g.is_finite()));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "attention with no heads")]
+    fn attention_no_heads() {
+        MultiheadAttention::new(4, 0);
+    }
+}
@@ -0,0 +1,505 @@
+use crate::grad_fn::conv::{check_groups, conv1d, conv2d, ConvParams, ConvParams1d};
+use crate::grad_fn::conv_transpose::{
+    conv_transpose1d, conv_transpose2d, conv_transpose_output_size,
+};
+use crate::module::{Module, Named};
+use crate::nn::init;
+use crate::variable::{Variable, VariableRef};
+use ndarray::{Array, IxDyn, NdFloat};
+
+/// uniform initialization in `[-1/sqrt(fan_in), 1/sqrt(fan_in)]`, as in `Linear`
+fn init_uniform(shape: &[usize], fan_in: usize) -> VariableRef<f32> {
+    init::uniform(shape, 1. / (fan_in as f32).sqrt())
+}
+
+fn empty() -> VariableRef<f32> {
+    Variable::new(Array::zeros(IxDyn(&[0])))
+}
+
+/// 2-D convolution over a `(N, C_in, H, W)` input, the bias is broadcast over the batch and
+/// spatial axes
+pub struct Conv2d<T: NdFloat> {
+    pub in_channels: usize,
+    pub out_channels: usize,
+    pub kernel_size: (usize, usize),
+    pub params: ConvParams,
+    pub groups: usize,
+    pub weight: VariableRef<T>,
+    pub bias: VariableRef<T>,
+}
+
+impl Conv2d<f32> {
+    pub fn new(
+        in_channels: usize,
+        out_channels: usize,
+        kernel_size: (usize, usize),
+    ) -> Conv2d<f32> {
+        let mut layer = Conv2d {
+            in_channels,
+            out_channels,
+            kernel_size,
+            params: ConvParams::default(),
+            groups: 1,
+            weight: empty(),
+            bias: empty(),
+        };
+        layer.init_params();
+        layer
+    }
+
+    pub fn with_stride(mut self, stride: (usize, usize)) -> Conv2d<f32> {
+        self.params.stride = stride;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: (usize, usize)) -> Conv2d<f32> {
+        self.params.padding = padding;
+        self
+    }
+
+    pub fn with_dilation(mut self, dilation: (usize, usize)) -> Conv2d<f32> {
+        self.params.dilation = dilation;
+        self
+    }
+
+    /// split the channels in `groups` independent convolutions, the parameters are initialized
+    /// again for the new fan in
+    pub fn with_groups(mut self, groups: usize) -> Conv2d<f32> {
+        check_groups(self.in_channels, self.out_channels, groups);
+        self.groups = groups;
+        self.init_params();
+        self
+    }
+
+    fn init_params(&mut self) {
+        let (kh, kw) = self.kernel_size;
+        let fan_in = self.in_channels / self.groups * kh * kw;
+
+        self.weight = init_uniform(
+            &[self.out_channels, self.in_channels / self.groups, kh, kw],
+            fan_in,
+        );
+        self.bias = init_uniform(&[1, self.out_channels, 1, 1], fan_in);
+    }
+
+    /// spatial size of the output for a `(H, W)` input
+    pub fn output_size(&self, input: (usize, usize)) -> (usize, usize) {
+        self.params.output_size(input, self.kernel_size)
+    }
+}
+
+impl<T: NdFloat> Module<T> for Conv2d<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![self.weight.clone(), self.bias.clone()]
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        vec![
+            ("weight".to_string(), self.weight.clone()),
+            ("bias".to_string(), self.bias.clone()),
+        ]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        conv2d(input, &self.weight, self.params, self.groups) + self.bias.clone()
+    }
+}
+
+/// 1-D convolution over a `(N, C_in, L)` input
+pub struct Conv1d<T: NdFloat> {
+    pub in_channels: usize,
+    pub out_channels: usize,
+    pub kernel_size: usize,
+    pub params: ConvParams1d,
+    pub groups: usize,
+    pub weight: VariableRef<T>,
+    pub bias: VariableRef<T>,
+}
+
+impl Conv1d<f32> {
+    pub fn new(in_channels: usize, out_channels: usize, kernel_size: usize) -> Conv1d<f32> {
+        let mut layer = Conv1d {
+            in_channels,
+            out_channels,
+            kernel_size,
+            params: ConvParams1d::default(),
+            groups: 1,
+            weight: empty(),
+            bias: empty(),
+        };
+        layer.init_params();
+        layer
+    }
+
+    pub fn with_stride(mut self, stride: usize) -> Conv1d<f32> {
+        self.params.stride = stride;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: usize) -> Conv1d<f32> {
+        self.params.padding = padding;
+        self
+    }
+
+    pub fn with_dilation(mut self, dilation: usize) -> Conv1d<f32> {
+        self.params.dilation = dilation;
+        self
+    }
+
+    pub fn with_groups(mut self, groups: usize) -> Conv1d<f32> {
+        check_groups(self.in_channels, self.out_channels, groups);
+        self.groups = groups;
+        self.init_params();
+        self
+    }
+
+    fn init_params(&mut self) {
+        let fan_in = self.in_channels / self.groups * self.kernel_size;
+
+        self.weight = init_uniform(
+            &[
+                self.out_channels,
+                self.in_channels / self.groups,
+                self.kernel_size,
+            ],
+            fan_in,
+        );
+        self.bias = init_uniform(&[1, self.out_channels, 1], fan_in);
+    }
+
+    pub fn output_size(&self, input: usize) -> usize {
+        self.params.output_size(input, self.kernel_size)
+    }
+}
+
+impl<T: NdFloat> Module<T> for Conv1d<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![self.weight.clone(), self.bias.clone()]
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        vec![
+            ("weight".to_string(), self.weight.clone()),
+            ("bias".to_string(), self.bias.clone()),
+        ]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        conv1d(input, &self.weight, self.params, self.groups) + self.bias.clone()
+    }
+}
+
+/// transposed 2-D convolution over a `(N, C_in, H, W)` input, the weight has the shape
+/// `(C_in, C_out / groups, kh, kw)`
+pub struct ConvTranspose2d<T: NdFloat> {
+    pub in_channels: usize,
+    pub out_channels: usize,
+    pub kernel_size: (usize, usize),
+    pub params: ConvParams,
+    pub output_padding: (usize, usize),
+    pub groups: usize,
+    pub weight: VariableRef<T>,
+    pub bias: VariableRef<T>,
+}
+
+impl ConvTranspose2d<f32> {
+    pub fn new(
+        in_channels: usize,
+        out_channels: usize,
+        kernel_size: (usize, usize),
+    ) -> ConvTranspose2d<f32> {
+        let mut layer = ConvTranspose2d {
+            in_channels,
+            out_channels,
+            kernel_size,
+            params: ConvParams::default(),
+            output_padding: (0, 0),
+            groups: 1,
+            weight: empty(),
+            bias: empty(),
+        };
+        layer.init_params();
+        layer
+    }
+
+    pub fn with_stride(mut self, stride: (usize, usize)) -> ConvTranspose2d<f32> {
+        self.params.stride = stride;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: (usize, usize)) -> ConvTranspose2d<f32> {
+        self.params.padding = padding;
+        self
+    }
+
+    /// extra rows and columns added at the end of the output, to pick among the input sizes
+    /// that a strided convolution maps to the same output size
+    pub fn with_output_padding(mut self, output_padding: (usize, usize)) -> ConvTranspose2d<f32> {
+        self.output_padding = output_padding;
+        self
+    }
+
+    pub fn with_dilation(mut self, dilation: (usize, usize)) -> ConvTranspose2d<f32> {
+        self.params.dilation = dilation;
+        self
+    }
+
+    pub fn with_groups(mut self, groups: usize) -> ConvTranspose2d<f32> {
+        check_groups(self.in_channels, self.out_channels, groups);
+        self.groups = groups;
+        self.init_params();
+        self
+    }
+
+    fn init_params(&mut self) {
+        let (kh, kw) = self.kernel_size;
+        // fan in of the weight seen as a convolution from C_out to C_in, as in pytorch
+        let fan_in = self.out_channels / self.groups * kh * kw;
+
+        self.weight = init_uniform(
+            &[self.in_channels, self.out_channels / self.groups, kh, kw],
+            fan_in,
+        );
+        self.bias = init_uniform(&[1, self.out_channels, 1, 1], fan_in);
+    }
+
+    pub fn output_size(&self, input: (usize, usize)) -> (usize, usize) {
+        let p = &self.params;
+        (
+            conv_transpose_output_size(
+                input.0,
+                self.kernel_size.0,
+                p.stride.0,
+                p.padding.0,
+                p.dilation.0,
+                self.output_padding.0,
+            ),
+            conv_transpose_output_size(
+                input.1,
+                self.kernel_size.1,
+                p.stride.1,
+                p.padding.1,
+                p.dilation.1,
+                self.output_padding.1,
+            ),
+        )
+    }
+}
+
+impl<T: NdFloat> Module<T> for ConvTranspose2d<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![self.weight.clone(), self.bias.clone()]
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        vec![
+            ("weight".to_string(), self.weight.clone()),
+            ("bias".to_string(), self.bias.clone()),
+        ]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        conv_transpose2d(
+            input,
+            &self.weight,
+            self.params,
+            self.output_padding,
+            self.groups,
+        ) + self.bias.clone()
+    }
+}
+
+/// transposed 1-D convolution over a `(N, C_in, L)` input, the weight has the shape
+/// `(C_in, C_out / groups, k)`
+pub struct ConvTranspose1d<T: NdFloat> {
+    pub in_channels: usize,
+    pub out_channels: usize,
+    pub kernel_size: usize,
+    pub params: ConvParams1d,
+    pub output_padding: usize,
+    pub groups: usize,
+    pub weight: VariableRef<T>,
+    pub bias: VariableRef<T>,
+}
+
+impl ConvTranspose1d<f32> {
+    pub fn new(
+        in_channels: usize,
+        out_channels: usize,
+        kernel_size: usize,
+    ) -> ConvTranspose1d<f32> {
+        let mut layer = ConvTranspose1d {
+            in_channels,
+            out_channels,
+            kernel_size,
+            params: ConvParams1d::default(),
+            output_padding: 0,
+            groups: 1,
+            weight: empty(),
+            bias: empty(),
+        };
+        layer.init_params();
+        layer
+    }
+
+    pub fn with_stride(mut self, stride: usize) -> ConvTranspose1d<f32> {
+        self.params.stride = stride;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: usize) -> ConvTranspose1d<f32> {
+        self.params.padding = padding;
+        self
+    }
+
+    pub fn with_output_padding(mut self, output_padding: usize) -> ConvTranspose1d<f32> {
+        self.output_padding = output_padding;
+        self
+    }
+
+    pub fn with_dilation(mut self, dilation: usize) -> ConvTranspose1d<f32> {
+        self.params.dilation = dilation;
+        self
+    }
+
+    pub fn with_groups(mut self, groups: usize) -> ConvTranspose1d<f32> {
+        check_groups(self.in_channels, self.out_channels, groups);
+        self.groups = groups;
+        self.init_params();
+        self
+    }
+
+    fn init_params(&mut self) {
+        let fan_in = self.out_channels / self.groups * self.kernel_size;
+
+        self.weight = init_uniform(
+            &[
+                self.in_channels,
+                self.out_channels / self.groups,
+                self.kernel_size,
+            ],
+            fan_in,
+        );
+        self.bias = init_uniform(&[1, self.out_channels, 1], fan_in);
+    }
+
+    pub fn output_size(&self, input: usize) -> usize {
+        conv_transpose_output_size(
+            input,
+            self.kernel_size,
+            self.params.stride,
+            self.params.padding,
+            self.params.dilation,
+            self.output_padding,
+        )
+    }
+}
+
+impl<T: NdFloat> Module<T> for ConvTranspose1d<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![self.weight.clone(), self.bias.clone()]
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        vec![
+            ("weight".to_string(), self.weight.clone()),
+            ("bias".to_string(), self.bias.clone()),
+        ]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        conv_transpose1d(
+            input,
+            &self.weight,
+            self.params,
+            self.output_padding,
+            self.groups,
+        ) + self.bias.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Ix3, Ix4};
+
+    #[test]
+    fn conv2d_forward() {
+        let x = &Variable::new(Array::<f32, Ix4>::ones((2, 4, 8, 7)).into_dyn());
+
+        let mut layer = Conv2d::new(4, 6, (3, 3))
+            .with_stride((2, 2))
+            .with_padding((1, 1))
+            .with_groups(2);
+        assert_eq!(layer.weight.borrow().data.shape(), &[6, 2, 3, 3]);
+        assert_eq!(layer.output_size((8, 7)), (4, 4));
+
+        let mut y = layer.f(x);
+        assert_eq!(y.borrow().data.shape(), &[2, 6, 4, 4]);
+
+        y = y.sum();
+        y.backward();
+        assert_eq!(
+            layer.bias.borrow().get_grad_f(),
+            Array::<f32, _>::from_elem((1, 6, 1, 1), 32.).into_dyn()
+        );
+    }
+
+    #[test]
+    fn conv1d_forward() {
+        let x = &Variable::new(Array::<f32, Ix3>::ones((2, 3, 10)).into_dyn());
+
+        let mut layer = Conv1d::new(3, 4, 3).with_stride(2).with_dilation(2);
+        assert_eq!(layer.output_size(10), 3);
+
+        let mut y = layer.f(x);
+        assert_eq!(y.borrow().data.shape(), &[2, 4, 3]);
+
+        y = y.sum();
+        y.backward();
+        assert_eq!(layer.weight.borrow().get_grad_f().shape(), &[4, 3, 3]);
+    }
+
+    #[test]
+    fn conv_transpose2d_upsamples() {
+        let x = &Variable::new(Array::<f32, Ix4>::ones((1, 4, 5, 5)).into_dyn());
+
+        // undo the shapes of a strided conv2d
+        let down = Conv2d::new(2, 4, (3, 3))
+            .with_stride((2, 2))
+            .with_padding((1, 1));
+        let mut up = ConvTranspose2d::new(4, 2, (3, 3))
+            .with_stride((2, 2))
+            .with_padding((1, 1))
+            .with_output_padding((1, 0));
+        assert_eq!(down.output_size((10, 9)), (5, 5));
+        assert_eq!(up.output_size((5, 5)), (10, 9));
+
+        let mut y = up.f(x);
+        assert_eq!(y.borrow().data.shape(), &[1, 2, 10, 9]);
+
+        y = y.sum();
+        y.backward();
+        assert_eq!(up.weight.borrow().get_grad_f().shape(), &[4, 2, 3, 3]);
+    }
+
+    #[test]
+    fn conv_transpose1d_forward() {
+        let x = &Variable::new(Array::<f32, Ix3>::ones((2, 4, 6)).into_dyn());
+
+        let mut layer = ConvTranspose1d::new(4, 2, 4)
+            .with_stride(2)
+            .with_padding(1)
+            .with_groups(2);
+        assert_eq!(layer.output_size(6), 12);
+
+        let y = layer.f(x);
+        assert_eq!(y.borrow().data.shape(), &[2, 2, 12]);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not be split into 0 groups")]
+    fn zero_groups() {
+        Conv1d::new(4, 2, 3).with_groups(0);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::grad_fn::dropout::{dropout, dropout_mask};
+use crate::module::Module;
+use crate::variable::VariableRef;
+use ndarray::NdFloat;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// zero each element of the input with probability `p` in training and scale the others by
+/// `1 / (1 - p)`, the identity in evaluation
+pub struct Dropout {
+    pub p: f64,
+    pub training: bool,
+    pub rng: StdRng,
+}
+
+impl Dropout {
+    pub fn new(p: f64) -> Dropout {
+        assert!(
+            (0. ..=1.).contains(&p),
+            "dropout probability must be in [0, 1]"
+        );
+        Dropout {
+            p,
+            training: true,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// draw the masks from a generator seeded with `seed`, for reproducible runs
+    pub fn with_seed(mut self, seed: u64) -> Dropout {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// change the probability, keeping the generator
+    pub fn set_p(&mut self, p: f64) {
+        assert!(
+            (0. ..=1.).contains(&p),
+            "dropout probability must be in [0, 1]"
+        );
+        self.p = p;
+    }
+}
+
+impl<T: NdFloat> Module<T> for Dropout {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn train(&mut self, mode: bool) {
+        self.training = mode;
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        if !self.training || self.p == 0. {
+            return input.clone();
+        }
+        let mask = dropout_mask(input.borrow().data.shape(), self.p, &mut self.rng);
+        dropout(input, &mask)
+    }
+}
+
+/// zero whole channels of a `(N, C, ...)` input with probability `p` in training, as
+/// `Dropout` does for single elements
+pub struct Dropout2d {
+    pub p: f64,
+    pub training: bool,
+    pub rng: StdRng,
+}
+
+impl Dropout2d {
+    pub fn new(p: f64) -> Dropout2d {
+        assert!(
+            (0. ..=1.).contains(&p),
+            "dropout probability must be in [0, 1]"
+        );
+        Dropout2d {
+            p,
+            training: true,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Dropout2d {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+}
+
+impl<T: NdFloat> Module<T> for Dropout2d {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn train(&mut self, mode: bool) {
+        self.training = mode;
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        if !self.training || self.p == 0. {
+            return input.clone();
+        }
+        let mut shape = input.borrow().data.shape().to_vec();
+        assert!(shape.len() > 2, "Dropout2d expects a (N, C, ...) input");
+        for len in shape[2..].iter_mut() {
+            *len = 1;
+        }
+        let mask = dropout_mask(&shape, self.p, &mut self.rng);
+        dropout(input, &mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variable::Variable;
+    use ndarray::{Array, Axis};
+
+    #[test]
+    fn dropout_train_and_eval() {
+        let x = &Variable::new(Array::<f32, _>::ones((20, 30)).into_dyn());
+
+        let mut layer = Dropout::new(0.5).with_seed(42);
+        let mut y = layer.f(x);
+        assert!(y.borrow().data.iter().all(|&a| a == 0. || a == 2.));
+
+        // the gradient goes through the kept elements only
+        let output = y.borrow().data.clone();
+        y.sum().backward();
+        assert_eq!(x.borrow().get_grad_f(), output);
+
+        // same seed, same mask
+        let other = Dropout::new(0.5).with_seed(42).f(x);
+        assert_eq!(other.borrow().data, output);
+
+        Module::<f32>::eval(&mut layer);
+        assert_eq!(layer.f(x).borrow().data, x.borrow().data);
+    }
+
+    #[test]
+    fn dropout2d_zeroes_channels() {
+        let x = &Variable::new(Array::<f32, _>::ones((4, 8, 3, 3)).into_dyn());
+
+        let mut layer = Dropout2d::new(0.5).with_seed(7);
+        let y = layer.f(x);
+
+        for sample in y.borrow().data.axis_iter(Axis(0)) {
+            for channel in sample.axis_iter(Axis(0)) {
+                let first = channel.iter().next().copied().unwrap();
+                assert!(first == 0. || first == 2.);
+                assert!(channel.iter().all(|&a| a == first));
+            }
+        }
+    }
+}
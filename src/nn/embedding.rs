@@ -0,0 +1,160 @@
+use crate::grad_fn::embedding::{embedding, renorm_rows};
+use crate::module::{Module, Named};
+use crate::nn::init;
+use crate::variable::VariableRef;
+use ndarray::{Array, Axis, IxDyn, NdFloat};
+
+/// lookup table of `num_embeddings` vectors of size `embedding_dim`. The embedding axis is
+/// inserted before the last axis of the indices, so that a `(batch,)` input gives a
+/// `(embedding_dim, batch)` output for `Linear` and a `(seq, batch)` input a
+/// `(seq, embedding_dim, batch)` output for the recurrent layers
+pub struct Embedding<T: NdFloat> {
+    pub num_embeddings: usize,
+    pub embedding_dim: usize,
+    /// row kept at zero and never updated, for the padding of sequences
+    pub padding_idx: Option<usize>,
+    /// rows looked up with a larger euclidean norm are rescaled in place to this norm
+    pub max_norm: Option<T>,
+    /// `(num_embeddings, embedding_dim)`
+    pub weight: VariableRef<T>,
+}
+
+impl Embedding<f32> {
+    pub fn new(num_embeddings: usize, embedding_dim: usize) -> Embedding<f32> {
+        Embedding {
+            num_embeddings,
+            embedding_dim,
+            padding_idx: None,
+            max_norm: None,
+            weight: init::normal(&[num_embeddings, embedding_dim], 1.),
+        }
+    }
+
+    pub fn with_padding_idx(mut self, padding_idx: usize) -> Embedding<f32> {
+        assert!(
+            padding_idx < self.num_embeddings,
+            "padding_idx out of range"
+        );
+        self.padding_idx = Some(padding_idx);
+        self.weight
+            .borrow_mut()
+            .data
+            .index_axis_mut(Axis(0), padding_idx)
+            .fill(0.);
+        self
+    }
+
+    pub fn with_max_norm(mut self, max_norm: f32) -> Embedding<f32> {
+        self.max_norm = Some(max_norm);
+        self
+    }
+}
+
+impl<T: NdFloat> Embedding<T> {
+    pub fn forward(&mut self, indices: &Array<usize, IxDyn>) -> VariableRef<T> {
+        if let Some(max_norm) = self.max_norm {
+            renorm_rows(&mut self.weight.borrow_mut().data, indices, max_norm);
+        }
+        embedding(&self.weight, indices, self.padding_idx)
+    }
+}
+
+impl<T: NdFloat> Module<T> for Embedding<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![self.weight.clone()]
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        vec![("weight".to_string(), self.weight.clone())]
+    }
+
+    /// the input holds the indices as floats, no gradient flows back to it
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        let indices = input.borrow().data.mapv(|a| match a.to_usize() {
+            Some(i) if a.fract() == T::zero() => i,
+            _ => panic!("index {} is not a non-negative integer", a),
+        });
+        self.forward(&indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::linear::Linear;
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn embedding_forward() {
+        let mut emb = Embedding::new(10, 4).with_padding_idx(0);
+        let mut head = Linear::new(4, 2);
+
+        let x = emb.forward(&array!(3, 0, 3, 7).into_dyn());
+        assert_eq!(x.borrow().data.shape(), &[4, 4]);
+        assert!(x
+            .borrow()
+            .data
+            .index_axis(Axis(1), 1)
+            .iter()
+            .all(|&a| a == 0.));
+
+        let mut y = head.f(&x).sum();
+        y.backward();
+
+        let grad = emb.weight.borrow().get_grad_f();
+        for row in 0..10 {
+            let touched = grad.index_axis(Axis(0), row).iter().any(|&g| g != 0.);
+            assert_eq!(touched, row == 3 || row == 7);
+        }
+    }
+
+    #[test]
+    fn embedding_module_input() {
+        let mut emb = Embedding::new(5, 3);
+        let indices = &Variable::new(array!([1.0, 4.0], [0.0, 2.0]).into_dyn());
+
+        let y = emb.f(indices);
+        assert_eq!(y.borrow().data.shape(), &[2, 3, 2]);
+        assert_eq!(
+            y.borrow()
+                .data
+                .index_axis(Axis(0), 0)
+                .index_axis(Axis(1), 1),
+            emb.weight.borrow().data.index_axis(Axis(0), 4)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "index -1 is not a non-negative integer")]
+    fn embedding_negative_input() {
+        Embedding::new(5, 3).f(&Variable::new(array!(1.0, -1.0).into_dyn()));
+    }
+
+    #[test]
+    #[should_panic(expected = "index NaN is not a non-negative integer")]
+    fn embedding_nan_input() {
+        Embedding::new(5, 3).f(&Variable::new(array!(f32::NAN).into_dyn()));
+    }
+
+    #[test]
+    fn embedding_max_norm() {
+        let mut emb = Embedding::new(6, 8).with_max_norm(0.5);
+        emb.weight.borrow_mut().data.fill(1.);
+
+        emb.forward(&array!(1, 4).into_dyn());
+
+        let weight = &emb.weight.borrow().data;
+        for row in 0..6 {
+            let norm = weight
+                .index_axis(Axis(0), row)
+                .fold(0., |acc: f32, &a| acc + a * a)
+                .sqrt();
+            if row == 1 || row == 4 {
+                assert!((norm - 0.5).abs() < 1e-5);
+            } else {
+                assert_eq!(norm, 8f32.sqrt());
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::variable::{Variable, VariableRef};
+use ndarray::{Array, IxDyn};
+
+use rand::distributions::{Distribution, Uniform};
+
+/// parameter of the given shape drawn uniformly in `[-bound, bound)`
+pub fn uniform(shape: &[usize], bound: f32) -> VariableRef<f32> {
+    let between = Uniform::from(-bound..bound);
+    let mut rng = rand::thread_rng();
+
+    let array_init = Array::<f32, IxDyn>::ones(shape).map(|_| between.sample(&mut rng));
+    Variable::new(array_init)
+}
+
+/// parameter of the given shape drawn from a centered normal distribution, with the
+/// Box-Muller transform
+pub fn normal(shape: &[usize], std: f32) -> VariableRef<f32> {
+    let between = Uniform::from(0f32..1.);
+    let mut rng = rand::thread_rng();
+
+    let array_init = Array::<f32, IxDyn>::ones(shape).map(|_| {
+        let u1 = 1. - between.sample(&mut rng);
+        let u2 = between.sample(&mut rng);
+        std * (-2. * u1.ln()).sqrt() * (2. * std::f32::consts::PI * u2).cos()
+    });
+    Variable::new(array_init)
+}
@@ -1,4 +1,5 @@
-use crate::module::Module;
+use crate::grad_fn::einsum::einsum;
+use crate::module::{prefixed, Module, Named};
 use crate::variable::{Variable, VariableRef};
 use ndarray::{Array, Ix2, NdFloat};
 
@@ -42,13 +43,28 @@ impl Linear<f32> {
     }
 }
 
+impl<T: NdFloat> Linear<T> {
+    /// apply the layer to every step of a `(seq, in_features, batch)` input
+    pub fn f_sequence(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        let bias = self.bias.clone().reshape(&[1, self.out_features, 1]);
+        einsum("oi,sib->sob", &[self.weight.clone(), input.clone()]) + bias
+    }
+}
+
 impl<T: NdFloat> Module<T> for Linear<T> {
     fn params(&self) -> Vec<VariableRef<T>> {
         vec![self.weight.clone(), self.bias.clone()]
     }
 
+    fn named_parameters(&self) -> Named<T> {
+        vec![
+            ("weight".to_string(), self.weight.clone()),
+            ("bias".to_string(), self.bias.clone()),
+        ]
+    }
+
     fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
-        self.weight.dot(&input) + self.bias.clone()
+        self.weight.dot(input) + self.bias.clone()
     }
 }
 
@@ -67,6 +83,32 @@ impl<T: NdFloat> Module<T> for MLP<T> {
         params
     }
 
+    fn buffers(&self) -> Vec<VariableRef<T>> {
+        self.layers.iter().flat_map(|lay| lay.buffers()).collect()
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(i, lay)| prefixed(&format!("layers.{}", i), lay.named_parameters()))
+            .collect()
+    }
+
+    fn named_buffers(&self) -> Named<T> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(i, lay)| prefixed(&format!("layers.{}", i), lay.named_buffers()))
+            .collect()
+    }
+
+    fn train(&mut self, mode: bool) {
+        for lay in self.layers.iter_mut() {
+            lay.train(mode);
+        }
+    }
+
     fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
         let mut output = input.clone();
         for i in 0..(self.layers.len() - 1) {
@@ -1 +1,14 @@
+pub mod activation;
+pub mod attention;
+pub mod conv;
+pub mod dropout;
+pub mod embedding;
+pub mod init;
 pub mod linear;
+pub mod norm;
+pub mod pool;
+pub mod rnn;
+pub mod sequential;
+pub mod transformer;
+
+pub use sequential::Sequential;
@@ -0,0 +1,338 @@
+use crate::grad_fn::norm::{batch_norm, layer_norm, rms_norm};
+use crate::module::{Module, Named};
+use crate::variable::{Variable, VariableRef};
+use ndarray::{Array, Axis, IxDyn, NdFloat};
+
+/// shape of the affine parameters, the normalized shape followed by a batch axis of length 1
+fn affine_shape(normalized_shape: &[usize]) -> Vec<usize> {
+    let mut shape = normalized_shape.to_vec();
+    shape.push(1);
+    shape
+}
+
+/// normalize the input over the `normalized_shape` axes just before the batch axis, then
+/// apply a learnable element-wise affine transform. A `(features, batch)` input for `Linear`
+/// is normalized with `LayerNorm::new(&[features])`
+pub struct LayerNorm<T: NdFloat> {
+    pub normalized_shape: Vec<usize>,
+    pub eps: T,
+    pub gamma: VariableRef<T>,
+    pub beta: VariableRef<T>,
+}
+
+impl LayerNorm<f32> {
+    pub fn new(normalized_shape: &[usize]) -> LayerNorm<f32> {
+        let shape = affine_shape(normalized_shape);
+        LayerNorm {
+            normalized_shape: normalized_shape.to_vec(),
+            eps: 1e-5,
+            gamma: Variable::new(Array::<f32, IxDyn>::ones(shape.clone())),
+            beta: Variable::new(Array::<f32, IxDyn>::zeros(shape)),
+        }
+    }
+
+    pub fn with_eps(mut self, eps: f32) -> LayerNorm<f32> {
+        self.eps = eps;
+        self
+    }
+}
+
+impl<T: NdFloat> Module<T> for LayerNorm<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![self.gamma.clone(), self.beta.clone()]
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        vec![
+            ("gamma".to_string(), self.gamma.clone()),
+            ("beta".to_string(), self.beta.clone()),
+        ]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        layer_norm(input, &self.gamma, self.normalized_shape.len(), self.eps) + self.beta.clone()
+    }
+}
+
+/// scale the input by the inverse of its root mean square over the `normalized_shape` axes,
+/// without centering it, then by a learnable gain
+pub struct RMSNorm<T: NdFloat> {
+    pub normalized_shape: Vec<usize>,
+    pub eps: T,
+    pub gamma: VariableRef<T>,
+}
+
+impl RMSNorm<f32> {
+    pub fn new(normalized_shape: &[usize]) -> RMSNorm<f32> {
+        RMSNorm {
+            normalized_shape: normalized_shape.to_vec(),
+            eps: f32::EPSILON,
+            gamma: Variable::new(Array::<f32, IxDyn>::ones(affine_shape(normalized_shape))),
+        }
+    }
+
+    pub fn with_eps(mut self, eps: f32) -> RMSNorm<f32> {
+        self.eps = eps;
+        self
+    }
+}
+
+impl<T: NdFloat> Module<T> for RMSNorm<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![self.gamma.clone()]
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        vec![("gamma".to_string(), self.gamma.clone())]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        rms_norm(input, &self.gamma, self.normalized_shape.len(), self.eps)
+    }
+}
+
+/// channels are on the first axis of a `(features, batch)` input for `Linear` and on the
+/// second one of the batch first inputs of the convolutions
+fn channel_axis(ndim: usize) -> usize {
+    if ndim == 2 {
+        0
+    } else {
+        1
+    }
+}
+
+/// shape with length 1 on every axis but the channel one, to broadcast per channel values
+fn channel_shape(ndim: usize, num_features: usize) -> Vec<usize> {
+    let mut shape = vec![1; ndim];
+    shape[channel_axis(ndim)] = num_features;
+    shape
+}
+
+macro_rules! impl_batch_norm {
+    ($name:ident, $ndims:expr) => {
+        impl $name<f32> {
+            pub fn new(num_features: usize) -> $name<f32> {
+                $name {
+                    num_features,
+                    eps: 1e-5,
+                    momentum: 0.1,
+                    training: true,
+                    gamma: Variable::new(Array::<f32, IxDyn>::ones(vec![num_features, 1])),
+                    beta: Variable::new(Array::<f32, IxDyn>::zeros(vec![num_features, 1])),
+                    running_mean: Variable::new_no_retain_grad(Array::zeros(vec![num_features])),
+                    running_var: Variable::new_no_retain_grad(Array::ones(vec![num_features])),
+                }
+            }
+
+            pub fn with_eps(mut self, eps: f32) -> $name<f32> {
+                self.eps = eps;
+                self
+            }
+
+            pub fn with_momentum(mut self, momentum: f32) -> $name<f32> {
+                self.momentum = momentum;
+                self
+            }
+        }
+
+        impl<T: NdFloat> $name<T> {
+            /// move the running statistics towards the ones of the batch, with the unbiased
+            /// variance
+            fn update_running_stats(&mut self, data: &Array<T, IxDyn>) {
+                let axis = Axis(channel_axis(data.ndim()));
+                let momentum = self.momentum;
+                let mut running_mean = self.running_mean.borrow_mut();
+                let mut running_var = self.running_var.borrow_mut();
+
+                for (c, lane) in data.axis_iter(axis).enumerate() {
+                    let n = lane.len();
+                    assert!(
+                        n > 1,
+                        "expected more than 1 value per channel when training"
+                    );
+                    let mean = lane.sum() / T::from(n).unwrap();
+                    let var = lane.fold(T::zero(), |acc, &a| acc + (a - mean) * (a - mean))
+                        / T::from(n - 1).unwrap();
+
+                    let old = (running_mean.data[c], running_var.data[c]);
+                    running_mean.data[c] = (T::one() - momentum) * old.0 + momentum * mean;
+                    running_var.data[c] = (T::one() - momentum) * old.1 + momentum * var;
+                }
+            }
+        }
+
+        impl<T: NdFloat> Module<T> for $name<T> {
+            fn params(&self) -> Vec<VariableRef<T>> {
+                vec![self.gamma.clone(), self.beta.clone()]
+            }
+
+            fn buffers(&self) -> Vec<VariableRef<T>> {
+                vec![self.running_mean.clone(), self.running_var.clone()]
+            }
+
+            fn named_parameters(&self) -> Named<T> {
+                vec![
+                    ("gamma".to_string(), self.gamma.clone()),
+                    ("beta".to_string(), self.beta.clone()),
+                ]
+            }
+
+            fn named_buffers(&self) -> Named<T> {
+                vec![
+                    ("running_mean".to_string(), self.running_mean.clone()),
+                    ("running_var".to_string(), self.running_var.clone()),
+                ]
+            }
+
+            fn train(&mut self, mode: bool) {
+                self.training = mode;
+            }
+
+            fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+                let ndim = input.borrow().data.ndim();
+                assert!(
+                    $ndims.contains(&ndim),
+                    "{} expects inputs with {:?} dimensions, got {}",
+                    stringify!($name),
+                    $ndims,
+                    ndim
+                );
+                let shape = channel_shape(ndim, self.num_features);
+                let gamma = self.gamma.clone().reshape(&shape);
+                let beta = self.beta.clone().reshape(&shape);
+
+                if self.training {
+                    self.update_running_stats(&input.borrow().data);
+                    return batch_norm(input, &gamma, channel_axis(ndim), self.eps) + beta;
+                }
+
+                let eps = self.eps;
+                let mean = self.running_mean.borrow().data.clone();
+                let rstd = self
+                    .running_var
+                    .borrow()
+                    .data
+                    .mapv(|v| T::one() / (v + eps).sqrt());
+                let mean = Variable::new_no_retain_grad(mean.into_shape(shape.clone()).unwrap());
+                let rstd = Variable::new_no_retain_grad(rstd.into_shape(shape).unwrap());
+
+                &(&(input - &mean) * &rstd) * &gamma + beta
+            }
+        }
+    };
+}
+
+/// normalize each feature with the statistics of the batch in training and with running
+/// estimates of them in evaluation. Takes a `(num_features, batch)` input for `Linear` or a
+/// `(batch, num_features, length)` one for `Conv1d`
+pub struct BatchNorm1d<T: NdFloat> {
+    pub num_features: usize,
+    pub eps: T,
+    /// weight of the current batch in the update of the running statistics
+    pub momentum: T,
+    pub training: bool,
+    pub gamma: VariableRef<T>,
+    pub beta: VariableRef<T>,
+    pub running_mean: VariableRef<T>,
+    pub running_var: VariableRef<T>,
+}
+
+/// `BatchNorm1d` over the channels of a `(batch, num_features, height, width)` input
+pub struct BatchNorm2d<T: NdFloat> {
+    pub num_features: usize,
+    pub eps: T,
+    pub momentum: T,
+    pub training: bool,
+    pub gamma: VariableRef<T>,
+    pub beta: VariableRef<T>,
+    pub running_mean: VariableRef<T>,
+    pub running_var: VariableRef<T>,
+}
+
+impl_batch_norm!(BatchNorm1d, [2, 3]);
+impl_batch_norm!(BatchNorm2d, [4]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::linear::Linear;
+    use ndarray::{array, Axis};
+
+    #[test]
+    fn layer_norm_forward() {
+        let mut layer = Linear::new(3, 8);
+        let mut norm = LayerNorm::new(&[8]);
+        assert_eq!(norm.gamma.borrow().data.shape(), &[8, 1]);
+
+        let x = &Variable::new(array!([1.0, -2.0], [0.5, 3.0], [2.0, 0.0]).into_dyn());
+        let y = norm.f(&layer.f(x));
+        assert_eq!(y.borrow().data.shape(), &[8, 2]);
+
+        for column in y.borrow().data.axis_iter(Axis(1)) {
+            let mean = column.sum() / 8.;
+            let var = column.fold(0., |acc, &a| acc + (a - mean) * (a - mean)) / 8.;
+            assert!(mean.abs() < 1e-5);
+            assert!((var - 1.).abs() < 1e-3);
+        }
+
+        let mut loss = (&y * &y.clone()).sum();
+        loss.backward();
+        for param in norm.params().iter().chain(layer.params().iter()) {
+            assert!(param.borrow().get_grad_f().iter().any(|&g| g != 0.));
+        }
+    }
+
+    #[test]
+    fn rms_norm_forward() {
+        let mut norm = RMSNorm::new(&[2, 3]).with_eps(0.);
+        let x = &Variable::new(Array::<f32, _>::from_elem((4, 2, 3, 5), 3.).into_dyn());
+
+        let y = norm.f(x);
+        assert_eq!(y.borrow().data.shape(), &[4, 2, 3, 5]);
+        assert!(y.borrow().data.iter().all(|&a| (a - 1.).abs() < 1e-6));
+        assert_eq!(norm.params().len(), 1);
+    }
+
+    #[test]
+    fn batch_norm_train_and_eval() {
+        let mut norm = BatchNorm1d::new(2).with_momentum(0.5);
+        let x = &Variable::new(array!([1.0, 3.0, 5.0], [2.0, 2.0, 2.0]).into_dyn());
+
+        let y = norm.f(x);
+        let s = 1.5_f32.sqrt();
+        let diff = &y.borrow().data - &array!([-s, 0.0, s], [0.0, 0.0, 0.0]).into_dyn();
+        assert!(diff.iter().all(|d| d.abs() < 1e-3));
+
+        // the running variance is updated with the unbiased variance of the batch
+        assert_eq!(norm.running_mean.borrow().data, array!(1.5, 1.0).into_dyn());
+        assert_eq!(norm.running_var.borrow().data, array!(2.5, 0.5).into_dyn());
+        assert_eq!(norm.params().len(), 2);
+        assert_eq!(norm.gamma.borrow().data.shape(), &[2, 1]);
+        assert_eq!(norm.buffers().len(), 2);
+
+        norm.eval();
+        let y = norm.f(&Variable::new(array!([1.5], [3.0]).into_dyn()));
+        let expected = array!([0.0], [2.0 / (0.5_f32 + 1e-5).sqrt()]).into_dyn();
+        assert!((&y.borrow().data - &expected)
+            .iter()
+            .all(|d| d.abs() < 1e-5));
+    }
+
+    #[test]
+    fn batch_norm_2d_forward() {
+        let mut norm = BatchNorm2d::new(3);
+        let x = Array::from_shape_fn((2, 3, 4, 4), |(n, c, h, w)| {
+            (n * 48 + c * 16 + h * 4 + w) as f32 * 0.1 + c as f32
+        });
+        let x = &Variable::new(x.into_dyn());
+
+        let mut y = norm.f(x);
+        for channel in y.borrow().data.axis_iter(Axis(1)) {
+            assert!(channel.mean().unwrap().abs() < 1e-5);
+        }
+
+        y.sum().backward();
+        assert_eq!(norm.gamma.borrow().get_grad_f().shape(), &[3, 1]);
+        assert!(norm.beta.borrow().get_grad_f().iter().all(|&g| g == 32.));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::grad_fn::conv::{ConvParams, ConvParams1d};
+use crate::module::Module;
+use crate::variable::VariableRef;
+use ndarray::NdFloat;
+
+/// max pooling over a `(N, C, H, W)` input, the stride defaults to the kernel size
+pub struct MaxPool2d {
+    pub kernel_size: (usize, usize),
+    pub params: ConvParams,
+}
+
+impl MaxPool2d {
+    pub fn new(kernel_size: (usize, usize)) -> MaxPool2d {
+        MaxPool2d {
+            kernel_size,
+            params: ConvParams {
+                stride: kernel_size,
+                ..ConvParams::default()
+            },
+        }
+    }
+
+    pub fn with_stride(mut self, stride: (usize, usize)) -> MaxPool2d {
+        self.params.stride = stride;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: (usize, usize)) -> MaxPool2d {
+        self.params.padding = padding;
+        self
+    }
+
+    pub fn with_dilation(mut self, dilation: (usize, usize)) -> MaxPool2d {
+        self.params.dilation = dilation;
+        self
+    }
+}
+
+impl<T: NdFloat> Module<T> for MaxPool2d {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        input.clone().max_pool2d(self.kernel_size, self.params)
+    }
+}
+
+/// average pooling over a `(N, C, H, W)` input, the stride defaults to the kernel size
+pub struct AvgPool2d {
+    pub kernel_size: (usize, usize),
+    pub params: ConvParams,
+}
+
+impl AvgPool2d {
+    pub fn new(kernel_size: (usize, usize)) -> AvgPool2d {
+        AvgPool2d {
+            kernel_size,
+            params: ConvParams {
+                stride: kernel_size,
+                ..ConvParams::default()
+            },
+        }
+    }
+
+    pub fn with_stride(mut self, stride: (usize, usize)) -> AvgPool2d {
+        self.params.stride = stride;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: (usize, usize)) -> AvgPool2d {
+        self.params.padding = padding;
+        self
+    }
+}
+
+impl<T: NdFloat> Module<T> for AvgPool2d {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        input.clone().avg_pool2d(self.kernel_size, self.params)
+    }
+}
+
+/// average pooling of a `(N, C, H, W)` input to a fixed `(N, C, output_size.0, output_size.1)`
+/// shape
+pub struct AdaptiveAvgPool2d {
+    pub output_size: (usize, usize),
+}
+
+impl AdaptiveAvgPool2d {
+    pub fn new(output_size: (usize, usize)) -> AdaptiveAvgPool2d {
+        AdaptiveAvgPool2d { output_size }
+    }
+}
+
+impl<T: NdFloat> Module<T> for AdaptiveAvgPool2d {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        input.clone().adaptive_avg_pool2d(self.output_size)
+    }
+}
+
+/// max pooling over a `(N, C, L)` input, the stride defaults to the kernel size
+pub struct MaxPool1d {
+    pub kernel_size: usize,
+    pub params: ConvParams1d,
+}
+
+impl MaxPool1d {
+    pub fn new(kernel_size: usize) -> MaxPool1d {
+        MaxPool1d {
+            kernel_size,
+            params: ConvParams1d {
+                stride: kernel_size,
+                ..ConvParams1d::default()
+            },
+        }
+    }
+
+    pub fn with_stride(mut self, stride: usize) -> MaxPool1d {
+        self.params.stride = stride;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: usize) -> MaxPool1d {
+        self.params.padding = padding;
+        self
+    }
+
+    pub fn with_dilation(mut self, dilation: usize) -> MaxPool1d {
+        self.params.dilation = dilation;
+        self
+    }
+}
+
+impl<T: NdFloat> Module<T> for MaxPool1d {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        input.clone().max_pool1d(self.kernel_size, self.params)
+    }
+}
+
+/// average pooling over a `(N, C, L)` input, the stride defaults to the kernel size
+pub struct AvgPool1d {
+    pub kernel_size: usize,
+    pub params: ConvParams1d,
+}
+
+impl AvgPool1d {
+    pub fn new(kernel_size: usize) -> AvgPool1d {
+        AvgPool1d {
+            kernel_size,
+            params: ConvParams1d {
+                stride: kernel_size,
+                ..ConvParams1d::default()
+            },
+        }
+    }
+
+    pub fn with_stride(mut self, stride: usize) -> AvgPool1d {
+        self.params.stride = stride;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: usize) -> AvgPool1d {
+        self.params.padding = padding;
+        self
+    }
+}
+
+impl<T: NdFloat> Module<T> for AvgPool1d {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        input.clone().avg_pool1d(self.kernel_size, self.params)
+    }
+}
+
+/// average pooling of a `(N, C, L)` input to a fixed `(N, C, output_size)` shape
+pub struct AdaptiveAvgPool1d {
+    pub output_size: usize,
+}
+
+impl AdaptiveAvgPool1d {
+    pub fn new(output_size: usize) -> AdaptiveAvgPool1d {
+        AdaptiveAvgPool1d { output_size }
+    }
+}
+
+impl<T: NdFloat> Module<T> for AdaptiveAvgPool1d {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        vec![]
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        input.clone().adaptive_avg_pool1d(self.output_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::conv::{Conv1d, Conv2d};
+    use crate::nn::linear::Linear;
+    use crate::variable::Variable;
+    use ndarray::{Array, Ix3, Ix4};
+
+    #[test]
+    fn cnn_with_linear_head() {
+        let x = &Variable::new(Array::<f32, Ix4>::ones((3, 1, 12, 12)).into_dyn());
+
+        let mut conv = Conv2d::new(1, 4, (3, 3)).with_padding((1, 1));
+        let mut pool = MaxPool2d::new((2, 2));
+        let mut global = AdaptiveAvgPool2d::new((2, 2));
+        let mut head = Linear::new(16, 5);
+
+        let mut h = conv.f(x).relu();
+        h = pool.f(&h);
+        assert_eq!(h.borrow().data.shape(), &[3, 4, 6, 6]);
+        h = global.f(&h);
+        assert_eq!(h.borrow().data.shape(), &[3, 4, 2, 2]);
+
+        // Linear expects the features on the first axis
+        h = h.flatten(1, 3).transpose(0, 1);
+        let mut y = head.f(&h);
+        assert_eq!(y.borrow().data.shape(), &[5, 3]);
+
+        y = y.sum();
+        y.backward();
+        assert_eq!(conv.weight.borrow().get_grad_f().shape(), &[4, 1, 3, 3]);
+    }
+
+    #[test]
+    fn pool1d_forward() {
+        let x = &Variable::new(Array::<f32, Ix3>::ones((2, 3, 20)).into_dyn());
+
+        let mut conv = Conv1d::new(3, 4, 3);
+        let mut max = MaxPool1d::new(3).with_stride(2).with_padding(1);
+        let mut avg = AvgPool1d::new(2);
+        let mut global = AdaptiveAvgPool1d::new(1);
+
+        let h = max.f(&conv.f(x));
+        assert_eq!(h.borrow().data.shape(), &[2, 4, 9]);
+        let h = avg.f(&h);
+        assert_eq!(h.borrow().data.shape(), &[2, 4, 4]);
+        let h = global.f(&h);
+        assert_eq!(h.borrow().data.shape(), &[2, 4, 1]);
+    }
+}
@@ -0,0 +1,541 @@
+use crate::grad_fn::concat::{cat, stack};
+use crate::module::{prefixed, Module, Named};
+use crate::nn::init;
+use crate::variable::{Variable, VariableRef};
+use ndarray::{Array, NdFloat};
+
+// the recurrent layers follow the layout of `Linear`: every time step is a
+// `(features, batch)` variable and a whole sequence is a `(seq, features, batch)` tensor
+
+/// `(hidden_size, batch)` states of every layer and direction, indexed by
+/// `layer * num_directions + direction`
+pub type States<T> = Vec<VariableRef<T>>;
+
+/// hidden and cell states of an `LSTM`
+pub type LstmStates<T> = (States<T>, States<T>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CellKind {
+    Rnn,
+    Lstm,
+    Gru,
+}
+
+impl CellKind {
+    /// number of gates stacked in the weights
+    fn gates(self) -> usize {
+        match self {
+            CellKind::Rnn => 1,
+            CellKind::Lstm => 4,
+            CellKind::Gru => 3,
+        }
+    }
+}
+
+/// parameters of one layer in one direction, with the gates stacked along the first axis in
+/// the pytorch order: `(i, f, g, o)` for the LSTM and `(r, z, n)` for the GRU
+pub struct RecurrentCell<T: NdFloat> {
+    /// `(gates * hidden_size, input_size)`
+    pub weight_ih: VariableRef<T>,
+    /// `(gates * hidden_size, hidden_size)`
+    pub weight_hh: VariableRef<T>,
+    /// `(gates * hidden_size, 1)`
+    pub bias_ih: VariableRef<T>,
+    /// `(gates * hidden_size, 1)`
+    pub bias_hh: VariableRef<T>,
+}
+
+impl<T: NdFloat> RecurrentCell<T> {
+    pub fn params(&self) -> Vec<VariableRef<T>> {
+        vec![
+            self.weight_ih.clone(),
+            self.weight_hh.clone(),
+            self.bias_ih.clone(),
+            self.bias_hh.clone(),
+        ]
+    }
+
+    pub fn named_parameters(&self) -> Named<T> {
+        vec![
+            ("weight_ih".to_string(), self.weight_ih.clone()),
+            ("weight_hh".to_string(), self.weight_hh.clone()),
+            ("bias_ih".to_string(), self.bias_ih.clone()),
+            ("bias_hh".to_string(), self.bias_hh.clone()),
+        ]
+    }
+}
+
+impl RecurrentCell<f32> {
+    fn new(kind: CellKind, input_size: usize, hidden_size: usize) -> RecurrentCell<f32> {
+        let bound = 1. / (hidden_size as f32).sqrt();
+        let rows = kind.gates() * hidden_size;
+
+        RecurrentCell {
+            weight_ih: init::uniform(&[rows, input_size], bound),
+            weight_hh: init::uniform(&[rows, hidden_size], bound),
+            bias_ih: init::uniform(&[rows, 1], bound),
+            bias_hh: init::uniform(&[rows, 1], bound),
+        }
+    }
+}
+
+/// one time step on a `(input_size, batch)` input. The cell state `c` is only used by the
+/// LSTM, the other cells return it unchanged
+fn step<T: NdFloat>(
+    kind: CellKind,
+    cell: &RecurrentCell<T>,
+    x: &VariableRef<T>,
+    h: &VariableRef<T>,
+    c: &VariableRef<T>,
+) -> (VariableRef<T>, VariableRef<T>) {
+    let hidden_size = h.borrow().data.shape()[0];
+    let mut gi = cell.weight_ih.clone().dot(x) + &cell.bias_ih;
+    let mut gh = cell.weight_hh.clone().dot(h) + &cell.bias_hh;
+
+    match kind {
+        CellKind::Rnn => ((gi + gh).tanh(), c.clone()),
+        CellKind::Lstm => {
+            let mut gates = (gi + gh).split(hidden_size, 0);
+            let i = gates[0].sigmoid();
+            let f = gates[1].sigmoid();
+            let g = gates[2].tanh();
+            let o = gates[3].sigmoid();
+
+            let mut c = &(&f * c) + &(&i * &g);
+            let h = &o * &c.tanh();
+            (h, c)
+        }
+        CellKind::Gru => {
+            let gi = gi.split(hidden_size, 0);
+            let gh = gh.split(hidden_size, 0);
+            let r = (&gi[0] + &gh[0]).sigmoid();
+            let z = (&gi[1] + &gh[1]).sigmoid();
+            let n = (&gi[2] + &(&r * &gh[2])).tanh();
+
+            // (1 - z) * n + z * h
+            let h = &n + &(&z * &(h - &n));
+            (h, c.clone())
+        }
+    }
+}
+
+/// run the stacked layers over the sequence, the cells are ordered by layer then direction.
+/// Return the outputs of the last layer and the final hidden and cell states of every cell
+fn unroll<T: NdFloat>(
+    kind: CellKind,
+    cells: &[RecurrentCell<T>],
+    bidirectional: bool,
+    inputs: &[VariableRef<T>],
+    h0: States<T>,
+    c0: States<T>,
+) -> (Vec<VariableRef<T>>, States<T>, States<T>) {
+    let directions = if bidirectional { 2 } else { 1 };
+    let num_layers = cells.len() / directions;
+    assert_eq!(h0.len(), cells.len(), "expected one initial state per cell");
+
+    let mut layer_input = inputs.to_vec();
+    let (mut h_n, mut c_n) = (vec![], vec![]);
+    for layer in 0..num_layers {
+        let mut outputs: Vec<Vec<VariableRef<T>>> = vec![];
+
+        for direction in 0..directions {
+            let idx = layer * directions + direction;
+            let (mut h, mut c) = (h0[idx].clone(), c0[idx].clone());
+
+            let mut order: Vec<usize> = (0..layer_input.len()).collect();
+            if direction == 1 {
+                order.reverse();
+            }
+
+            let mut out = vec![];
+            for t in order {
+                let (new_h, new_c) = step(kind, &cells[idx], &layer_input[t], &h, &c);
+                h = new_h;
+                c = new_c;
+                out.push(h.clone());
+            }
+            if direction == 1 {
+                out.reverse();
+            }
+
+            h_n.push(h);
+            c_n.push(c);
+            outputs.push(out);
+        }
+
+        layer_input = if bidirectional {
+            (0..layer_input.len())
+                .map(|t| cat(&[outputs[0][t].clone(), outputs[1][t].clone()], 0))
+                .collect()
+        } else {
+            outputs.pop().unwrap()
+        };
+    }
+
+    (layer_input, h_n, c_n)
+}
+
+fn zero_states<T: NdFloat>(count: usize, hidden_size: usize, batch: usize) -> States<T> {
+    (0..count)
+        .map(|_| {
+            Variable::new_no_retain_grad(Array::<T, _>::zeros((hidden_size, batch)).into_dyn())
+        })
+        .collect()
+}
+
+/// batch size of a sequence of `(input_size, batch)` steps
+fn batch_size<T: NdFloat>(inputs: &[VariableRef<T>]) -> usize {
+    assert!(
+        !inputs.is_empty(),
+        "recurrent layer run over an empty sequence"
+    );
+    inputs[0].borrow().data.shape()[1]
+}
+
+macro_rules! impl_recurrent {
+    ($name:ident, $kind:expr) => {
+        impl $name<f32> {
+            pub fn new(input_size: usize, hidden_size: usize) -> $name<f32> {
+                let mut layer = $name {
+                    input_size,
+                    hidden_size,
+                    num_layers: 1,
+                    bidirectional: false,
+                    cells: vec![],
+                };
+                layer.init_cells();
+                layer
+            }
+
+            /// stack `num_layers` layers, the parameters are initialized again
+            pub fn with_num_layers(mut self, num_layers: usize) -> $name<f32> {
+                self.num_layers = num_layers;
+                self.init_cells();
+                self
+            }
+
+            /// run a second set of layers from the end of the sequence, their outputs are
+            /// concatenated to the forward ones along the feature axis
+            pub fn with_bidirectional(mut self, bidirectional: bool) -> $name<f32> {
+                self.bidirectional = bidirectional;
+                self.init_cells();
+                self
+            }
+
+            fn init_cells(&mut self) {
+                let directions = self.num_directions();
+                self.cells = (0..self.num_layers * directions)
+                    .map(|idx| {
+                        let input_size = if idx < directions {
+                            self.input_size
+                        } else {
+                            self.hidden_size * directions
+                        };
+                        RecurrentCell::new($kind, input_size, self.hidden_size)
+                    })
+                    .collect();
+            }
+        }
+
+        impl<T: NdFloat> $name<T> {
+            pub fn num_directions(&self) -> usize {
+                if self.bidirectional {
+                    2
+                } else {
+                    1
+                }
+            }
+
+            /// zero hidden states for a batch of `batch` sequences
+            fn zero_states(&self, batch: usize) -> States<T> {
+                zero_states(self.cells.len(), self.hidden_size, batch)
+            }
+        }
+
+        impl<T: NdFloat> Module<T> for $name<T> {
+            fn params(&self) -> Vec<VariableRef<T>> {
+                self.cells.iter().flat_map(|cell| cell.params()).collect()
+            }
+
+            /// the cells are named `cells.{layer * num_directions + direction}`
+            fn named_parameters(&self) -> Named<T> {
+                self.cells
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, cell)| {
+                        prefixed(&format!("cells.{}", i), cell.named_parameters())
+                    })
+                    .collect()
+            }
+
+            /// run over a `(seq, input_size, batch)` tensor from zero states and return the
+            /// `(seq, num_directions * hidden_size, batch)` outputs of the last layer
+            fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+                let inputs = input.clone().unbind(0);
+                let batch = batch_size(&inputs);
+                let states = self.zero_states(batch);
+
+                let (outputs, _, _) = unroll(
+                    $kind,
+                    &self.cells,
+                    self.bidirectional,
+                    &inputs,
+                    states.clone(),
+                    states,
+                );
+                stack(&outputs, 0)
+            }
+        }
+    };
+}
+
+/// Elman recurrent network, `h' = tanh(W_ih x + b_ih + W_hh h + b_hh)`
+pub struct RNN<T: NdFloat> {
+    pub input_size: usize,
+    pub hidden_size: usize,
+    pub num_layers: usize,
+    pub bidirectional: bool,
+    /// indexed by `layer * num_directions + direction`
+    pub cells: Vec<RecurrentCell<T>>,
+}
+
+impl_recurrent!(RNN, CellKind::Rnn);
+
+impl<T: NdFloat> RNN<T> {
+    /// unroll over the `(input_size, batch)` steps of a sequence from the `(hidden_size, batch)`
+    /// states `h0`, one per layer and direction, zero when not given. Return the outputs of the
+    /// last layer at every step and the final hidden states
+    pub fn forward(
+        &mut self,
+        inputs: &[VariableRef<T>],
+        h0: Option<States<T>>,
+    ) -> (Vec<VariableRef<T>>, States<T>) {
+        let batch = batch_size(inputs);
+        let h0 = h0.unwrap_or_else(|| self.zero_states(batch));
+        let c0 = self.zero_states(batch);
+
+        let (outputs, h_n, _) = unroll(
+            CellKind::Rnn,
+            &self.cells,
+            self.bidirectional,
+            inputs,
+            h0,
+            c0,
+        );
+        (outputs, h_n)
+    }
+}
+
+/// long short-term memory network
+pub struct LSTM<T: NdFloat> {
+    pub input_size: usize,
+    pub hidden_size: usize,
+    pub num_layers: usize,
+    pub bidirectional: bool,
+    /// indexed by `layer * num_directions + direction`
+    pub cells: Vec<RecurrentCell<T>>,
+}
+
+impl_recurrent!(LSTM, CellKind::Lstm);
+
+impl<T: NdFloat> LSTM<T> {
+    /// same as `RNN::forward` with both the hidden and the cell states
+    pub fn forward(
+        &mut self,
+        inputs: &[VariableRef<T>],
+        state: Option<LstmStates<T>>,
+    ) -> (Vec<VariableRef<T>>, LstmStates<T>) {
+        let batch = batch_size(inputs);
+        let (h0, c0) = state.unwrap_or_else(|| (self.zero_states(batch), self.zero_states(batch)));
+
+        let (outputs, h_n, c_n) = unroll(
+            CellKind::Lstm,
+            &self.cells,
+            self.bidirectional,
+            inputs,
+            h0,
+            c0,
+        );
+        (outputs, (h_n, c_n))
+    }
+}
+
+/// gated recurrent unit network
+pub struct GRU<T: NdFloat> {
+    pub input_size: usize,
+    pub hidden_size: usize,
+    pub num_layers: usize,
+    pub bidirectional: bool,
+    /// indexed by `layer * num_directions + direction`
+    pub cells: Vec<RecurrentCell<T>>,
+}
+
+impl_recurrent!(GRU, CellKind::Gru);
+
+impl<T: NdFloat> GRU<T> {
+    /// same as `RNN::forward`
+    pub fn forward(
+        &mut self,
+        inputs: &[VariableRef<T>],
+        h0: Option<States<T>>,
+    ) -> (Vec<VariableRef<T>>, States<T>) {
+        let batch = batch_size(inputs);
+        let h0 = h0.unwrap_or_else(|| self.zero_states(batch));
+        let c0 = self.zero_states(batch);
+
+        let (outputs, h_n, _) = unroll(
+            CellKind::Gru,
+            &self.cells,
+            self.bidirectional,
+            inputs,
+            h0,
+            c0,
+        );
+        (outputs, h_n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grad_fn::gradcheck::{arange, gradcheck};
+    use ndarray::{array, Ix3, IxDyn};
+
+    /// single layer cell built from the gradcheck inputs `[x, weight_ih, weight_hh, bias_ih,
+    /// bias_hh]`
+    fn cell(v: &[VariableRef<f64>]) -> Vec<RecurrentCell<f64>> {
+        vec![RecurrentCell {
+            weight_ih: v[1].clone(),
+            weight_hh: v[2].clone(),
+            bias_ih: v[3].clone(),
+            bias_hh: v[4].clone(),
+        }]
+    }
+
+    fn cell_inputs(gates: usize) -> Vec<Array<f64, IxDyn>> {
+        let (seq, input_size, hidden_size, batch) = (4, 3, 2, 2);
+        vec![
+            arange(&[seq, input_size, batch]),
+            arange(&[gates * hidden_size, input_size]) * 0.5,
+            arange(&[gates * hidden_size, hidden_size]) * 0.7,
+            arange(&[gates * hidden_size, 1]) * 0.3,
+            arange(&[gates * hidden_size, 1]) * -0.2,
+        ]
+    }
+
+    #[test]
+    fn rnn_check_method() {
+        let mut rnn = RNN {
+            input_size: 1,
+            hidden_size: 1,
+            num_layers: 1,
+            bidirectional: false,
+            cells: vec![RecurrentCell {
+                weight_ih: Variable::new(array!([0.5]).into_dyn()),
+                weight_hh: Variable::new(array!([2.0]).into_dyn()),
+                bias_ih: Variable::new(array!([0.1]).into_dyn()),
+                bias_hh: Variable::new(array!([-0.3]).into_dyn()),
+            }],
+        };
+        let inputs = vec![
+            Variable::new(array!([1.0]).into_dyn()),
+            Variable::new(array!([-1.0]).into_dyn()),
+        ];
+
+        let (outputs, h_n) = rnn.forward(&inputs, None);
+
+        let h1 = ((0.5_f64 + 0.1) + (0.0 - 0.3)).tanh();
+        let h2 = ((-0.5 + 0.1) + (2.0 * h1 - 0.3)).tanh();
+        assert_eq!(outputs[0].borrow().data, array!([h1]).into_dyn());
+        assert_eq!(outputs[1].borrow().data, array!([h2]).into_dyn());
+        assert_eq!(h_n[0].borrow().data, array!([h2]).into_dyn());
+    }
+
+    #[test]
+    fn check_backward_through_time() {
+        for &(kind, gates) in [(CellKind::Rnn, 1), (CellKind::Lstm, 4), (CellKind::Gru, 3)].iter() {
+            let f = |v: &[VariableRef<f64>]| {
+                let inputs = v[0].clone().unbind(0);
+                let states = zero_states(1, 2, 2);
+                let (outputs, h_n, c_n) =
+                    unroll(kind, &cell(v), false, &inputs, states.clone(), states);
+                // the final states take part in the loss along with the outputs
+                &(&stack(&outputs, 0).sum() + &h_n[0].clone().sum()) + &c_n[0].clone().sum()
+            };
+            if let Err(message) = gradcheck(f, &cell_inputs(gates), 1e-6, 1e-6) {
+                panic!("{:?}: {}", kind, message);
+            }
+        }
+    }
+
+    #[test]
+    fn bidirectional_reverse_direction() {
+        // the reverse direction of a bidirectional layer reads the reversed sequence
+        let mut gru = GRU::new(3, 4).with_bidirectional(true);
+        let inputs: Vec<VariableRef<f32>> = (0..5)
+            .map(|t| Variable::new(Array::from_elem((3, 2), t as f32 / 5.).into_dyn()))
+            .collect();
+
+        let (outputs, h_n) = gru.forward(&inputs, None);
+        assert_eq!(outputs.len(), 5);
+        assert_eq!(outputs[0].borrow().data.shape(), &[8, 2]);
+
+        let last_forward = outputs[4]
+            .borrow()
+            .data
+            .slice(ndarray::s![..4, ..])
+            .to_owned();
+        let first_reverse = outputs[0]
+            .borrow()
+            .data
+            .slice(ndarray::s![4.., ..])
+            .to_owned();
+        assert_eq!(last_forward.into_dyn(), h_n[0].borrow().data);
+        assert_eq!(first_reverse.into_dyn(), h_n[1].borrow().data);
+    }
+
+    #[test]
+    fn lstm_forward_stacked() {
+        let mut lstm = LSTM::new(3, 4).with_num_layers(2).with_bidirectional(true);
+        assert_eq!(lstm.params().len(), 16);
+        assert_eq!(lstm.cells[2].weight_ih.borrow().data.shape(), &[16, 8]);
+
+        let x = &Variable::new(Array::<f32, Ix3>::ones((6, 3, 2)).into_dyn());
+        let mut y = lstm.f(x);
+        assert_eq!(y.borrow().data.shape(), &[6, 8, 2]);
+
+        y = y.sum();
+        y.backward();
+        for p in lstm.params() {
+            assert!(p.borrow().get_grad_f().iter().any(|&g| g != 0.));
+        }
+    }
+
+    #[test]
+    fn lstm_initial_state() {
+        let mut lstm = LSTM::new(2, 3);
+        let inputs = vec![Variable::new(Array::<f32, _>::ones((2, 1)).into_dyn())];
+        let h0 = zero_states::<f32>(1, 3, 1);
+        let c0 = vec![Variable::new(Array::<f32, _>::ones((3, 1)).into_dyn())];
+
+        let (_, (_, mut c_n)) = lstm.forward(&inputs, Some((h0, c0.clone())));
+        let mut loss = c_n[0].sum();
+        loss.backward();
+
+        // the gradient of the cell state goes through the forget gate
+        assert!(c0[0].borrow().get_grad_f().iter().all(|&g| g > 0.));
+    }
+
+    #[test]
+    fn zero_states_keep_no_gradient() {
+        for h in zero_states::<f32>(2, 3, 1) {
+            assert!(!h.borrow().is_grad_retain());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "empty sequence")]
+    fn empty_sequence() {
+        GRU::<f32>::new(2, 3).forward(&[], None);
+    }
+}
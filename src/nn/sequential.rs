@@ -0,0 +1,193 @@
+use crate::module::{prefixed, Module, Named};
+use crate::variable::VariableRef;
+use ndarray::NdFloat;
+
+/// chain of modules applied one after the other, the output of each being the input of the
+/// next. Parameters, buffers and the training mode of the children are handled together
+pub struct Sequential<T: NdFloat> {
+    pub layers: Vec<Box<dyn Module<T>>>,
+}
+
+impl<T: NdFloat> Sequential<T> {
+    pub fn new() -> Sequential<T> {
+        Sequential { layers: vec![] }
+    }
+
+    /// append a module, as a builder
+    pub fn with_layer<M: Module<T> + 'static>(mut self, module: M) -> Sequential<T> {
+        self.push(module);
+        self
+    }
+
+    pub fn push<M: Module<T> + 'static>(&mut self, module: M) {
+        self.layers.push(Box::new(module));
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+impl<T: NdFloat> Default for Sequential<T> {
+    fn default() -> Sequential<T> {
+        Sequential::new()
+    }
+}
+
+impl<T: NdFloat> Module<T> for Sequential<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        self.layers.iter().flat_map(|lay| lay.params()).collect()
+    }
+
+    fn buffers(&self) -> Vec<VariableRef<T>> {
+        self.layers.iter().flat_map(|lay| lay.buffers()).collect()
+    }
+
+    /// the layers are named by their position, `0.weight`, `0.bias`, `2.weight`, ...
+    fn named_parameters(&self) -> Named<T> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(i, lay)| prefixed(&i.to_string(), lay.named_parameters()))
+            .collect()
+    }
+
+    fn named_buffers(&self) -> Named<T> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(i, lay)| prefixed(&i.to_string(), lay.named_buffers()))
+            .collect()
+    }
+
+    fn train(&mut self, mode: bool) {
+        for lay in self.layers.iter_mut() {
+            lay.train(mode);
+        }
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        let mut output = input.clone();
+        for lay in self.layers.iter_mut() {
+            output = lay.f(&output);
+        }
+        output
+    }
+}
+
+/// build a `Sequential` from a list of modules, `seq![Linear::new(2, 8), ReLU, Linear::new(8, 1)]`
+#[macro_export]
+macro_rules! seq {
+    ($($module:expr),* $(,)?) => {
+        $crate::nn::Sequential::new()$(.with_layer($module))*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grad_fn::functional::loss::mse_loss;
+    use crate::nn::activation::{ReLU, Tanh};
+    use crate::nn::dropout::Dropout;
+    use crate::nn::linear::Linear;
+    use crate::nn::norm::BatchNorm1d;
+    use crate::optim::sgd::{Optim, SGD};
+    use crate::variable::Variable;
+    use ndarray::{array, Array};
+
+    #[test]
+    fn sequential_forward() {
+        let mut model = seq![
+            Linear::new(3, 8),
+            BatchNorm1d::new(8),
+            ReLU,
+            Dropout::new(0.5),
+            Linear::new(8, 2),
+            Tanh,
+        ];
+        assert_eq!(model.len(), 6);
+        assert_eq!(model.params().len(), 6);
+        assert_eq!(model.buffers().len(), 2);
+
+        let x = &Variable::new(Array::<f32, _>::ones((3, 4)).into_dyn());
+        let mut y = model.f(x);
+        assert_eq!(y.borrow().data.shape(), &[2, 4]);
+
+        y.sum().backward();
+        model.zero_grad();
+        for param in model.params() {
+            assert!(param.borrow().get_grad_f().iter().all(|&g| g == 0.));
+        }
+
+        // the evaluation mode reaches the dropout, which becomes deterministic
+        model.eval();
+        let a = model.f(x).borrow().data.clone();
+        assert_eq!(model.f(x).borrow().data, a);
+    }
+
+    #[test]
+    fn sequential_train() {
+        let data = Variable::new_no_retain_grad(array!([1.0, -1.0], [0.5, 2.0]).into_dyn());
+        let target = Variable::new_no_retain_grad(array!([0.5, -0.5]).into_dyn());
+
+        let mut model = Sequential::new()
+            .with_layer(Linear::new(2, 8))
+            .with_layer(Tanh)
+            .with_layer(Linear::new(8, 1));
+        let optim = &mut SGD::new(model.params(), 0.05).unwrap();
+
+        let mut losses = vec![];
+        for _ in 0..50 {
+            model.zero_grad();
+            let mut loss = mse_loss(&model.f(&data), &target);
+            losses.push(loss.borrow().data[0]);
+            loss.backward();
+            optim.step();
+        }
+        assert!(losses[49] < losses[0]);
+    }
+
+    #[test]
+    fn sequential_state_dict() {
+        let model = seq![
+            Linear::new(3, 4),
+            ReLU,
+            BatchNorm1d::new(4),
+            Linear::new(4, 1)
+        ];
+
+        let keys: Vec<String> = model.state_dict().into_keys().collect();
+        assert_eq!(
+            keys,
+            vec![
+                "0.bias",
+                "0.weight",
+                "2.beta",
+                "2.gamma",
+                "2.running_mean",
+                "2.running_var",
+                "3.bias",
+                "3.weight"
+            ]
+        );
+
+        // same variables as `params`, in the same order
+        let named = model.named_parameters();
+        for ((_, a), b) in named.iter().zip(model.params()) {
+            assert_eq!(a.as_ptr(), b.as_ptr());
+        }
+
+        let mut other = seq![
+            Linear::new(3, 4),
+            ReLU,
+            BatchNorm1d::new(4),
+            Linear::new(4, 1)
+        ];
+        assert!(other.load_state_dict(&model.state_dict()).is_ok());
+        assert_eq!(other.state_dict(), model.state_dict());
+    }
+}
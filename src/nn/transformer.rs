@@ -0,0 +1,241 @@
+use crate::module::{prefixed, Module, Named};
+use crate::nn::attention::MultiheadAttention;
+use crate::nn::dropout::Dropout;
+use crate::nn::linear::Linear;
+use crate::nn::norm::LayerNorm;
+use crate::variable::VariableRef;
+use ndarray::{Array, IxDyn, NdFloat};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// self-attention followed by a position-wise feed-forward network, each block with a
+/// residual connection and a `LayerNorm`. Works on `(seq, d_model, batch)` sequences
+pub struct TransformerEncoderLayer<T: NdFloat> {
+    pub self_attn: MultiheadAttention<T>,
+    pub linear1: Linear<T>,
+    pub linear2: Linear<T>,
+    pub norm1: LayerNorm<T>,
+    pub norm2: LayerNorm<T>,
+    /// between the two linear layers of the feed-forward network
+    pub dropout: Dropout,
+    /// on the output of the attention block
+    pub dropout1: Dropout,
+    /// on the output of the feed-forward block
+    pub dropout2: Dropout,
+    /// normalize the input of each block instead of the output of the residual connection
+    pub norm_first: bool,
+}
+
+impl TransformerEncoderLayer<f32> {
+    pub fn new(
+        d_model: usize,
+        nhead: usize,
+        dim_feedforward: usize,
+    ) -> TransformerEncoderLayer<f32> {
+        TransformerEncoderLayer {
+            self_attn: MultiheadAttention::new(d_model, nhead),
+            linear1: Linear::new(d_model, dim_feedforward),
+            linear2: Linear::new(dim_feedforward, d_model),
+            norm1: LayerNorm::new(&[d_model]),
+            norm2: LayerNorm::new(&[d_model]),
+            dropout: Dropout::new(0.),
+            dropout1: Dropout::new(0.),
+            dropout2: Dropout::new(0.),
+            norm_first: false,
+        }
+    }
+
+    /// same probability for every dropout of the layer, attention weights included. The
+    /// generators are kept, seeded or not
+    pub fn with_dropout(mut self, p: f64) -> TransformerEncoderLayer<f32> {
+        for dropout in self.dropouts().iter_mut() {
+            dropout.set_p(p);
+        }
+        self
+    }
+
+    /// seed the generators of the dropouts, each one from a different seed
+    pub fn with_seed(mut self, seed: u64) -> TransformerEncoderLayer<f32> {
+        for (i, dropout) in self.dropouts().iter_mut().enumerate() {
+            dropout.rng = StdRng::seed_from_u64(seed + i as u64);
+        }
+        self
+    }
+
+    pub fn with_norm_first(mut self, norm_first: bool) -> TransformerEncoderLayer<f32> {
+        self.norm_first = norm_first;
+        self
+    }
+}
+
+impl<T: NdFloat> TransformerEncoderLayer<T> {
+    fn dropouts(&mut self) -> [&mut Dropout; 4] {
+        [
+            &mut self.self_attn.dropout,
+            &mut self.dropout,
+            &mut self.dropout1,
+            &mut self.dropout2,
+        ]
+    }
+
+    fn attention_block(
+        &mut self,
+        x: &VariableRef<T>,
+        attn_mask: Option<&Array<bool, IxDyn>>,
+        key_padding_mask: Option<&Array<bool, IxDyn>>,
+    ) -> VariableRef<T> {
+        let (output, _) = self.self_attn.forward(x, x, x, attn_mask, key_padding_mask);
+        self.dropout1.f(&output)
+    }
+
+    fn feed_forward_block(&mut self, x: &VariableRef<T>) -> VariableRef<T> {
+        let hidden = self.linear1.f_sequence(x).relu();
+        let hidden = self.dropout.f(&hidden);
+        let output = self.linear2.f_sequence(&hidden);
+        self.dropout2.f(&output)
+    }
+
+    /// encode a `(seq, d_model, batch)` input, the masks are the ones of
+    /// `MultiheadAttention::forward`
+    pub fn forward(
+        &mut self,
+        src: &VariableRef<T>,
+        src_mask: Option<&Array<bool, IxDyn>>,
+        key_padding_mask: Option<&Array<bool, IxDyn>>,
+    ) -> VariableRef<T> {
+        let mut x = src.clone();
+        if self.norm_first {
+            let normed = self.norm1.f(&x);
+            x = &x + &self.attention_block(&normed, src_mask, key_padding_mask);
+            let normed = self.norm2.f(&x);
+            x = &x + &self.feed_forward_block(&normed);
+        } else {
+            let attended = self.attention_block(&x, src_mask, key_padding_mask);
+            x = self.norm1.f(&(&x + &attended));
+            let fed = self.feed_forward_block(&x);
+            x = self.norm2.f(&(&x + &fed));
+        }
+        x
+    }
+}
+
+impl<T: NdFloat> Module<T> for TransformerEncoderLayer<T> {
+    fn params(&self) -> Vec<VariableRef<T>> {
+        let mut params = self.self_attn.params();
+        params.extend(self.linear1.params());
+        params.extend(self.linear2.params());
+        params.extend(self.norm1.params());
+        params.extend(self.norm2.params());
+        params
+    }
+
+    fn named_parameters(&self) -> Named<T> {
+        let mut named = prefixed("self_attn", self.self_attn.named_parameters());
+        named.extend(prefixed("linear1", self.linear1.named_parameters()));
+        named.extend(prefixed("linear2", self.linear2.named_parameters()));
+        named.extend(prefixed("norm1", self.norm1.named_parameters()));
+        named.extend(prefixed("norm2", self.norm2.named_parameters()));
+        named
+    }
+
+    fn train(&mut self, mode: bool) {
+        self.self_attn.train(mode);
+        Module::<T>::train(&mut self.dropout, mode);
+        Module::<T>::train(&mut self.dropout1, mode);
+        Module::<T>::train(&mut self.dropout2, mode);
+    }
+
+    fn f(&mut self, input: &VariableRef<T>) -> VariableRef<T> {
+        self.forward(input, None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grad_fn::functional::loss::mse_loss;
+    use crate::nn::attention::causal_mask;
+    use crate::nn::embedding::Embedding;
+    use crate::optim::sgd::{Optim, SGD};
+    use crate::variable::Variable;
+    use ndarray::{array, Axis};
+
+    #[test]
+    fn encoder_layer_forward() {
+        for &norm_first in [false, true].iter() {
+            let mut layer = TransformerEncoderLayer::new(8, 2, 16)
+                .with_dropout(0.1)
+                .with_seed(3)
+                .with_norm_first(norm_first);
+            let x = &Variable::new(Array::<f32, _>::ones((4, 8, 3)).into_dyn());
+
+            let mut y = layer.forward(x, Some(&causal_mask(4)), None);
+            assert_eq!(y.borrow().data.shape(), &[4, 8, 3]);
+            y.sum().backward();
+            assert_eq!(layer.params().len(), 16);
+
+            // no dropout in evaluation, two runs give the same output
+            layer.eval();
+            let a = layer.f(x).borrow().data.clone();
+            assert_eq!(layer.f(x).borrow().data, a);
+        }
+    }
+
+    #[test]
+    fn dropout_after_seed_keeps_generators() {
+        let mut layer = TransformerEncoderLayer::new(4, 2, 8)
+            .with_seed(3)
+            .with_dropout(0.2);
+        for (i, dropout) in layer.dropouts().iter().enumerate() {
+            assert_eq!(dropout.p, 0.2);
+            assert!(dropout.rng == StdRng::seed_from_u64(3 + i as u64));
+        }
+    }
+
+    #[test]
+    fn encoder_overfits_copy_task() {
+        let vocab = 5;
+        let d_model = 16;
+        let tokens = array!([0, 3, 1, 4], [2, 2, 0, 1], [4, 1, 3, 0], [1, 0, 2, 3]).into_dyn();
+        let one_hot = Array::from_shape_fn((4, vocab, 4), |(s, v, b)| {
+            f32::from(u8::from(tokens[[s, b]] == v))
+        });
+        let target = Variable::new_no_retain_grad(one_hot.into_dyn());
+
+        let mut embedding = Embedding::new(vocab, d_model);
+        let mut encoder = TransformerEncoderLayer::new(d_model, 2, 32);
+        let mut head = Linear::new(d_model, vocab);
+
+        let mut params = embedding.params();
+        params.extend(encoder.params());
+        params.extend(head.params());
+        let optim = &mut SGD::new(params.clone(), 0.5).unwrap();
+
+        let mut predict = |embedding: &mut Embedding<f32>| {
+            let x = embedding.forward(&tokens);
+            let mut logits = head.f_sequence(&encoder.f(&x));
+            logits.softmax_axis(1)
+        };
+
+        for _ in 0..150 {
+            for param in params.iter_mut() {
+                param.borrow_mut().zero_grad();
+            }
+            let mut loss = mse_loss(&predict(&mut embedding), &target);
+            loss.backward();
+            optim.step();
+        }
+
+        let probs = predict(&mut embedding);
+        for (pos, &token) in tokens.indexed_iter() {
+            let probs = &probs.borrow().data;
+            let lane = probs.index_axis(Axis(0), pos[0]);
+            let lane = lane.index_axis(Axis(1), pos[1]);
+            let best = lane.indexed_iter().fold(
+                (0, 0.),
+                |acc, (i, &p)| if p > acc.1 { (i[0], p) } else { acc },
+            );
+            assert_eq!(best.0, token);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use ndarray::{NdFloat, Zip};
+
+use crate::optim::{
+    decayed_grad, load_param_states, save_param_states, Optim, OptimState, ParamStates, Stateful,
+};
+use crate::variable::VariableRef;
+
+/// Adadelta, with the semantics of `torch.optim.Adadelta`
+pub struct Adadelta<T: NdFloat> {
+    pub params: Vec<VariableRef<T>>,
+    /// scale of the updates, 1 in the original method
+    pub lr: T,
+    /// decay rate of the running averages
+    pub rho: T,
+    pub eps: T,
+    pub weight_decay: T,
+    /// `square_avg` and `acc_delta`, running averages of the squared gradient and of the
+    /// squared updates
+    pub state: ParamStates<T>,
+}
+
+impl<T: NdFloat> Adadelta<T> {
+    pub fn new(params: Vec<VariableRef<T>>, lr: T) -> Adadelta<T> {
+        Adadelta {
+            params,
+            lr,
+            rho: T::from(0.9).unwrap(),
+            eps: T::from(1e-6).unwrap(),
+            weight_decay: T::zero(),
+            state: ParamStates::new(),
+        }
+    }
+
+    pub fn with_rho(mut self, rho: T) -> Adadelta<T> {
+        self.rho = rho;
+        self
+    }
+
+    pub fn with_eps(mut self, eps: T) -> Adadelta<T> {
+        self.eps = eps;
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: T) -> Adadelta<T> {
+        self.weight_decay = weight_decay;
+        self
+    }
+}
+
+impl<T: NdFloat> Optim for Adadelta<T> {
+    fn step(&mut self) {
+        let (lr, rho, eps) = (self.lr, self.rho, self.eps);
+        for p in self.params.iter_mut() {
+            let key = p.as_ptr();
+            let mut param_var = p.borrow_mut();
+            let grad = match decayed_grad(&param_var, self.weight_decay) {
+                Some(grad) => grad,
+                None => continue,
+            };
+            let shape = param_var.data.shape().to_vec();
+            let s = self.state.entry(key).or_default();
+            s.step += 1;
+
+            let mut square_avg = s.take("square_avg", T::zero(), &shape);
+            let mut acc_delta = s.take("acc_delta", T::zero(), &shape);
+            Zip::from(&mut param_var.data)
+                .and(&mut square_avg)
+                .and(&mut acc_delta)
+                .and(&grad)
+                .for_each(|p, v, a, &g| {
+                    *v = rho * *v + (T::one() - rho) * g * g;
+                    let delta = (*a + eps).sqrt() / (*v + eps).sqrt() * g;
+                    *a = rho * *a + (T::one() - rho) * delta * delta;
+                    *p -= lr * delta;
+                });
+            s.put("square_avg", square_avg);
+            s.put("acc_delta", acc_delta);
+        }
+    }
+}
+
+impl<T: NdFloat> Stateful<T> for Adadelta<T> {
+    fn lr(&self) -> T {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: T) {
+        self.lr = lr;
+    }
+
+    fn state_dict(&self) -> OptimState<T> {
+        let mut state = OptimState::default();
+        state.insert_scalars(&[
+            ("lr", self.lr.to_f64().unwrap()),
+            ("rho", self.rho.to_f64().unwrap()),
+            ("eps", self.eps.to_f64().unwrap()),
+            ("weight_decay", self.weight_decay.to_f64().unwrap()),
+        ]);
+        save_param_states(&self.params, &self.state, &mut state);
+        state
+    }
+
+    fn load_state_dict(&mut self, state: &OptimState<T>) -> Result<(), String> {
+        let param_states = load_param_states(&self.params, state)?;
+        self.lr = T::from(state.scalar("lr")?).unwrap();
+        self.rho = T::from(state.scalar("rho")?).unwrap();
+        self.eps = T::from(state.scalar("eps")?).unwrap();
+        self.weight_decay = T::from(state.scalar("weight_decay")?).unwrap();
+        self.state = param_states;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optim::testing::{assert_close, check_state_dict, trajectory};
+    use ndarray::array;
+
+    #[test]
+    fn adadelta() {
+        let steps = trajectory(|p| Adadelta::new(p, 1.), 3);
+        assert_close(&steps[0], &array![0.9968377381511013, -1.9968377262926713]);
+        assert_close(&steps[1], &array![0.9935981984076517, -1.9935957350142592]);
+        assert_close(&steps[2], &array![0.9903090828008376, -1.990300761714474]);
+    }
+
+    #[test]
+    fn weight_decay() {
+        let steps = trajectory(
+            |p| Adadelta::new(p, 1.).with_rho(0.5).with_weight_decay(0.5),
+            3,
+        );
+        assert_close(&steps[0], &array![0.9985857870661659, -1.9985857865947618]);
+        assert_close(&steps[1], &array![0.9969535656414367, -1.9969531788171067]);
+        assert_close(&steps[2], &array![0.9951561221591122, -1.9951547203556825]);
+    }
+
+    #[test]
+    fn state_dict_round_trip() {
+        check_state_dict(|p| Adadelta::new(p, 1.));
+    }
+}
@@ -0,0 +1,153 @@
+use ndarray::{NdFloat, Zip};
+
+use crate::optim::{
+    decayed_grad, load_param_states, save_param_states, Optim, OptimState, ParamStates, Stateful,
+};
+use crate::variable::VariableRef;
+
+/// Adagrad, with the semantics of `torch.optim.Adagrad`
+pub struct Adagrad<T: NdFloat> {
+    pub params: Vec<VariableRef<T>>,
+    pub lr: T,
+    /// the learning rate of step `t` is `lr / (1 + (t - 1) * lr_decay)`
+    pub lr_decay: T,
+    pub weight_decay: T,
+    /// starting value of the sums of squared gradients
+    pub initial_accumulator_value: T,
+    pub eps: T,
+    /// `sum`, the sum of the squared gradients
+    pub state: ParamStates<T>,
+}
+
+impl<T: NdFloat> Adagrad<T> {
+    pub fn new(params: Vec<VariableRef<T>>, lr: T) -> Adagrad<T> {
+        Adagrad {
+            params,
+            lr,
+            lr_decay: T::zero(),
+            weight_decay: T::zero(),
+            initial_accumulator_value: T::zero(),
+            eps: T::from(1e-10).unwrap(),
+            state: ParamStates::new(),
+        }
+    }
+
+    pub fn with_lr_decay(mut self, lr_decay: T) -> Adagrad<T> {
+        self.lr_decay = lr_decay;
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: T) -> Adagrad<T> {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    pub fn with_initial_accumulator_value(mut self, value: T) -> Adagrad<T> {
+        self.initial_accumulator_value = value;
+        self
+    }
+
+    pub fn with_eps(mut self, eps: T) -> Adagrad<T> {
+        self.eps = eps;
+        self
+    }
+}
+
+impl<T: NdFloat> Optim for Adagrad<T> {
+    fn step(&mut self) {
+        let eps = self.eps;
+        for p in self.params.iter_mut() {
+            let key = p.as_ptr();
+            let mut param_var = p.borrow_mut();
+            let grad = match decayed_grad(&param_var, self.weight_decay) {
+                Some(grad) => grad,
+                None => continue,
+            };
+            let shape = param_var.data.shape().to_vec();
+            let s = self.state.entry(key).or_default();
+            s.step += 1;
+
+            let clr = self.lr / (T::one() + T::from(s.step - 1).unwrap() * self.lr_decay);
+            let mut sum = s.take("sum", self.initial_accumulator_value, &shape);
+            Zip::from(&mut sum).and(&grad).for_each(|s, &g| *s += g * g);
+            Zip::from(&mut param_var.data)
+                .and(&grad)
+                .and(&sum)
+                .for_each(|p, &g, &s| *p -= clr * g / (s.sqrt() + eps));
+            s.put("sum", sum);
+        }
+    }
+}
+
+impl<T: NdFloat> Stateful<T> for Adagrad<T> {
+    fn lr(&self) -> T {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: T) {
+        self.lr = lr;
+    }
+
+    fn state_dict(&self) -> OptimState<T> {
+        let mut state = OptimState::default();
+        state.insert_scalars(&[
+            ("lr", self.lr.to_f64().unwrap()),
+            ("lr_decay", self.lr_decay.to_f64().unwrap()),
+            ("weight_decay", self.weight_decay.to_f64().unwrap()),
+            (
+                "initial_accumulator_value",
+                self.initial_accumulator_value.to_f64().unwrap(),
+            ),
+            ("eps", self.eps.to_f64().unwrap()),
+        ]);
+        save_param_states(&self.params, &self.state, &mut state);
+        state
+    }
+
+    fn load_state_dict(&mut self, state: &OptimState<T>) -> Result<(), String> {
+        let param_states = load_param_states(&self.params, state)?;
+        self.lr = T::from(state.scalar("lr")?).unwrap();
+        self.lr_decay = T::from(state.scalar("lr_decay")?).unwrap();
+        self.weight_decay = T::from(state.scalar("weight_decay")?).unwrap();
+        self.initial_accumulator_value =
+            T::from(state.scalar("initial_accumulator_value")?).unwrap();
+        self.eps = T::from(state.scalar("eps")?).unwrap();
+        self.state = param_states;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optim::testing::{assert_close, check_state_dict, trajectory};
+    use ndarray::array;
+
+    #[test]
+    fn adagrad() {
+        let steps = trajectory(|p| Adagrad::new(p, 0.1), 3);
+        assert_close(&steps[0], &array![0.90000000001, -1.900000000005]);
+        assert_close(&steps[1], &array![0.8331035268523168, -1.831125053815932]);
+        assert_close(&steps[2], &array![0.7804561813655163, -1.7758215150175451]);
+    }
+
+    #[test]
+    fn lr_decay() {
+        let steps = trajectory(
+            |p| {
+                Adagrad::new(p, 0.1)
+                    .with_lr_decay(0.5)
+                    .with_initial_accumulator_value(0.1)
+            },
+            3,
+        );
+        assert_close(&steps[0], &array![0.9046537410845317, -1.901227040339919]);
+        assert_close(&steps[1], &array![0.8611104025872476, -1.8555934953040996]);
+        assert_close(&steps[2], &array![0.8347109204056948, -1.827818002111034]);
+    }
+
+    #[test]
+    fn state_dict_round_trip() {
+        check_state_dict(|p| Adagrad::new(p, 0.1).with_lr_decay(0.1));
+    }
+}
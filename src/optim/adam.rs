@@ -0,0 +1,269 @@
+use ndarray::{NdFloat, Zip};
+
+use crate::optim::{
+    decayed_grad, flag, load_param_states, save_param_states, Optim, OptimState, ParamStates,
+    Stateful,
+};
+use crate::variable::VariableRef;
+
+/// Adam, with the semantics of `torch.optim.Adam`: the weight decay is an L2 penalty added
+/// to the gradient
+pub struct Adam<T: NdFloat> {
+    pub params: Vec<VariableRef<T>>,
+    pub lr: T,
+    pub betas: (T, T),
+    pub eps: T,
+    pub weight_decay: T,
+    pub amsgrad: bool,
+    /// `exp_avg` and `exp_avg_sq`, the running averages of the gradient and of its square,
+    /// and `max_exp_avg_sq` with amsgrad
+    pub state: ParamStates<T>,
+}
+
+/// AdamW, with the semantics of `torch.optim.AdamW`: the weight decay shrinks the
+/// parameters directly and is not rescaled by the moments
+pub struct AdamW<T: NdFloat> {
+    pub params: Vec<VariableRef<T>>,
+    pub lr: T,
+    pub betas: (T, T),
+    pub eps: T,
+    pub weight_decay: T,
+    pub amsgrad: bool,
+    /// same as `Adam::state`
+    pub state: ParamStates<T>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update<T: NdFloat>(
+    param: &mut VariableRef<T>,
+    state: &mut ParamStates<T>,
+    lr: T,
+    (beta1, beta2): (T, T),
+    eps: T,
+    weight_decay: T,
+    decoupled: bool,
+    amsgrad: bool,
+) {
+    let key = param.as_ptr();
+    let mut param_var = param.borrow_mut();
+    let l2 = if decoupled { T::zero() } else { weight_decay };
+    let grad = match decayed_grad(&param_var, l2) {
+        Some(grad) => grad,
+        None => return,
+    };
+    if decoupled && weight_decay != T::zero() {
+        param_var.data *= T::one() - lr * weight_decay;
+    }
+
+    let shape = param_var.data.shape().to_vec();
+    let s = state.entry(key).or_default();
+    s.step += 1;
+    let mut exp_avg = s.take("exp_avg", T::zero(), &shape);
+    let mut exp_avg_sq = s.take("exp_avg_sq", T::zero(), &shape);
+    Zip::from(&mut exp_avg)
+        .and(&grad)
+        .for_each(|m, &g| *m = beta1 * *m + (T::one() - beta1) * g);
+    Zip::from(&mut exp_avg_sq)
+        .and(&grad)
+        .for_each(|v, &g| *v = beta2 * *v + (T::one() - beta2) * g * g);
+
+    let mut max_exp_avg_sq = if amsgrad {
+        let mut max = s.take("max_exp_avg_sq", T::zero(), &shape);
+        Zip::from(&mut max)
+            .and(&exp_avg_sq)
+            .for_each(|m, &v| *m = m.max(v));
+        Some(max)
+    } else {
+        None
+    };
+
+    let bias_correction1 = T::one() - beta1.powi(s.step as i32);
+    let bias_correction2_sqrt = (T::one() - beta2.powi(s.step as i32)).sqrt();
+    let step_size = lr / bias_correction1;
+    Zip::from(&mut param_var.data)
+        .and(&exp_avg)
+        .and(max_exp_avg_sq.as_ref().unwrap_or(&exp_avg_sq))
+        .for_each(|p, &m, &v| *p -= step_size * m / (v.sqrt() / bias_correction2_sqrt + eps));
+
+    s.put("exp_avg", exp_avg);
+    s.put("exp_avg_sq", exp_avg_sq);
+    if let Some(max) = max_exp_avg_sq.take() {
+        s.put("max_exp_avg_sq", max);
+    }
+}
+
+macro_rules! impl_adam {
+    ($name:ident, $decoupled:expr, $weight_decay:expr) => {
+        impl<T: NdFloat> $name<T> {
+            pub fn new(params: Vec<VariableRef<T>>, lr: T) -> $name<T> {
+                $name {
+                    params,
+                    lr,
+                    betas: (T::from(0.9).unwrap(), T::from(0.999).unwrap()),
+                    eps: T::from(1e-8).unwrap(),
+                    weight_decay: T::from($weight_decay).unwrap(),
+                    amsgrad: false,
+                    state: ParamStates::new(),
+                }
+            }
+
+            /// decay rates of the running averages of the gradient and of its square
+            pub fn with_betas(mut self, betas: (T, T)) -> $name<T> {
+                self.betas = betas;
+                self
+            }
+
+            pub fn with_eps(mut self, eps: T) -> $name<T> {
+                self.eps = eps;
+                self
+            }
+
+            pub fn with_weight_decay(mut self, weight_decay: T) -> $name<T> {
+                self.weight_decay = weight_decay;
+                self
+            }
+
+            /// normalize by the largest running average of the squared gradient seen so far
+            pub fn with_amsgrad(mut self, amsgrad: bool) -> $name<T> {
+                self.amsgrad = amsgrad;
+                self
+            }
+        }
+
+        impl<T: NdFloat> Optim for $name<T> {
+            fn step(&mut self) {
+                for p in self.params.iter_mut() {
+                    update(
+                        p,
+                        &mut self.state,
+                        self.lr,
+                        self.betas,
+                        self.eps,
+                        self.weight_decay,
+                        $decoupled,
+                        self.amsgrad,
+                    );
+                }
+            }
+        }
+
+        impl<T: NdFloat> Stateful<T> for $name<T> {
+            fn lr(&self) -> T {
+                self.lr
+            }
+
+            fn set_lr(&mut self, lr: T) {
+                self.lr = lr;
+            }
+
+            fn state_dict(&self) -> OptimState<T> {
+                let mut state = OptimState::default();
+                state.insert_scalars(&[
+                    ("lr", self.lr.to_f64().unwrap()),
+                    ("beta1", self.betas.0.to_f64().unwrap()),
+                    ("beta2", self.betas.1.to_f64().unwrap()),
+                    ("eps", self.eps.to_f64().unwrap()),
+                    ("weight_decay", self.weight_decay.to_f64().unwrap()),
+                    ("amsgrad", flag(self.amsgrad)),
+                ]);
+                save_param_states(&self.params, &self.state, &mut state);
+                state
+            }
+
+            fn load_state_dict(&mut self, state: &OptimState<T>) -> Result<(), String> {
+                let param_states = load_param_states(&self.params, state)?;
+                self.lr = T::from(state.scalar("lr")?).unwrap();
+                self.betas = (
+                    T::from(state.scalar("beta1")?).unwrap(),
+                    T::from(state.scalar("beta2")?).unwrap(),
+                );
+                self.eps = T::from(state.scalar("eps")?).unwrap();
+                self.weight_decay = T::from(state.scalar("weight_decay")?).unwrap();
+                self.amsgrad = state.scalar("amsgrad")? != 0.;
+                self.state = param_states;
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_adam!(Adam, false, 0.);
+impl_adam!(AdamW, true, 0.01);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optim::testing::{assert_close, check_state_dict, descend, trajectory};
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    #[test]
+    fn adam() {
+        let steps = trajectory(|p| Adam::new(p, 0.1), 3);
+        assert_close(&steps[0], &array![0.900000001, -1.9000000005]);
+        assert_close(&steps[1], &array![0.8004122297123382, -1.8001664866210927]);
+        assert_close(&steps[2], &array![0.701586274504415, -1.7006233928121137]);
+    }
+
+    #[test]
+    fn adam_weight_decay() {
+        let steps = trajectory(|p| Adam::new(p, 0.1).with_weight_decay(0.5), 3);
+        assert_close(&steps[0], &array![0.9000000006666666, -1.9000000003333333]);
+        assert_close(&steps[1], &array![0.8004122290319746, -1.8001664862841649]);
+        assert_close(&steps[2], &array![0.7015862734654918, -1.7006233923016814]);
+    }
+
+    #[test]
+    fn adamw() {
+        let steps = trajectory(|p| AdamW::new(p, 0.1).with_weight_decay(0.5), 3);
+        assert_close(&steps[0], &array![0.850000001, -1.8000000004999999]);
+        assert_close(&steps[1], &array![0.7082484443656524, -1.6104122286682376]);
+        assert_close(&steps[2], &array![0.574973932253246, -1.431025244410647]);
+    }
+
+    #[test]
+    fn amsgrad() {
+        // the running average of the squared gradient decreases on the third step, which
+        // amsgrad ignores
+        let steps = trajectory(|p| Adam::new(p, 0.1).with_betas((0.9, 0.5)), 3);
+        assert_close(&steps[2], &array![0.6947884398971987, -1.6974448323401812]);
+
+        let steps = trajectory(
+            |p| Adam::new(p, 0.1).with_betas((0.9, 0.5)).with_amsgrad(true),
+            3,
+        );
+        assert_close(&steps[1], &array![0.7986254645971879, -1.7993039771829957]);
+        assert_close(&steps[2], &array![0.6954722654500891, -1.6974448323401812]);
+    }
+
+    #[test]
+    fn state_follows_parameters() {
+        let mut a = Variable::new(array![1.].into_dyn());
+        let mut b = Variable::new(array![1., 2.].into_dyn());
+        let mut optim = Adam::new(vec![a.clone(), b.clone()], 0.1);
+
+        // b has no gradient, it gets no state
+        a.borrow_mut().grad = Some(array![1.].into_dyn());
+        b.borrow_mut().grad = None;
+        optim.step();
+        assert_eq!(optim.state.len(), 1);
+        assert_eq!(optim.state[&a.as_ptr()].step, 1);
+        assert!(!optim.state.contains_key(&b.as_ptr()));
+    }
+
+    #[test]
+    fn state_dict_round_trip() {
+        let mut param = Variable::new(array![1., -2.].into_dyn());
+        let mut optim = AdamW::new(vec![param.clone()], 0.1).with_amsgrad(true);
+        descend(&mut param, &mut optim);
+        let state = optim.state_dict();
+        assert_eq!(state.scalar("0.step").unwrap(), 1.);
+        assert_eq!(
+            state.buffers.keys().collect::<Vec<_>>(),
+            vec!["0.exp_avg", "0.exp_avg_sq", "0.max_exp_avg_sq"]
+        );
+
+        check_state_dict(|p| AdamW::new(p, 0.1).with_amsgrad(true));
+        check_state_dict(|p| Adam::new(p, 0.1).with_weight_decay(0.1));
+    }
+}
@@ -0,0 +1,149 @@
+use ndarray::{NdFloat, Zip};
+
+use crate::optim::{
+    decayed_grad, load_param_states, save_param_states, Optim, OptimState, ParamStates, Stateful,
+};
+use crate::variable::VariableRef;
+
+/// Adamax, the infinity norm variant of Adam, with the semantics of `torch.optim.Adamax`
+pub struct Adamax<T: NdFloat> {
+    pub params: Vec<VariableRef<T>>,
+    pub lr: T,
+    pub betas: (T, T),
+    pub eps: T,
+    pub weight_decay: T,
+    /// `exp_avg`, running average of the gradient, and `exp_inf`, decaying maximum of its
+    /// absolute value
+    pub state: ParamStates<T>,
+}
+
+impl<T: NdFloat> Adamax<T> {
+    pub fn new(params: Vec<VariableRef<T>>, lr: T) -> Adamax<T> {
+        Adamax {
+            params,
+            lr,
+            betas: (T::from(0.9).unwrap(), T::from(0.999).unwrap()),
+            eps: T::from(1e-8).unwrap(),
+            weight_decay: T::zero(),
+            state: ParamStates::new(),
+        }
+    }
+
+    pub fn with_betas(mut self, betas: (T, T)) -> Adamax<T> {
+        self.betas = betas;
+        self
+    }
+
+    pub fn with_eps(mut self, eps: T) -> Adamax<T> {
+        self.eps = eps;
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: T) -> Adamax<T> {
+        self.weight_decay = weight_decay;
+        self
+    }
+}
+
+impl<T: NdFloat> Optim for Adamax<T> {
+    fn step(&mut self) {
+        let ((beta1, beta2), eps) = (self.betas, self.eps);
+        for p in self.params.iter_mut() {
+            let key = p.as_ptr();
+            let mut param_var = p.borrow_mut();
+            let grad = match decayed_grad(&param_var, self.weight_decay) {
+                Some(grad) => grad,
+                None => continue,
+            };
+            let shape = param_var.data.shape().to_vec();
+            let s = self.state.entry(key).or_default();
+            s.step += 1;
+
+            let clr = self.lr / (T::one() - beta1.powi(s.step as i32));
+            let mut exp_avg = s.take("exp_avg", T::zero(), &shape);
+            let mut exp_inf = s.take("exp_inf", T::zero(), &shape);
+            Zip::from(&mut param_var.data)
+                .and(&mut exp_avg)
+                .and(&mut exp_inf)
+                .and(&grad)
+                .for_each(|p, m, u, &g| {
+                    *m = beta1 * *m + (T::one() - beta1) * g;
+                    *u = (beta2 * *u).max(g.abs() + eps);
+                    *p -= clr * *m / *u;
+                });
+            s.put("exp_avg", exp_avg);
+            s.put("exp_inf", exp_inf);
+        }
+    }
+}
+
+impl<T: NdFloat> Stateful<T> for Adamax<T> {
+    fn lr(&self) -> T {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: T) {
+        self.lr = lr;
+    }
+
+    fn state_dict(&self) -> OptimState<T> {
+        let mut state = OptimState::default();
+        state.insert_scalars(&[
+            ("lr", self.lr.to_f64().unwrap()),
+            ("beta1", self.betas.0.to_f64().unwrap()),
+            ("beta2", self.betas.1.to_f64().unwrap()),
+            ("eps", self.eps.to_f64().unwrap()),
+            ("weight_decay", self.weight_decay.to_f64().unwrap()),
+        ]);
+        save_param_states(&self.params, &self.state, &mut state);
+        state
+    }
+
+    fn load_state_dict(&mut self, state: &OptimState<T>) -> Result<(), String> {
+        let param_states = load_param_states(&self.params, state)?;
+        self.lr = T::from(state.scalar("lr")?).unwrap();
+        self.betas = (
+            T::from(state.scalar("beta1")?).unwrap(),
+            T::from(state.scalar("beta2")?).unwrap(),
+        );
+        self.eps = T::from(state.scalar("eps")?).unwrap();
+        self.weight_decay = T::from(state.scalar("weight_decay")?).unwrap();
+        self.state = param_states;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optim::testing::{assert_close, check_state_dict, trajectory};
+    use ndarray::array;
+
+    #[test]
+    fn adamax() {
+        let steps = trajectory(|p| Adamax::new(p, 0.1), 3);
+        assert_close(&steps[0], &array![0.900000001, -1.9000000005]);
+        assert_close(&steps[1], &array![0.8051683281165902, -1.8025341140345872]);
+        assert_close(&steps[2], &array![0.7154994747368055, -1.7076482362641507]);
+    }
+
+    #[test]
+    fn weight_decay() {
+        let steps = trajectory(
+            |p| {
+                Adamax::new(p, 0.1)
+                    .with_betas((0.9, 0.5))
+                    .with_weight_decay(0.5)
+            },
+            3,
+        );
+        assert_close(&steps[0], &array![0.9000000006666666, -1.9000000003333333]);
+        assert_close(&steps[1], &array![0.7947368435906432, -1.7975069259094618]);
+        assert_close(&steps[2], &array![0.6826183724657777, -1.6922461927144357]);
+    }
+
+    #[test]
+    fn state_dict_round_trip() {
+        check_state_dict(|p| Adamax::new(p, 0.1));
+    }
+}
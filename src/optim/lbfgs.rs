@@ -0,0 +1,747 @@
+use std::collections::VecDeque;
+
+use ndarray::{s, Array, Array1, Array2, Axis, IxDyn, NdFloat};
+
+use crate::optim::{flag, OptimState, Stateful};
+use crate::variable::VariableRef;
+
+// port of `torch.optim.LBFGS`. The parameters are seen as a single flat vector, the
+// history holds the last `history_size` pairs of parameter and gradient differences and
+// the direction is given by the two-loop recursion
+
+/// limited memory BFGS. Every step runs up to `max_iter` iterations, each one evaluating
+/// the loss again through the closure given to `step`
+pub struct LBFGS<T: NdFloat> {
+    pub params: Vec<VariableRef<T>>,
+    pub lr: T,
+    /// iterations per step
+    pub max_iter: usize,
+    /// evaluations of the closure per step, `max_iter * 5 / 4` when `None`
+    pub max_eval: Option<usize>,
+    /// stop when the largest gradient coordinate is below
+    pub tolerance_grad: T,
+    /// stop when the loss or the parameters change less than this
+    pub tolerance_change: T,
+    pub history_size: usize,
+    /// pick the step length with a line search satisfying the strong Wolfe conditions,
+    /// instead of the fixed `lr`
+    pub strong_wolfe: bool,
+    pub state: LBFGSState<T>,
+}
+
+/// what carries over from one step to the next
+#[derive(Clone, Debug, PartialEq)]
+pub struct LBFGSState<T: NdFloat> {
+    /// last direction and step length
+    pub d: Option<Array1<T>>,
+    pub t: T,
+    /// differences of gradients
+    pub old_dirs: VecDeque<Array1<T>>,
+    /// differences of parameters
+    pub old_stps: VecDeque<Array1<T>>,
+    /// inverses of `old_dirs[i] . old_stps[i]`
+    pub ro: VecDeque<T>,
+    /// scale of the initial inverse hessian
+    pub h_diag: T,
+    pub prev_flat_grad: Option<Array1<T>>,
+    pub prev_loss: T,
+    pub n_iter: usize,
+    pub func_evals: usize,
+}
+
+impl<T: NdFloat> Default for LBFGSState<T> {
+    fn default() -> LBFGSState<T> {
+        LBFGSState {
+            d: None,
+            t: T::zero(),
+            old_dirs: VecDeque::new(),
+            old_stps: VecDeque::new(),
+            ro: VecDeque::new(),
+            h_diag: T::one(),
+            prev_flat_grad: None,
+            prev_loss: T::zero(),
+            n_iter: 0,
+            func_evals: 0,
+        }
+    }
+}
+
+impl<T: NdFloat> LBFGS<T> {
+    pub fn new(params: Vec<VariableRef<T>>, lr: T) -> LBFGS<T> {
+        LBFGS {
+            params,
+            lr,
+            max_iter: 20,
+            max_eval: None,
+            tolerance_grad: T::from(1e-7).unwrap(),
+            tolerance_change: T::from(1e-9).unwrap(),
+            history_size: 100,
+            strong_wolfe: false,
+            state: LBFGSState::default(),
+        }
+    }
+
+    pub fn with_max_iter(mut self, max_iter: usize) -> LBFGS<T> {
+        self.max_iter = max_iter;
+        self
+    }
+
+    pub fn with_max_eval(mut self, max_eval: usize) -> LBFGS<T> {
+        self.max_eval = Some(max_eval);
+        self
+    }
+
+    pub fn with_tolerance_grad(mut self, tolerance_grad: T) -> LBFGS<T> {
+        self.tolerance_grad = tolerance_grad;
+        self
+    }
+
+    pub fn with_tolerance_change(mut self, tolerance_change: T) -> LBFGS<T> {
+        self.tolerance_change = tolerance_change;
+        self
+    }
+
+    pub fn with_history_size(mut self, history_size: usize) -> LBFGS<T> {
+        assert!(history_size > 0, "history_size must be positive");
+        self.history_size = history_size;
+        self
+    }
+
+    pub fn with_strong_wolfe(mut self, strong_wolfe: bool) -> LBFGS<T> {
+        self.strong_wolfe = strong_wolfe;
+        self
+    }
+
+    /// gradients of all the parameters one after the other, zeros for the ones without
+    fn flat_grad(&self) -> Array1<T> {
+        let mut flat = Vec::new();
+        for p in self.params.iter() {
+            let var = p.borrow();
+            match var.get_grad() {
+                Ok(grad) => flat.extend(grad.iter().copied()),
+                Err(_) => flat.resize(flat.len() + var.data.len(), T::zero()),
+            }
+        }
+        Array1::from(flat)
+    }
+
+    fn flat_params(&self) -> Array1<T> {
+        let mut flat = Vec::new();
+        for p in self.params.iter() {
+            flat.extend(p.borrow().data.iter().copied());
+        }
+        Array1::from(flat)
+    }
+
+    fn set_params(&mut self, flat: &Array1<T>) {
+        let mut offset = 0;
+        for p in self.params.iter_mut() {
+            let mut var = p.borrow_mut();
+            let len = var.data.len();
+            let values = flat.slice(s![offset..offset + len]);
+            var.data
+                .iter_mut()
+                .zip(values.iter())
+                .for_each(|(x, &v)| *x = v);
+            offset += len;
+        }
+    }
+
+    /// move the parameters by `t * d`
+    fn add_grad(&mut self, t: T, d: &Array1<T>) {
+        let flat = self.flat_params() + d * t;
+        self.set_params(&flat);
+    }
+
+    /// loss and gradient at `x + t * d`, the parameters are left at `x`
+    fn directional_evaluate<F: FnMut() -> T>(
+        &mut self,
+        closure: &mut F,
+        x: &Array1<T>,
+        t: T,
+        d: &Array1<T>,
+    ) -> (T, Array1<T>) {
+        self.set_params(&(x + &(d * t)));
+        let loss = closure();
+        let flat_grad = self.flat_grad();
+        self.set_params(x);
+        (loss, flat_grad)
+    }
+
+    /// run up to `max_iter` iterations and return the loss before the first one. The
+    /// closure must zero the gradients, compute the loss, call `backward` on it and return
+    /// its value
+    pub fn step<F: FnMut() -> T>(&mut self, mut closure: F) -> T {
+        let max_eval = self.max_eval.unwrap_or(self.max_iter * 5 / 4);
+        let (lr, tolerance_grad, tolerance_change) =
+            (self.lr, self.tolerance_grad, self.tolerance_change);
+
+        let orig_loss = closure();
+        let mut loss = orig_loss;
+        let mut current_evals = 1;
+        self.state.func_evals += 1;
+
+        let mut flat_grad = self.flat_grad();
+        if max_abs(&flat_grad) <= tolerance_grad {
+            return orig_loss;
+        }
+
+        let mut n_iter = 0;
+        while n_iter < self.max_iter {
+            n_iter += 1;
+            self.state.n_iter += 1;
+
+            let d = if self.state.n_iter == 1 {
+                self.state.old_dirs.clear();
+                self.state.old_stps.clear();
+                self.state.ro.clear();
+                self.state.h_diag = T::one();
+                flat_grad.mapv(|g| -g)
+            } else {
+                self.update_history(&flat_grad);
+                self.direction(&flat_grad)
+            };
+
+            self.state.prev_flat_grad = Some(flat_grad.clone());
+            self.state.prev_loss = loss;
+
+            // a first step of at most 1 in the L1 norm, the history gives a scale after
+            let mut t = if self.state.n_iter == 1 {
+                let l1 = flat_grad.iter().fold(T::zero(), |acc, g| acc + g.abs());
+                T::one().min(T::one() / l1) * lr
+            } else {
+                lr
+            };
+
+            let gtd = flat_grad.dot(&d);
+            if gtd > -tolerance_change {
+                self.state.d = Some(d);
+                self.state.t = t;
+                break;
+            }
+
+            let mut ls_func_evals = 0;
+            let mut opt_cond = false;
+            if self.strong_wolfe {
+                let x_init = self.flat_params();
+                let (new_loss, new_grad, new_t, evals) =
+                    self.strong_wolfe_search(&mut closure, &x_init, t, &d, loss, &flat_grad, gtd);
+                loss = new_loss;
+                flat_grad = new_grad;
+                t = new_t;
+                ls_func_evals = evals;
+                self.add_grad(t, &d);
+                opt_cond = max_abs(&flat_grad) <= tolerance_grad;
+            } else {
+                self.add_grad(t, &d);
+                if n_iter != self.max_iter {
+                    // no need to evaluate the loss after the last iteration
+                    loss = closure();
+                    flat_grad = self.flat_grad();
+                    opt_cond = max_abs(&flat_grad) <= tolerance_grad;
+                    ls_func_evals = 1;
+                }
+            }
+            current_evals += ls_func_evals;
+            self.state.func_evals += ls_func_evals;
+
+            let moved = max_abs(&(&d * t));
+            self.state.d = Some(d);
+            self.state.t = t;
+
+            if n_iter == self.max_iter
+                || current_evals >= max_eval
+                || opt_cond
+                || moved <= tolerance_change
+                || (loss - self.state.prev_loss).abs() < tolerance_change
+            {
+                break;
+            }
+        }
+        orig_loss
+    }
+
+    /// store the curvature pair of the last iteration, when it keeps the approximation
+    /// positive definite
+    fn update_history(&mut self, flat_grad: &Array1<T>) {
+        let state = &mut self.state;
+        let y = flat_grad - state.prev_flat_grad.as_ref().unwrap();
+        let s = state.d.as_ref().unwrap() * state.t;
+        let ys = y.dot(&s);
+        if ys > T::from(1e-10).unwrap() {
+            if state.old_dirs.len() == self.history_size {
+                state.old_dirs.pop_front();
+                state.old_stps.pop_front();
+                state.ro.pop_front();
+            }
+            state.h_diag = ys / y.dot(&y);
+            state.old_dirs.push_back(y);
+            state.old_stps.push_back(s);
+            state.ro.push_back(T::one() / ys);
+        }
+    }
+
+    /// product of the approximate inverse hessian with `-flat_grad`, by the two-loop
+    /// recursion
+    fn direction(&self, flat_grad: &Array1<T>) -> Array1<T> {
+        let state = &self.state;
+        let num_old = state.old_dirs.len();
+        let mut al = vec![T::zero(); num_old];
+        let mut q = flat_grad.mapv(|g| -g);
+        for i in (0..num_old).rev() {
+            al[i] = state.old_stps[i].dot(&q) * state.ro[i];
+            q.scaled_add(-al[i], &state.old_dirs[i]);
+        }
+        let mut r = q * state.h_diag;
+        for (((y, s), &ro), &a) in state
+            .old_dirs
+            .iter()
+            .zip(&state.old_stps)
+            .zip(&state.ro)
+            .zip(&al)
+        {
+            let be_i = y.dot(&r) * ro;
+            r.scaled_add(a - be_i, s);
+        }
+        r
+    }
+
+    /// step length along `d` satisfying the strong Wolfe conditions, found by bracketing
+    /// then zooming with cubic interpolation. Returns the loss, the gradient and the step
+    /// length found and the number of evaluations of the closure
+    #[allow(clippy::too_many_arguments)]
+    fn strong_wolfe_search<F: FnMut() -> T>(
+        &mut self,
+        closure: &mut F,
+        x: &Array1<T>,
+        mut t: T,
+        d: &Array1<T>,
+        f: T,
+        g: &Array1<T>,
+        gtd: T,
+    ) -> (T, Array1<T>, T, usize) {
+        let c1 = T::from(1e-4).unwrap();
+        let c2 = T::from(0.9).unwrap();
+        let tolerance_change = self.tolerance_change;
+        let max_ls = 25;
+
+        let d_norm = max_abs(d);
+        let (mut f_new, mut g_new) = self.directional_evaluate(closure, x, t, d);
+        let mut ls_func_evals = 1;
+        let mut gtd_new = g_new.dot(d);
+
+        let (mut t_prev, mut f_prev, mut g_prev, mut gtd_prev) = (T::zero(), f, g.clone(), gtd);
+        let mut done = false;
+        let mut ls_iter = 0;
+
+        // bracket an interval containing a point satisfying the conditions
+        let mut bracket: Vec<(T, T, Array1<T>, T)> = loop {
+            if ls_iter == max_ls {
+                break vec![(T::zero(), f, g.clone(), gtd), (t, f_new, g_new, gtd_new)];
+            }
+            if f_new > f + c1 * t * gtd || (ls_iter > 1 && f_new >= f_prev) {
+                break vec![
+                    (t_prev, f_prev, g_prev, gtd_prev),
+                    (t, f_new, g_new, gtd_new),
+                ];
+            }
+            if gtd_new.abs() <= -c2 * gtd {
+                done = true;
+                break vec![(t, f_new, g_new, gtd_new)];
+            }
+            if gtd_new >= T::zero() {
+                break vec![
+                    (t_prev, f_prev, g_prev, gtd_prev),
+                    (t, f_new, g_new, gtd_new),
+                ];
+            }
+
+            // extrapolate
+            let min_step = t + T::from(0.01).unwrap() * (t - t_prev);
+            let max_step = t * T::from(10.).unwrap();
+            let next = cubic_interpolate(
+                (t_prev, f_prev, gtd_prev),
+                (t, f_new, gtd_new),
+                Some((min_step, max_step)),
+            );
+            t_prev = t;
+            f_prev = f_new;
+            g_prev = g_new;
+            gtd_prev = gtd_new;
+            t = next;
+
+            let (f_next, g_next) = self.directional_evaluate(closure, x, t, d);
+            f_new = f_next;
+            g_new = g_next;
+            ls_func_evals += 1;
+            gtd_new = g_new.dot(d);
+            ls_iter += 1;
+        };
+
+        // zoom into the bracket until a point satisfies the conditions
+        let positions = |bracket: &Vec<(T, T, Array1<T>, T)>| {
+            if bracket[0].1 <= bracket[bracket.len() - 1].1 {
+                (0, 1)
+            } else {
+                (1, 0)
+            }
+        };
+        let mut insuf_progress = false;
+        let (mut low_pos, mut high_pos) = positions(&bracket);
+        while !done && ls_iter < max_ls {
+            let (lo, hi) = if bracket[0].0 <= bracket[1].0 {
+                (bracket[0].0, bracket[1].0)
+            } else {
+                (bracket[1].0, bracket[0].0)
+            };
+            if (hi - lo) * d_norm < tolerance_change {
+                break;
+            }
+
+            t = cubic_interpolate(
+                (bracket[0].0, bracket[0].1, bracket[0].3),
+                (bracket[1].0, bracket[1].1, bracket[1].3),
+                None,
+            );
+
+            // stay away from the ends of the bracket when the interpolation keeps
+            // falling next to them
+            let eps = T::from(0.1).unwrap() * (hi - lo);
+            if (hi - t).min(t - lo) < eps {
+                if insuf_progress || t >= hi || t <= lo {
+                    t = if (t - hi).abs() < (t - lo).abs() {
+                        hi - eps
+                    } else {
+                        lo + eps
+                    };
+                    insuf_progress = false;
+                } else {
+                    insuf_progress = true;
+                }
+            } else {
+                insuf_progress = false;
+            }
+
+            let (f_next, g_next) = self.directional_evaluate(closure, x, t, d);
+            ls_func_evals += 1;
+            let gtd_next = g_next.dot(d);
+            ls_iter += 1;
+
+            if f_next > f + c1 * t * gtd || f_next >= bracket[low_pos].1 {
+                bracket[high_pos] = (t, f_next, g_next, gtd_next);
+                let (low, high) = positions(&bracket);
+                low_pos = low;
+                high_pos = high;
+            } else {
+                if gtd_next.abs() <= -c2 * gtd {
+                    done = true;
+                } else if gtd_next * (bracket[high_pos].0 - bracket[low_pos].0) >= T::zero() {
+                    bracket[high_pos] = bracket[low_pos].clone();
+                }
+                bracket[low_pos] = (t, f_next, g_next, gtd_next);
+            }
+        }
+
+        let (t, f_new, g_new, _) = bracket.swap_remove(low_pos);
+        (f_new, g_new, t, ls_func_evals)
+    }
+}
+
+/// lets the optimizer go in a `Checkpoint` or under a learning rate scheduler. It is not an
+/// `Optim`: a step evaluates the loss again, call `LBFGS::step` with a closure
+impl<T: NdFloat> Stateful<T> for LBFGS<T> {
+    fn lr(&self) -> T {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: T) {
+        self.lr = lr;
+    }
+
+    fn state_dict(&self) -> OptimState<T> {
+        let to_f64 = |x: T| x.to_f64().unwrap();
+        let state = &self.state;
+        let mut dict = OptimState::default();
+        dict.insert_scalars(&[
+            ("lr", to_f64(self.lr)),
+            ("max_iter", self.max_iter as f64),
+            ("tolerance_grad", to_f64(self.tolerance_grad)),
+            ("tolerance_change", to_f64(self.tolerance_change)),
+            ("history_size", self.history_size as f64),
+            ("strong_wolfe", flag(self.strong_wolfe)),
+            ("t", to_f64(state.t)),
+            ("h_diag", to_f64(state.h_diag)),
+            ("prev_loss", to_f64(state.prev_loss)),
+            ("n_iter", state.n_iter as f64),
+            ("func_evals", state.func_evals as f64),
+        ]);
+        if let Some(max_eval) = self.max_eval {
+            dict.insert_scalars(&[("max_eval", max_eval as f64)]);
+        }
+
+        let mut insert = |name: &str, array: Array<T, IxDyn>| {
+            dict.buffers.insert(name.to_string(), array);
+        };
+        if let Some(d) = &state.d {
+            insert("d", d.clone().into_dyn());
+        }
+        if let Some(grad) = &state.prev_flat_grad {
+            insert("prev_flat_grad", grad.clone().into_dyn());
+        }
+        if !state.ro.is_empty() {
+            insert("old_dirs", stack_history(&state.old_dirs).into_dyn());
+            insert("old_stps", stack_history(&state.old_stps).into_dyn());
+            insert("ro", Array1::from(Vec::from(state.ro.clone())).into_dyn());
+        }
+        dict
+    }
+
+    fn load_state_dict(&mut self, dict: &OptimState<T>) -> Result<(), String> {
+        let scalar = |key: &str| dict.scalar(key).map(|x| T::from(x).unwrap());
+        let count = |key: &str| dict.scalar(key).map(|x| x as usize);
+
+        let n = self
+            .params
+            .iter()
+            .map(|p| p.borrow().data.len())
+            .sum::<usize>();
+        let k = dict.buffers.get("ro").map_or(0, |ro| ro.len());
+        let mut state = LBFGSState::default();
+        for (key, array) in dict.buffers.iter() {
+            let expected = match key.as_str() {
+                "d" | "prev_flat_grad" => vec![n],
+                "old_dirs" | "old_stps" => vec![k, n],
+                "ro" => vec![k],
+                _ => return Err(format!("unexpected buffer {} in optimizer state", key)),
+            };
+            if array.shape() != expected.as_slice() {
+                return Err(format!(
+                    "{} has shape {:?} but {:?} was expected",
+                    key,
+                    array.shape(),
+                    expected
+                ));
+            }
+            let flat = || array.iter().copied().collect::<Array1<T>>();
+            let rows = || {
+                array
+                    .outer_iter()
+                    .map(|row| row.iter().copied().collect())
+                    .collect()
+            };
+            match key.as_str() {
+                "d" => state.d = Some(flat()),
+                "prev_flat_grad" => state.prev_flat_grad = Some(flat()),
+                "old_dirs" => state.old_dirs = rows(),
+                "old_stps" => state.old_stps = rows(),
+                _ => state.ro = array.iter().copied().collect(),
+            }
+        }
+        if state.old_dirs.len() != k || state.old_stps.len() != k {
+            return Err("optimizer state has an incomplete history".to_string());
+        }
+        let history_size = count("history_size")?;
+        if history_size == 0 || k > history_size {
+            return Err(format!(
+                "history of {} pairs for a history_size of {}",
+                k, history_size
+            ));
+        }
+
+        state.t = scalar("t")?;
+        state.h_diag = scalar("h_diag")?;
+        state.prev_loss = scalar("prev_loss")?;
+        state.n_iter = count("n_iter")?;
+        state.func_evals = count("func_evals")?;
+
+        self.lr = scalar("lr")?;
+        self.max_iter = count("max_iter")?;
+        self.max_eval = dict.scalars.get("max_eval").map(|&x| x as usize);
+        self.tolerance_grad = scalar("tolerance_grad")?;
+        self.tolerance_change = scalar("tolerance_change")?;
+        self.history_size = history_size;
+        self.strong_wolfe = dict.scalar("strong_wolfe")? != 0.;
+        self.state = state;
+        Ok(())
+    }
+}
+
+/// pairs of the history as the rows of a `(history, n)` array
+fn stack_history<T: NdFloat>(rows: &VecDeque<Array1<T>>) -> Array2<T> {
+    let views: Vec<_> = rows.iter().map(|row| row.view()).collect();
+    ndarray::stack(Axis(0), &views).unwrap()
+}
+
+fn max_abs<T: NdFloat>(a: &Array1<T>) -> T {
+    a.iter().fold(T::zero(), |acc, x| acc.max(x.abs()))
+}
+
+/// minimizer of the cubic through two points with their values and derivatives, clamped
+/// to `bounds` or to the interval between the points
+fn cubic_interpolate<T: NdFloat>(
+    (x1, f1, g1): (T, T, T),
+    (x2, f2, g2): (T, T, T),
+    bounds: Option<(T, T)>,
+) -> T {
+    let (xmin_bound, xmax_bound) = bounds.unwrap_or(if x1 <= x2 { (x1, x2) } else { (x2, x1) });
+    let three = T::from(3.).unwrap();
+    let two = T::from(2.).unwrap();
+
+    let d1 = g1 + g2 - three * (f1 - f2) / (x1 - x2);
+    let d2_square = d1 * d1 - g1 * g2;
+    if d2_square >= T::zero() {
+        let d2 = d2_square.sqrt();
+        let min_pos = if x1 <= x2 {
+            x2 - (x2 - x1) * ((g2 + d2 - d1) / (g2 - g1 + two * d2))
+        } else {
+            x1 - (x1 - x2) * ((g1 + d2 - d1) / (g1 - g2 + two * d2))
+        };
+        min_pos.max(xmin_bound).min(xmax_bound)
+    } else {
+        (xmin_bound + xmax_bound) / two
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variable::Variable;
+    use ndarray::array;
+
+    /// `(1 - x)^2 + 100 (y - x^2)^2`, minimal at (1, 1)
+    fn rosenbrock(x: &mut VariableRef<f64>, y: &mut VariableRef<f64>) -> f64 {
+        x.borrow_mut().zero_grad();
+        y.borrow_mut().zero_grad();
+        let one = Variable::new_no_retain_grad(array![1.].into_dyn());
+        let hundred = Variable::new_no_retain_grad(array![100.].into_dyn());
+
+        let mut a = &one - &*x;
+        let mut b = &*y - &(&*x * &*x);
+        let mut loss = (a.powf(2.) + &hundred * &b.powf(2.)).sum();
+        loss.backward();
+        let value = loss.borrow().data[0];
+        value
+    }
+
+    fn minimize(optim: fn(Vec<VariableRef<f64>>) -> LBFGS<f64>, steps: usize) -> (f64, f64) {
+        let x = Variable::new(array![-1.5].into_dyn());
+        let y = Variable::new(array![2.].into_dyn());
+        let mut optim = optim(vec![x.clone(), y.clone()]);
+        let (mut cx, mut cy) = (x.clone(), y.clone());
+        for _ in 0..steps {
+            optim.step(|| rosenbrock(&mut cx, &mut cy));
+        }
+        let point = (x.borrow().data[0], y.borrow().data[0]);
+        point
+    }
+
+    #[test]
+    fn rosenbrock_strong_wolfe() {
+        // the default tolerance stops as soon as the loss moves by less than 1e-9, which
+        // happens a few 1e-6 away from the minimum
+        let (x, y) = minimize(
+            |p| {
+                LBFGS::new(p, 1.)
+                    .with_strong_wolfe(true)
+                    .with_tolerance_change(1e-14)
+            },
+            3,
+        );
+        assert!(
+            (x - 1.).abs() < 1e-6 && (y - 1.).abs() < 1e-6,
+            "{} {}",
+            x,
+            y
+        );
+    }
+
+    #[test]
+    fn rosenbrock_fixed_step() {
+        let (x, y) = minimize(|p| LBFGS::new(p, 0.1).with_max_iter(100), 20);
+        assert!(
+            (x - 1.).abs() < 1e-3 && (y - 1.).abs() < 1e-3,
+            "{} {}",
+            x,
+            y
+        );
+    }
+
+    #[test]
+    fn quadratic_history() {
+        // on `0.5 |p|^2` the first iteration goes straight to the minimum
+        let p = Variable::new(array![0.5, -0.25].into_dyn());
+        let mut optim = LBFGS::new(vec![p.clone()], 1.).with_max_iter(1);
+        let mut param = p.clone();
+        let loss = optim.step(|| {
+            let grad = param.borrow().data.clone();
+            let loss = 0.5 * grad.mapv(|g| g * g).sum();
+            param.borrow_mut().grad = Some(grad);
+            loss
+        });
+        assert_eq!(loss, 0.15625);
+        assert_eq!(p.borrow().data, array![0., 0.].into_dyn());
+        assert_eq!(optim.state.n_iter, 1);
+        assert_eq!(optim.state.func_evals, 1);
+    }
+
+    #[test]
+    fn state_dict_round_trip() {
+        let (x, y) = (
+            Variable::new(array![-1.5].into_dyn()),
+            Variable::new(array![2.].into_dyn()),
+        );
+        let build = |x: &VariableRef<f64>, y: &VariableRef<f64>| {
+            LBFGS::new(vec![x.clone(), y.clone()], 1.)
+                .with_max_iter(3)
+                .with_history_size(2)
+                .with_strong_wolfe(true)
+        };
+        let mut optim = build(&x, &y);
+        let (mut cx, mut cy) = (x.clone(), y.clone());
+        optim.step(|| rosenbrock(&mut cx, &mut cy));
+        optim.step(|| rosenbrock(&mut cx, &mut cy));
+
+        let state = Stateful::state_dict(&optim);
+        let k = optim.state.ro.len();
+        assert!(k > 0);
+        assert_eq!(state.buffers["old_dirs"].shape(), &[k, 2]);
+        let (other_x, other_y) = (
+            Variable::new(x.borrow().data.clone()),
+            Variable::new(y.borrow().data.clone()),
+        );
+        let mut restored = LBFGS::new(vec![other_x.clone(), other_y.clone()], 0.1);
+        restored.load_state_dict(&state).unwrap();
+        assert_eq!(restored.state, optim.state);
+        assert_eq!(Stateful::state_dict(&restored), state);
+
+        let (mut ox, mut oy) = (other_x.clone(), other_y.clone());
+        optim.step(|| rosenbrock(&mut cx, &mut cy));
+        restored.step(|| rosenbrock(&mut ox, &mut oy));
+        assert_eq!(x.borrow().data, other_x.borrow().data);
+        assert_eq!(y.borrow().data, other_y.borrow().data);
+
+        let mut wrong = state.clone();
+        wrong.buffers.insert("d".to_string(), array![1.].into_dyn());
+        assert!(restored.load_state_dict(&wrong).is_err());
+        let mut wrong = state;
+        wrong.scalars.insert("history_size".to_string(), 0.);
+        assert!(restored.load_state_dict(&wrong).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "history_size must be positive")]
+    fn empty_history() {
+        LBFGS::<f64>::new(vec![], 1.).with_history_size(0);
+    }
+
+    #[test]
+    fn cubic() {
+        // f(x) = x^3 - 3x has a local minimum at 1
+        let f = |x: f64| x.powi(3) - 3. * x;
+        let g = |x: f64| 3. * x * x - 3.;
+        let min = cubic_interpolate((0., f(0.), g(0.)), (2., f(2.), g(2.)), None);
+        assert!((min - 1.).abs() < 1e-12);
+        let clamped = cubic_interpolate((0., f(0.), g(0.)), (2., f(2.), g(2.)), Some((1.5, 3.)));
+        assert_eq!(clamped, 1.5);
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use ndarray::NdFloat;
+
+use crate::optim::Stateful;
+
+/// learning rate policy applied once per epoch
+pub trait LRScheduler<T: NdFloat> {
+    /// end the current epoch and set the learning rate of the next one
+    fn step(&mut self, optim: &mut dyn Stateful<T>);
+
+    fn state_dict(&self) -> BTreeMap<String, f64>;
+
+    fn load_state_dict(&mut self, state: &BTreeMap<String, f64>) -> Result<(), String>;
+}
+
+fn get(state: &BTreeMap<String, f64>, key: &str) -> Result<f64, String> {
+    state
+        .get(key)
+        .copied()
+        .ok_or(format!("scheduler state has no {}", key))
+}
+
+/// multiply the initial learning rate by `gamma` every `step_size` epochs
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepLR {
+    pub base_lr: f64,
+    pub step_size: usize,
+    pub gamma: f64,
+    /// number of epochs done
+    pub last_epoch: usize,
+}
+
+impl StepLR {
+    pub fn new<T: NdFloat, O: Stateful<T> + ?Sized>(
+        optim: &O,
+        step_size: usize,
+        gamma: f64,
+    ) -> StepLR {
+        assert!(step_size > 0, "step_size must be positive");
+        StepLR {
+            base_lr: optim.lr().to_f64().unwrap(),
+            step_size,
+            gamma,
+            last_epoch: 0,
+        }
+    }
+
+    pub fn lr(&self) -> f64 {
+        self.base_lr * self.gamma.powi((self.last_epoch / self.step_size) as i32)
+    }
+}
+
+impl<T: NdFloat> LRScheduler<T> for StepLR {
+    fn step(&mut self, optim: &mut dyn Stateful<T>) {
+        self.last_epoch += 1;
+        optim.set_lr(T::from(self.lr()).unwrap());
+    }
+
+    fn state_dict(&self) -> BTreeMap<String, f64> {
+        let mut state = BTreeMap::new();
+        state.insert("base_lr".to_string(), self.base_lr);
+        state.insert("step_size".to_string(), self.step_size as f64);
+        state.insert("gamma".to_string(), self.gamma);
+        state.insert("last_epoch".to_string(), self.last_epoch as f64);
+        state
+    }
+
+    fn load_state_dict(&mut self, state: &BTreeMap<String, f64>) -> Result<(), String> {
+        self.base_lr = get(state, "base_lr")?;
+        self.step_size = get(state, "step_size")? as usize;
+        self.gamma = get(state, "gamma")?;
+        self.last_epoch = get(state, "last_epoch")? as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optim::sgd::SGD;
+    use crate::variable::Variable;
+    use ndarray::Array;
+
+    #[test]
+    fn step_lr() {
+        let param = Variable::new(Array::<f64, _>::zeros(vec![2]));
+        let mut optim = SGD::new(vec![param], 1.).unwrap();
+        let mut scheduler = StepLR::new(&optim, 2, 0.5);
+
+        let mut lrs = vec![];
+        for _ in 0..5 {
+            scheduler.step(&mut optim);
+            lrs.push(optim.lr);
+        }
+        assert_eq!(lrs, vec![1., 0.5, 0.5, 0.25, 0.25]);
+
+        let mut restored = StepLR::new(&optim, 1, 1.);
+        LRScheduler::<f64>::load_state_dict(
+            &mut restored,
+            &LRScheduler::<f64>::state_dict(&scheduler),
+        )
+        .unwrap();
+        assert_eq!(restored, scheduler);
+    }
+}
@@ -1 +1,316 @@
+pub mod adadelta;
+pub mod adagrad;
+pub mod adam;
+pub mod adamax;
+pub mod lbfgs;
+pub mod lr_scheduler;
+pub mod rmsprop;
 pub mod sgd;
+
+pub use adadelta::Adadelta;
+pub use adagrad::Adagrad;
+pub use adam::{Adam, AdamW};
+pub use adamax::Adamax;
+pub use lbfgs::LBFGS;
+pub use rmsprop::RMSprop;
+pub use sgd::SGD;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+use ndarray::{Array, IxDyn, NdFloat};
+
+use crate::module::StateDict;
+use crate::variable::{Variable, VariableRef};
+
+pub trait Optim {
+    fn step(&mut self);
+}
+
+/// optimizer whose learning rate can be changed, by a scheduler for instance, and whose state
+/// can be saved and restored. The state dict defaults to the learning rate alone, enough for
+/// optimizers without per parameter state
+pub trait Stateful<T: NdFloat> {
+    /// learning rate of the next steps
+    fn lr(&self) -> T;
+
+    fn set_lr(&mut self, lr: T);
+
+    /// copy of everything the next steps depend on, hyper-parameters included
+    fn state_dict(&self) -> OptimState<T> {
+        let mut state = OptimState::default();
+        state.insert_scalars(&[("lr", self.lr().to_f64().unwrap())]);
+        state
+    }
+
+    /// restore a state returned by `state_dict` on an optimizer of the same kind built
+    /// over the same parameters
+    fn load_state_dict(&mut self, state: &OptimState<T>) -> Result<(), String> {
+        self.set_lr(T::from(state.scalar("lr")?).unwrap());
+        Ok(())
+    }
+}
+
+/// internal state of an optimizer
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptimState<T: NdFloat> {
+    /// hyper-parameters and counters, like `lr` or `step`
+    pub scalars: BTreeMap<String, f64>,
+    /// per parameter arrays keyed by `<position of the parameter>.<name>`, like
+    /// `0.momentum_buffer`
+    pub buffers: StateDict<T>,
+}
+
+impl<T: NdFloat> Default for OptimState<T> {
+    fn default() -> OptimState<T> {
+        OptimState {
+            scalars: BTreeMap::new(),
+            buffers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: NdFloat> OptimState<T> {
+    pub fn scalar(&self, key: &str) -> Result<f64, String> {
+        self.scalars
+            .get(key)
+            .copied()
+            .ok_or(format!("optimizer state has no {}", key))
+    }
+
+    /// add hyper-parameters, booleans are stored as 0 or 1
+    pub fn insert_scalars(&mut self, scalars: &[(&str, f64)]) {
+        for (key, value) in scalars.iter() {
+            self.scalars.insert(key.to_string(), *value);
+        }
+    }
+}
+
+pub(crate) fn flag(value: bool) -> f64 {
+    if value {
+        1.
+    } else {
+        0.
+    }
+}
+
+/// state of one parameter, created at its first step
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamState<T: NdFloat> {
+    /// number of steps taken by the parameter
+    pub step: usize,
+    /// running averages and other arrays shaped like the parameter, by name
+    pub buffers: BTreeMap<String, Array<T, IxDyn>>,
+}
+
+impl<T: NdFloat> Default for ParamState<T> {
+    fn default() -> ParamState<T> {
+        ParamState {
+            step: 0,
+            buffers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: NdFloat> ParamState<T> {
+    /// remove buffer `name` to update it, filled with `init` the first time. It goes back
+    /// with `put`
+    pub fn take(&mut self, name: &str, init: T, shape: &[usize]) -> Array<T, IxDyn> {
+        self.buffers
+            .remove(name)
+            .unwrap_or_else(|| Array::from_elem(shape, init))
+    }
+
+    pub fn put(&mut self, name: &str, buffer: Array<T, IxDyn>) {
+        self.buffers.insert(name.to_string(), buffer);
+    }
+}
+
+/// per parameter states, keyed by the address of the parameters so that they follow the
+/// parameters and not their position
+pub type ParamStates<T> = HashMap<*const RefCell<Variable<T>>, ParamState<T>>;
+
+/// copy `states` into `state`, as a `<i>.step` scalar and `<i>.<name>` buffers where `i`
+/// is the position of the parameter in `params`
+pub fn save_param_states<T: NdFloat>(
+    params: &[VariableRef<T>],
+    states: &ParamStates<T>,
+    state: &mut OptimState<T>,
+) {
+    for (i, p) in params.iter().enumerate() {
+        if let Some(s) = states.get(&p.as_ptr()) {
+            state.scalars.insert(format!("{}.step", i), s.step as f64);
+            for (name, array) in s.buffers.iter() {
+                state
+                    .buffers
+                    .insert(format!("{}.{}", i, name), array.clone());
+            }
+        }
+    }
+}
+
+/// inverse of `save_param_states`, the buffers must have the shape of their parameter
+pub fn load_param_states<T: NdFloat>(
+    params: &[VariableRef<T>],
+    state: &OptimState<T>,
+) -> Result<ParamStates<T>, String> {
+    let mut by_position: BTreeMap<usize, ParamState<T>> = BTreeMap::new();
+    for (key, &step) in state.scalars.iter() {
+        if let Some(i) = key.strip_suffix(".step").and_then(|i| i.parse().ok()) {
+            let s = ParamState {
+                step: step as usize,
+                buffers: BTreeMap::new(),
+            };
+            by_position.insert(i, s);
+        }
+    }
+    for (key, array) in state.buffers.iter() {
+        let (i, name) = key
+            .split_once('.')
+            .and_then(|(i, name)| Some((i.parse::<usize>().ok()?, name)))
+            .ok_or(format!("unexpected buffer {} in optimizer state", key))?;
+        let s = by_position
+            .get_mut(&i)
+            .ok_or(format!("optimizer state has no {}.step", i))?;
+        s.buffers.insert(name.to_string(), array.clone());
+    }
+
+    let mut states = HashMap::new();
+    for (i, s) in by_position {
+        let param = params.get(i).ok_or(format!(
+            "optimizer state for parameter {} of {}",
+            i,
+            params.len()
+        ))?;
+        let shape = param.borrow().data.shape().to_vec();
+        for (name, array) in s.buffers.iter() {
+            if array.shape() != shape.as_slice() {
+                return Err(format!(
+                    "{}.{} has shape {:?} but the parameter has shape {:?}",
+                    i,
+                    name,
+                    array.shape(),
+                    shape
+                ));
+            }
+        }
+        states.insert(param.as_ptr(), s);
+    }
+    Ok(states)
+}
+
+/// gradient of the parameter plus the L2 penalty `weight_decay * data`, `None` when the
+/// parameter has no gradient
+pub(crate) fn decayed_grad<T: NdFloat>(
+    param: &Variable<T>,
+    weight_decay: T,
+) -> Option<Array<T, IxDyn>> {
+    let grad = param.get_grad().ok()?;
+    if weight_decay == T::zero() {
+        Some(grad)
+    } else {
+        Some(grad + &param.data * weight_decay)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use ndarray::Array1;
+
+    pub fn assert_close(a: &Array<f64, IxDyn>, b: &Array1<f64>) {
+        assert!(
+            a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-12),
+            "{} != {}",
+            a,
+            b
+        );
+    }
+
+    /// one step on `0.5 * |p|^2`, the gradient is the parameter itself
+    pub fn descend(param: &mut VariableRef<f64>, optim: &mut dyn Optim) {
+        let grad = param.borrow().data.clone();
+        param.borrow_mut().grad = Some(grad);
+        optim.step();
+    }
+
+    /// parameter after each step, starting from [1, -2]
+    pub fn trajectory<O: Optim>(
+        build: fn(Vec<VariableRef<f64>>) -> O,
+        steps: usize,
+    ) -> Vec<Array<f64, IxDyn>> {
+        let mut param = Variable::new(ndarray::array![1., -2.].into_dyn());
+        let mut optim = build(vec![param.clone()]);
+        (0..steps)
+            .map(|_| {
+                descend(&mut param, &mut optim);
+                param.borrow().data.clone()
+            })
+            .collect()
+    }
+
+    /// take a few steps, restore the state on a copy of the parameter and check that both
+    /// keep going the same way
+    pub fn check_state_dict<O: Optim + Stateful<f64>>(build: fn(Vec<VariableRef<f64>>) -> O) {
+        let mut param = Variable::new(ndarray::array![1., -2.].into_dyn());
+        let mut optim = build(vec![param.clone()]);
+        descend(&mut param, &mut optim);
+        descend(&mut param, &mut optim);
+
+        let state = optim.state_dict();
+        let mut other = Variable::new(param.borrow().data.clone());
+        let mut restored = build(vec![other.clone()]);
+        restored.set_lr(123.);
+        restored.load_state_dict(&state).unwrap();
+        assert_eq!(restored.state_dict(), state);
+
+        descend(&mut param, &mut optim);
+        descend(&mut other, &mut restored);
+        assert_eq!(param.borrow().data, other.borrow().data);
+
+        for (key, array) in state.buffers.iter() {
+            let mut wrong = state.clone();
+            wrong
+                .buffers
+                .insert(key.clone(), Array::zeros(array.len() + 1).into_dyn());
+            assert!(restored.load_state_dict(&wrong).is_err());
+        }
+        let mut wrong = state;
+        wrong
+            .buffers
+            .insert("1.unknown".to_string(), Array::zeros(2).into_dyn());
+        assert!(restored.load_state_dict(&wrong).is_err());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// optimizer relying on the default state dict
+    struct Constant {
+        lr: f64,
+    }
+
+    impl Stateful<f64> for Constant {
+        fn lr(&self) -> f64 {
+            self.lr
+        }
+
+        fn set_lr(&mut self, lr: f64) {
+            self.lr = lr;
+        }
+    }
+
+    #[test]
+    fn default_state_dict() {
+        let state = Constant { lr: 0.1 }.state_dict();
+        assert_eq!(state.scalar("lr"), Ok(0.1));
+        assert!(state.buffers.is_empty());
+
+        let mut other = Constant { lr: 1. };
+        other.load_state_dict(&state).unwrap();
+        assert_eq!(other.lr, 0.1);
+        assert!(other.load_state_dict(&OptimState::default()).is_err());
+    }
+}
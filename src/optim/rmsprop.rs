@@ -0,0 +1,193 @@
+use ndarray::{NdFloat, Zip};
+
+use crate::optim::{
+    decayed_grad, flag, load_param_states, save_param_states, Optim, OptimState, ParamStates,
+    Stateful,
+};
+use crate::variable::VariableRef;
+
+/// RMSprop, with the semantics of `torch.optim.RMSprop`
+pub struct RMSprop<T: NdFloat> {
+    pub params: Vec<VariableRef<T>>,
+    pub lr: T,
+    /// decay rate of the running average of the squared gradient
+    pub alpha: T,
+    pub eps: T,
+    pub weight_decay: T,
+    pub momentum: T,
+    /// normalize by an estimate of the variance of the gradient instead of its second moment
+    pub centered: bool,
+    /// `square_avg`, running average of the squared gradient, `grad_avg` when centered and
+    /// `momentum_buffer` with momentum
+    pub state: ParamStates<T>,
+}
+
+impl<T: NdFloat> RMSprop<T> {
+    pub fn new(params: Vec<VariableRef<T>>, lr: T) -> RMSprop<T> {
+        RMSprop {
+            params,
+            lr,
+            alpha: T::from(0.99).unwrap(),
+            eps: T::from(1e-8).unwrap(),
+            weight_decay: T::zero(),
+            momentum: T::zero(),
+            centered: false,
+            state: ParamStates::new(),
+        }
+    }
+
+    pub fn with_alpha(mut self, alpha: T) -> RMSprop<T> {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn with_eps(mut self, eps: T) -> RMSprop<T> {
+        self.eps = eps;
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: T) -> RMSprop<T> {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    pub fn with_momentum(mut self, momentum: T) -> RMSprop<T> {
+        self.momentum = momentum;
+        self
+    }
+
+    pub fn with_centered(mut self, centered: bool) -> RMSprop<T> {
+        self.centered = centered;
+        self
+    }
+}
+
+impl<T: NdFloat> Optim for RMSprop<T> {
+    fn step(&mut self) {
+        let (alpha, eps, momentum) = (self.alpha, self.eps, self.momentum);
+        for p in self.params.iter_mut() {
+            let key = p.as_ptr();
+            let mut param_var = p.borrow_mut();
+            let grad = match decayed_grad(&param_var, self.weight_decay) {
+                Some(grad) => grad,
+                None => continue,
+            };
+            let shape = param_var.data.shape().to_vec();
+            let s = self.state.entry(key).or_default();
+            s.step += 1;
+
+            let mut square_avg = s.take("square_avg", T::zero(), &shape);
+            Zip::from(&mut square_avg)
+                .and(&grad)
+                .for_each(|v, &g| *v = alpha * *v + (T::one() - alpha) * g * g);
+            let mut avg = square_avg.clone();
+            s.put("square_avg", square_avg);
+
+            if self.centered {
+                let mut grad_avg = s.take("grad_avg", T::zero(), &shape);
+                Zip::from(&mut grad_avg)
+                    .and(&grad)
+                    .for_each(|m, &g| *m = alpha * *m + (T::one() - alpha) * g);
+                Zip::from(&mut avg)
+                    .and(&grad_avg)
+                    .for_each(|v, &m| *v -= m * m);
+                s.put("grad_avg", grad_avg);
+            }
+            avg.mapv_inplace(|v| v.sqrt() + eps);
+
+            let update = if momentum > T::zero() {
+                let mut buf = s.take("momentum_buffer", T::zero(), &shape);
+                Zip::from(&mut buf)
+                    .and(&grad)
+                    .and(&avg)
+                    .for_each(|b, &g, &a| *b = momentum * *b + g / a);
+                let update = buf.clone();
+                s.put("momentum_buffer", buf);
+                update
+            } else {
+                grad / avg
+            };
+            param_var.data = param_var.data.clone() - update * self.lr;
+        }
+    }
+}
+
+impl<T: NdFloat> Stateful<T> for RMSprop<T> {
+    fn lr(&self) -> T {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: T) {
+        self.lr = lr;
+    }
+
+    fn state_dict(&self) -> OptimState<T> {
+        let mut state = OptimState::default();
+        state.insert_scalars(&[
+            ("lr", self.lr.to_f64().unwrap()),
+            ("alpha", self.alpha.to_f64().unwrap()),
+            ("eps", self.eps.to_f64().unwrap()),
+            ("weight_decay", self.weight_decay.to_f64().unwrap()),
+            ("momentum", self.momentum.to_f64().unwrap()),
+            ("centered", flag(self.centered)),
+        ]);
+        save_param_states(&self.params, &self.state, &mut state);
+        state
+    }
+
+    fn load_state_dict(&mut self, state: &OptimState<T>) -> Result<(), String> {
+        let param_states = load_param_states(&self.params, state)?;
+        self.lr = T::from(state.scalar("lr")?).unwrap();
+        self.alpha = T::from(state.scalar("alpha")?).unwrap();
+        self.eps = T::from(state.scalar("eps")?).unwrap();
+        self.weight_decay = T::from(state.scalar("weight_decay")?).unwrap();
+        self.momentum = T::from(state.scalar("momentum")?).unwrap();
+        self.centered = state.scalar("centered")? != 0.;
+        self.state = param_states;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optim::testing::{assert_close, check_state_dict, trajectory};
+    use ndarray::array;
+
+    #[test]
+    fn rmsprop() {
+        let steps = trajectory(|p| RMSprop::new(p, 0.01), 3);
+        assert_close(&steps[0], &array![0.9000000099999991, -1.9000000049999999]);
+        assert_close(&steps[1], &array![0.8329179752650593, -1.8309433328172848]);
+        assert_close(&steps[2], &array![0.7799822819823541, -1.775349450099218]);
+    }
+
+    #[test]
+    fn weight_decay() {
+        let steps = trajectory(|p| RMSprop::new(p, 0.01).with_weight_decay(0.5), 3);
+        assert_close(&steps[0], &array![0.9000000066666662, -1.9000000033333333]);
+        assert_close(&steps[1], &array![0.8329179704017086, -1.8309433303456708]);
+        assert_close(&steps[2], &array![0.7799822761565415, -1.7753494471007127]);
+    }
+
+    #[test]
+    fn centered_momentum() {
+        let steps = trajectory(
+            |p| {
+                RMSprop::new(p, 0.1)
+                    .with_alpha(0.9)
+                    .with_momentum(0.5)
+                    .with_centered(true)
+            },
+            3,
+        );
+        assert_close(&steps[0], &array![0.6666666777777774, -1.666666672222222]);
+        assert_close(&steps[1], &array![0.2989010279491235, -1.2683397103802676]);
+        assert_close(&steps[2], &array![0.02084087865461448, -0.8969423987424936]);
+    }
+
+    #[test]
+    fn state_dict_round_trip() {
+        check_state_dict(|p| RMSprop::new(p, 0.01).with_momentum(0.9).with_centered(true));
+    }
+}
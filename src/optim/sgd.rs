@@ -2,13 +2,23 @@ use crate::variable::VariableRef;
 
 use ndarray::NdFloat;
 
-pub trait Optim {
-    fn step(&mut self);
-}
+use crate::optim::{flag, load_param_states, save_param_states, ParamStates};
+pub use crate::optim::{Optim, OptimState, Stateful};
 
+/// stochastic gradient descent, with the semantics of `torch.optim.SGD`
 pub struct SGD<T: NdFloat> {
     pub params: Vec<VariableRef<T>>,
     pub lr: T,
+    pub momentum: T,
+    /// fraction of the gradient left out of the momentum buffer
+    pub dampening: T,
+    /// L2 penalty added to the gradient
+    pub weight_decay: T,
+    pub nesterov: bool,
+    /// follow the gradient up instead of down
+    pub maximize: bool,
+    /// `momentum_buffer`, created at the first step of a parameter with momentum
+    pub state: ParamStates<T>,
 }
 
 impl<T: NdFloat> SGD<T> {
@@ -23,16 +33,212 @@ impl<T: NdFloat> SGD<T> {
         if no_retain {
             Err("One of the parameters does not retrain his grad no optim possible for it")
         } else {
-            Ok(SGD { params, lr })
+            Ok(SGD {
+                params,
+                lr,
+                momentum: T::zero(),
+                dampening: T::zero(),
+                weight_decay: T::zero(),
+                nesterov: false,
+                maximize: false,
+                state: ParamStates::new(),
+            })
         }
     }
+
+    pub fn with_momentum(mut self, momentum: T) -> SGD<T> {
+        self.momentum = momentum;
+        self
+    }
+
+    pub fn with_dampening(mut self, dampening: T) -> SGD<T> {
+        self.dampening = dampening;
+        self
+    }
+
+    pub fn with_weight_decay(mut self, weight_decay: T) -> SGD<T> {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    pub fn with_nesterov(mut self, nesterov: bool) -> SGD<T> {
+        self.nesterov = nesterov;
+        self
+    }
+
+    pub fn with_maximize(mut self, maximize: bool) -> SGD<T> {
+        self.maximize = maximize;
+        self
+    }
 }
 
 impl<T: NdFloat> Optim for SGD<T> {
     fn step(&mut self) {
+        assert!(
+            !self.nesterov || (self.momentum > T::zero() && self.dampening == T::zero()),
+            "nesterov momentum requires a momentum and zero dampening"
+        );
+
         for p in self.params.iter_mut() {
+            let key = p.as_ptr();
             let mut param_var = p.borrow_mut();
-            param_var.data = param_var.data.clone() - param_var.get_grad().unwrap() * self.lr;
+            let mut grad = match param_var.get_grad() {
+                Ok(grad) => grad,
+                Err(_) => continue,
+            };
+            if self.maximize {
+                grad = -grad;
+            }
+            if self.weight_decay != T::zero() {
+                grad = grad + &param_var.data * self.weight_decay;
+            }
+
+            if self.momentum != T::zero() {
+                let s = self.state.entry(key).or_default();
+                s.step += 1;
+                let buf = match s.buffers.remove("momentum_buffer") {
+                    Some(buf) => buf * self.momentum + &grad * (T::one() - self.dampening),
+                    None => grad.clone(),
+                };
+                if self.nesterov {
+                    grad = grad + &buf * self.momentum;
+                } else {
+                    grad = buf.clone();
+                }
+                s.put("momentum_buffer", buf);
+            }
+
+            param_var.data = param_var.data.clone() - grad * self.lr;
         }
     }
 }
+
+impl<T: NdFloat> Stateful<T> for SGD<T> {
+    fn lr(&self) -> T {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: T) {
+        self.lr = lr;
+    }
+
+    fn state_dict(&self) -> OptimState<T> {
+        let mut state = OptimState::default();
+        state.insert_scalars(&[
+            ("lr", self.lr.to_f64().unwrap()),
+            ("momentum", self.momentum.to_f64().unwrap()),
+            ("dampening", self.dampening.to_f64().unwrap()),
+            ("weight_decay", self.weight_decay.to_f64().unwrap()),
+            ("nesterov", flag(self.nesterov)),
+            ("maximize", flag(self.maximize)),
+        ]);
+        save_param_states(&self.params, &self.state, &mut state);
+        state
+    }
+
+    fn load_state_dict(&mut self, state: &OptimState<T>) -> Result<(), String> {
+        let param_states = load_param_states(&self.params, state)?;
+        self.lr = T::from(state.scalar("lr")?).unwrap();
+        self.momentum = T::from(state.scalar("momentum")?).unwrap();
+        self.dampening = T::from(state.scalar("dampening")?).unwrap();
+        self.weight_decay = T::from(state.scalar("weight_decay")?).unwrap();
+        self.nesterov = state.scalar("nesterov")? != 0.;
+        self.maximize = state.scalar("maximize")? != 0.;
+        self.state = param_states;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optim::testing::{assert_close, check_state_dict, trajectory};
+    use ndarray::array;
+
+    #[test]
+    fn plain() {
+        let steps = trajectory(|p| SGD::new(p, 0.1).unwrap(), 2);
+        assert_close(&steps[0], &array![0.9, -1.8]);
+        assert_close(&steps[1], &array![0.81, -1.62]);
+    }
+
+    #[test]
+    fn momentum() {
+        // buffers: p0, then 0.9 p0 + 0.9 p0 = 1.8 p0, then 0.9 * 1.8 p0 + 0.72 p0 = 2.34 p0
+        let steps = trajectory(|p| SGD::new(p, 0.1).unwrap().with_momentum(0.9), 3);
+        assert_close(&steps[0], &array![0.9, -1.8]);
+        assert_close(&steps[1], &array![0.72, -1.44]);
+        assert_close(&steps[2], &array![0.486, -0.972]);
+    }
+
+    #[test]
+    fn dampening() {
+        // buffers: p0 (no dampening on the first step), then 0.5 p0 + 0.5 * 0.9 p0
+        let steps = trajectory(
+            |p| {
+                SGD::new(p, 0.1)
+                    .unwrap()
+                    .with_momentum(0.5)
+                    .with_dampening(0.5)
+            },
+            2,
+        );
+        assert_close(&steps[0], &array![0.9, -1.8]);
+        assert_close(&steps[1], &array![0.805, -1.61]);
+    }
+
+    #[test]
+    fn nesterov() {
+        // updates: p0 + 0.9 p0 = 1.9 p0, then 0.81 p0 + 0.9 * 1.71 p0 = 2.349 p0
+        let steps = trajectory(
+            |p| {
+                SGD::new(p, 0.1)
+                    .unwrap()
+                    .with_momentum(0.9)
+                    .with_nesterov(true)
+            },
+            2,
+        );
+        assert_close(&steps[0], &array![0.81, -1.62]);
+        assert_close(&steps[1], &array![0.5751, -1.1502]);
+    }
+
+    #[test]
+    fn weight_decay() {
+        // g + 0.5 p = 1.5 p
+        let steps = trajectory(|p| SGD::new(p, 0.1).unwrap().with_weight_decay(0.5), 2);
+        assert_close(&steps[0], &array![0.85, -1.7]);
+        assert_close(&steps[1], &array![0.7225, -1.445]);
+    }
+
+    #[test]
+    fn maximize() {
+        let steps = trajectory(
+            |p| {
+                SGD::new(p, 0.1)
+                    .unwrap()
+                    .with_maximize(true)
+                    .with_momentum(0.9)
+            },
+            2,
+        );
+        assert_close(&steps[0], &array![1.1, -2.2]);
+        assert_close(&steps[1], &array![1.3, -2.6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "nesterov")]
+    fn nesterov_without_momentum() {
+        trajectory(|p| SGD::new(p, 0.1).unwrap().with_nesterov(true), 1);
+    }
+
+    #[test]
+    fn state_dict_round_trip() {
+        check_state_dict(|p| {
+            SGD::new(p, 0.1)
+                .unwrap()
+                .with_momentum(0.9)
+                .with_nesterov(true)
+        });
+    }
+}
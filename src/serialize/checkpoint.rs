@@ -0,0 +1,357 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use ndarray::NdFloat;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::module::{Module, StateDict};
+use crate::optim::lr_scheduler::LRScheduler;
+use crate::optim::{OptimState, Stateful};
+use crate::serialize::safetensors::{deserialize, read_metadata, serialize};
+
+// a checkpoint is a safetensors file: the arrays of the module and of the optimizer are
+// tensors prefixed with `model.` and `optimizer.`, the scalars are metadata strings. The
+// `Display` of floats is the shortest decimal reading back to the same value, so the
+// scalars round trip exactly
+
+const FORMAT: &str = "rusty_grad.checkpoint";
+
+/// generator of the epoch `epoch` of a run seeded with `seed`. Drawing all the randomness
+/// of an epoch from it, shuffling and dropout masks included, makes a run resumed from a
+/// checkpoint identical to an uninterrupted one
+pub fn epoch_rng(seed: u64, epoch: usize) -> StdRng {
+    let mut key = [0; 32];
+    key[..8].copy_from_slice(&seed.to_le_bytes());
+    key[8..16].copy_from_slice(&(epoch as u64).to_le_bytes());
+    StdRng::from_seed(key)
+}
+
+/// state of a training run between two epochs, see `Checkpoint::new`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint<T: NdFloat> {
+    pub model: StateDict<T>,
+    pub optimizer: OptimState<T>,
+    /// `None` for runs without a learning rate scheduler
+    pub scheduler: Option<BTreeMap<String, f64>>,
+    /// number of epochs done, the one to run next
+    pub epoch: usize,
+    pub seed: u64,
+}
+
+impl<T: NdFloat> Checkpoint<T> {
+    /// checkpoint taken after `epoch` full epochs, it can only resume a run at that epoch
+    /// boundary. The states of random generators are not saved, only the `seed` of the run:
+    /// the resumed run matches an uninterrupted one only if the training loop reseeds every
+    /// generator it uses, like the `rng` of the `Dropout` layers or the one shuffling the
+    /// data, from `rng` (or `epoch_rng`) at the start of each epoch
+    pub fn new<M: Module<T> + ?Sized, O: Stateful<T> + ?Sized>(
+        module: &M,
+        optim: &O,
+        epoch: usize,
+        seed: u64,
+    ) -> Checkpoint<T> {
+        Checkpoint {
+            model: module.state_dict(),
+            optimizer: optim.state_dict(),
+            scheduler: None,
+            epoch,
+            seed,
+        }
+    }
+
+    pub fn with_scheduler<S: LRScheduler<T> + ?Sized>(mut self, scheduler: &S) -> Checkpoint<T> {
+        self.scheduler = Some(scheduler.state_dict());
+        self
+    }
+
+    /// generator of the next epoch, see `epoch_rng`
+    pub fn rng(&self) -> StdRng {
+        epoch_rng(self.seed, self.epoch)
+    }
+
+    /// load the state of the module and of the optimizer. The state dict must match the
+    /// module exactly
+    pub fn restore<M: Module<T> + ?Sized, O: Stateful<T> + ?Sized>(
+        &self,
+        module: &mut M,
+        optim: &mut O,
+    ) -> Result<(), String> {
+        let report = module.load_state_dict(&self.model);
+        if !report.is_ok() {
+            return Err(format!("checkpoint does not match the module, {}", report));
+        }
+        optim.load_state_dict(&self.optimizer)
+    }
+
+    pub fn restore_scheduler<S: LRScheduler<T> + ?Sized>(
+        &self,
+        scheduler: &mut S,
+    ) -> Result<(), String> {
+        match &self.scheduler {
+            Some(state) => scheduler.load_state_dict(state),
+            None => Err("checkpoint has no scheduler state".to_string()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut tensors = StateDict::new();
+        for (key, array) in self.model.iter() {
+            tensors.insert(format!("model.{}", key), array.clone());
+        }
+        for (key, array) in self.optimizer.buffers.iter() {
+            tensors.insert(format!("optimizer.{}", key), array.clone());
+        }
+
+        let mut metadata = BTreeMap::new();
+        metadata.insert("format".to_string(), FORMAT.to_string());
+        metadata.insert("epoch".to_string(), self.epoch.to_string());
+        metadata.insert("seed".to_string(), self.seed.to_string());
+        for (key, value) in self.optimizer.scalars.iter() {
+            metadata.insert(format!("optimizer.{}", key), value.to_string());
+        }
+        for (key, value) in self.scheduler.iter().flatten() {
+            metadata.insert(format!("scheduler.{}", key), value.to_string());
+        }
+        if self.scheduler.is_some() {
+            metadata.insert("scheduler".to_string(), "true".to_string());
+        }
+        serialize(&tensors, Some(&metadata))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Checkpoint<T>, String> {
+        let metadata = read_metadata(bytes)?;
+        if metadata.get("format").map(String::as_str) != Some(FORMAT) {
+            return Err("not a checkpoint file".to_string());
+        }
+        let get = |key: &str| {
+            metadata
+                .get(key)
+                .ok_or(format!("checkpoint has no {}", key))
+        };
+        let parse_err = |key: &str| format!("invalid {} in checkpoint", key);
+
+        let mut checkpoint = Checkpoint {
+            model: StateDict::new(),
+            optimizer: OptimState::default(),
+            scheduler: None,
+            epoch: get("epoch")?.parse().map_err(|_| parse_err("epoch"))?,
+            seed: get("seed")?.parse().map_err(|_| parse_err("seed"))?,
+        };
+        for (key, array) in deserialize::<T>(bytes)? {
+            if let Some(key) = key.strip_prefix("model.") {
+                checkpoint.model.insert(key.to_string(), array);
+            } else if let Some(key) = key.strip_prefix("optimizer.") {
+                checkpoint.optimizer.buffers.insert(key.to_string(), array);
+            } else {
+                return Err(format!("unexpected tensor {} in checkpoint", key));
+            }
+        }
+        if metadata.contains_key("scheduler") {
+            checkpoint.scheduler = Some(BTreeMap::new());
+        }
+        for (key, value) in metadata.iter() {
+            let parse = || value.parse::<f64>().map_err(|_| parse_err(key));
+            if let Some(name) = key.strip_prefix("optimizer.") {
+                checkpoint
+                    .optimizer
+                    .scalars
+                    .insert(name.to_string(), parse()?);
+            } else if let Some(name) = key.strip_prefix("scheduler.") {
+                let scheduler = checkpoint
+                    .scheduler
+                    .as_mut()
+                    .ok_or(format!("unexpected {} in checkpoint", key))?;
+                scheduler.insert(name.to_string(), parse()?);
+            }
+        }
+        Ok(checkpoint)
+    }
+
+    /// write the checkpoint next to `path` then move it there, so that an interruption
+    /// while saving leaves the previous checkpoint intact
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, self.to_bytes()).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, path).map_err(|e| e.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Checkpoint<T>, String> {
+        Checkpoint::from_bytes(&fs::read(path).map_err(|e| e.to_string())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grad_fn::functional::loss::mse_loss;
+    use crate::module::{prefixed, Named};
+    use crate::nn::dropout::Dropout;
+    use crate::nn::linear::Linear;
+    use crate::optim::lr_scheduler::StepLR;
+    use crate::optim::sgd::{Optim, SGD};
+    use crate::variable::{Variable, VariableRef};
+    use ndarray::{Array, Axis};
+    use rand::seq::SliceRandom;
+    use rand::Rng;
+
+    struct Net {
+        linear1: Linear<f32>,
+        dropout: Dropout,
+        linear2: Linear<f32>,
+    }
+
+    impl Net {
+        fn new() -> Net {
+            Net {
+                linear1: Linear::new(2, 8),
+                dropout: Dropout::new(0.2),
+                linear2: Linear::new(8, 1),
+            }
+        }
+    }
+
+    impl Module<f32> for Net {
+        fn params(&self) -> Vec<VariableRef<f32>> {
+            let mut params = self.linear1.params();
+            params.extend(self.linear2.params());
+            params
+        }
+
+        fn named_parameters(&self) -> Named<f32> {
+            let mut named = prefixed("linear1", self.linear1.named_parameters());
+            named.extend(prefixed("linear2", self.linear2.named_parameters()));
+            named
+        }
+
+        fn f(&mut self, input: &VariableRef<f32>) -> VariableRef<f32> {
+            let hidden = self.linear1.f(input).relu();
+            let hidden = self.dropout.f(&hidden);
+            self.linear2.f(&hidden)
+        }
+    }
+
+    /// run the epochs `epochs` of a training with shuffled mini-batches and dropout, every
+    /// random draw coming from the generator of the epoch
+    fn train(
+        net: &mut Net,
+        optim: &mut SGD<f32>,
+        scheduler: &mut StepLR,
+        seed: u64,
+        epochs: std::ops::Range<usize>,
+    ) {
+        let x = Array::from_shape_fn((2, 16), |(i, j)| ((i * 16 + j) as f32 * 0.37).sin());
+        let y = x
+            .map_axis(Axis(0), |col| col[0] * col[1])
+            .insert_axis(Axis(0));
+
+        for epoch in epochs {
+            // the contract of `Checkpoint`: every generator is reseeded at each epoch
+            let mut rng = epoch_rng(seed, epoch);
+            net.dropout.rng = StdRng::seed_from_u64(rng.gen());
+            let mut order: Vec<usize> = (0..16).collect();
+            order.shuffle(&mut rng);
+
+            for batch in order.chunks(4) {
+                net.zero_grad();
+                let input = Variable::new(x.select(Axis(1), batch).into_dyn());
+                let target = Variable::new_no_retain_grad(y.select(Axis(1), batch).into_dyn());
+                let mut loss = mse_loss(&net.f(&input), &target);
+                loss.backward();
+                optim.step();
+            }
+            scheduler.step(optim);
+        }
+    }
+
+    fn setup(init: &StateDict<f32>) -> (Net, SGD<f32>, StepLR) {
+        let mut net = Net::new();
+        assert!(net.load_state_dict(init).is_ok());
+        let optim = SGD::new(net.params(), 0.1).unwrap();
+        let scheduler = StepLR::new(&optim, 2, 0.5);
+        (net, optim, scheduler)
+    }
+
+    #[test]
+    fn resume_is_identical() {
+        let init = Net::new().state_dict();
+        let seed = 17;
+
+        let (mut net, mut optim, mut scheduler) = setup(&init);
+        train(&mut net, &mut optim, &mut scheduler, seed, 0..6);
+
+        // interrupted after 3 epochs
+        let (mut first, mut first_optim, mut first_scheduler) = setup(&init);
+        train(
+            &mut first,
+            &mut first_optim,
+            &mut first_scheduler,
+            seed,
+            0..3,
+        );
+        let path =
+            std::env::temp_dir().join(format!("rusty_grad_resume_{}.ckpt", std::process::id()));
+        Checkpoint::new(&first, &first_optim, 3, seed)
+            .with_scheduler(&first_scheduler)
+            .save(&path)
+            .unwrap();
+
+        // resumed by a new process, from a different initialization
+        let checkpoint = Checkpoint::<f32>::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut resumed = Net::new();
+        let mut resumed_optim = SGD::new(resumed.params(), 1.).unwrap();
+        let mut resumed_scheduler = StepLR::new(&resumed_optim, 1, 1.);
+        checkpoint
+            .restore(&mut resumed, &mut resumed_optim)
+            .unwrap();
+        checkpoint
+            .restore_scheduler(&mut resumed_scheduler)
+            .unwrap();
+        train(
+            &mut resumed,
+            &mut resumed_optim,
+            &mut resumed_scheduler,
+            checkpoint.seed,
+            checkpoint.epoch..6,
+        );
+
+        assert_eq!(resumed.state_dict(), net.state_dict());
+        assert_ne!(resumed.state_dict(), init);
+        assert_eq!(resumed_optim.lr, optim.lr);
+        assert_eq!(resumed_scheduler, scheduler);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let net = Net::new();
+        let mut optim = SGD::new(net.params(), 0.1).unwrap();
+        optim.lr = 0.1 + f32::EPSILON;
+        let checkpoint = Checkpoint::new(&net, &optim, 4, u64::MAX);
+
+        let restored = Checkpoint::<f32>::from_bytes(&checkpoint.to_bytes()).unwrap();
+        assert_eq!(restored, checkpoint);
+        assert_eq!(restored.optimizer.scalar("lr").unwrap() as f32, optim.lr);
+        assert!(restored
+            .restore_scheduler(&mut StepLR::new(&optim, 1, 1.))
+            .is_err());
+    }
+
+    #[test]
+    fn restore_errors() {
+        let net = Net::new();
+        let mut optim = SGD::new(net.params(), 0.1).unwrap();
+        let checkpoint = Checkpoint::new(&net, &optim, 1, 0);
+
+        let mut other = Linear::new(2, 8);
+        assert!(checkpoint.restore(&mut other, &mut optim).is_err());
+
+        let mut bytes = crate::serialize::safetensors::serialize(&net.state_dict(), None);
+        assert!(Checkpoint::<f32>::from_bytes(&bytes).is_err());
+        bytes.truncate(4);
+        assert!(Checkpoint::<f32>::from_bytes(&bytes).is_err());
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::BTreeMap;
+
+/// the subset of JSON found in the headers of the serialization formats. Objects keep the
+/// order of their keys
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.chars.len() {
+            return Err(format!("unexpected trailing data at {}", parser.pos));
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// non negative integer
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) if *n >= 0. && n.fract() == 0. => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize_vec(&self) -> Option<Vec<usize>> {
+        match self {
+            Json::Array(items) => items.iter().map(Json::as_usize).collect(),
+            _ => None,
+        }
+    }
+
+    /// compact serialization, without spaces
+    pub fn dump(&self) -> String {
+        match self {
+            Json::Null => "null".to_string(),
+            Json::Bool(b) => b.to_string(),
+            Json::Number(n) => {
+                if n.fract() == 0. && n.abs() < 1e15 {
+                    format!("{}", *n as i64)
+                } else {
+                    format!("{}", n)
+                }
+            }
+            Json::String(s) => quote(s),
+            Json::Array(items) => format!(
+                "[{}]",
+                items.iter().map(Json::dump).collect::<Vec<_>>().join(",")
+            ),
+            Json::Object(entries) => format!(
+                "{{{}}}",
+                entries
+                    .iter()
+                    .map(|(k, v)| format!("{}:{}", quote(k), v.dump()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+impl From<&BTreeMap<String, String>> for Json {
+    fn from(map: &BTreeMap<String, String>) -> Json {
+        Json::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), Json::String(v.clone())))
+                .collect(),
+        )
+    }
+}
+
+impl From<&[usize]> for Json {
+    fn from(values: &[usize]) -> Json {
+        Json::Array(values.iter().map(|&v| Json::Number(v as f64)).collect())
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// nesting of arrays and objects allowed, deeper input is rejected instead of overflowing the
+/// stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at {}", c, self.pos))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.pos + word.len();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            Ok(value)
+        } else {
+            Err(format!("invalid literal at {}", self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some('{') => self.nested(Parser::object),
+            Some('[') => self.nested(Parser::array),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(format!("unexpected character at {}", self.pos)),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Parser) -> Result<Json, String>) -> Result<Json, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("nesting deeper than {} at {}", MAX_DEPTH, self.pos));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut entries = vec![];
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(entries));
+                }
+                _ => return Err(format!("expected ',' or '}}' at {}", self.pos)),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = vec![];
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(format!("expected ',' or ']' at {}", self.pos)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.chars.get(self.pos) != Some(&'"') {
+            return Err(format!("expected a string at {}", self.pos));
+        }
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = *self.chars.get(self.pos).ok_or("unterminated string")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escaped = *self.chars.get(self.pos).ok_or("unterminated string")?;
+                    self.pos += 1;
+                    out.push(match escaped {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape()?,
+                        _ => return Err(format!("invalid escape at {}", self.pos)),
+                    });
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        if self.pos + 4 > self.chars.len() {
+            return Err("truncated unicode escape".to_string());
+        }
+        let hex: String = self.chars[self.pos..self.pos + 4].iter().collect();
+        self.pos += 4;
+        u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid unicode escape {}", hex))
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let mut code = self.hex4()?;
+        // surrogate pair
+        if (0xd800..0xdc00).contains(&code) && self.chars[self.pos..].starts_with(&['\\', 'u']) {
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(format!("invalid low surrogate {:x}", low));
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+        char::from_u32(code).ok_or_else(|| format!("invalid code point {}", code))
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self.pos < self.chars.len() && "+-.eE0123456789".contains(self.chars[self.pos]) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| format!("invalid number {}", text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_dump() {
+        let text = r#" {"a": {"dtype": "F32", "shape": [2, 3], "ok": true},
+            "b\"é": [-1.5, null, false] } "#;
+        let value = Json::parse(text).unwrap();
+
+        assert_eq!(
+            value.get("a").unwrap().get("shape").unwrap().as_usize_vec(),
+            Some(vec![2, 3])
+        );
+        assert_eq!(
+            value.dump(),
+            r#"{"a":{"dtype":"F32","shape":[2,3],"ok":true},"b\"é":[-1.5,null,false]}"#
+        );
+        assert_eq!(Json::parse(&value.dump()).unwrap(), value);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Json::parse("{\"a\": 1").is_err());
+        assert!(Json::parse("[1, 2] 3").is_err());
+        assert!(Json::parse("\"abc").is_err());
+    }
+
+    #[test]
+    fn parse_malformed() {
+        let malformed = [
+            "",
+            " ",
+            "{",
+            "}",
+            "{\"a\"}",
+            "{\"a\":}",
+            "{\"a\" 1}",
+            "{\"a\":1,}",
+            "{1:2}",
+            "[1,]",
+            "[,1]",
+            "tru",
+            "nul",
+            "-",
+            "1.2.3",
+            "\"\\x\"",
+            "\"\\u12\"",
+            "\"\\uzzzz\"",
+            "\"\\ud800\\u0041\"",
+            "\"\\udc00\"",
+        ];
+        for text in malformed.iter() {
+            assert!(Json::parse(text).is_err(), "{:?} was accepted", text);
+        }
+
+        let deep = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(Json::parse(&deep).is_err());
+        let nested = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(Json::parse(&nested).is_ok());
+    }
+
+    #[test]
+    fn parse_mutated_headers() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let header = r#"{"__metadata__":{"format":"pt"},"w":{"dtype":"F32","shape":[2,2],"data_offsets":[0,16]},"s":"\u00e9\ud83d\ude00"}"#;
+        let alphabet: Vec<char> = "{}[]\",:\\u0123456789abcdef-+.eEtrunl \u{e9}"
+            .chars()
+            .collect();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // random edits of a valid header must give a value or an error, never a panic
+        for _ in 0..5000 {
+            let mut chars: Vec<char> = header.chars().collect();
+            for _ in 0..rng.gen_range(1..4) {
+                let pos = rng.gen_range(0..chars.len());
+                match rng.gen_range(0..3) {
+                    0 => {
+                        chars.remove(pos);
+                    }
+                    1 => chars.insert(pos, alphabet[rng.gen_range(0..alphabet.len())]),
+                    _ => chars.truncate(pos),
+                }
+                if chars.is_empty() {
+                    break;
+                }
+            }
+            let text: String = chars.into_iter().collect();
+            if let Ok(value) = Json::parse(&text) {
+                assert_eq!(Json::parse(&value.dump()).unwrap(), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+pub mod checkpoint;
+pub mod json;
+pub mod npy;
+pub mod npz;
+pub mod onnx;
+pub mod protobuf;
+pub mod safetensors;
+#[cfg(feature = "serde")]
+pub mod serde_impls;
+
+use std::convert::TryInto;
+
+use ndarray::{Array, IxDyn, NdFloat};
+
+/// floating point type of the elements stored in a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dtype {
+    F32,
+    F64,
+}
+
+impl Dtype {
+    /// type matching `T`, `f32` or `f64`
+    pub fn of<T: NdFloat>() -> Dtype {
+        match std::mem::size_of::<T>() {
+            4 => Dtype::F32,
+            8 => Dtype::F64,
+            size => panic!("no dtype for floats of {} bytes", size),
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Dtype::F32 => 4,
+            Dtype::F64 => 8,
+        }
+    }
+
+    /// little endian bytes of `array` in C order
+    pub fn encode<T: NdFloat>(self, array: &Array<T, IxDyn>) -> Vec<u8> {
+        let mut out = Vec::with_capacity(array.len() * self.size());
+        for &x in array.as_standard_layout().iter() {
+            match self {
+                Dtype::F32 => out.extend_from_slice(&x.to_f32().unwrap().to_le_bytes()),
+                Dtype::F64 => out.extend_from_slice(&x.to_f64().unwrap().to_le_bytes()),
+            }
+        }
+        out
+    }
+
+    /// values stored as little or big endian bytes, converted to `T`
+    pub fn decode<T: NdFloat>(self, bytes: &[u8], little_endian: bool) -> Vec<T> {
+        bytes
+            .chunks_exact(self.size())
+            .map(|chunk| {
+                let value = match self {
+                    Dtype::F32 => {
+                        let chunk: [u8; 4] = chunk.try_into().unwrap();
+                        if little_endian {
+                            f32::from_le_bytes(chunk) as f64
+                        } else {
+                            f32::from_be_bytes(chunk) as f64
+                        }
+                    }
+                    Dtype::F64 => {
+                        let chunk: [u8; 8] = chunk.try_into().unwrap();
+                        if little_endian {
+                            f64::from_le_bytes(chunk)
+                        } else {
+                            f64::from_be_bytes(chunk)
+                        }
+                    }
+                };
+                T::from(value).unwrap()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,300 @@
+use std::fs;
+use std::path::Path;
+
+use ndarray::{Array, IxDyn, NdFloat, ShapeBuilder};
+
+use crate::serialize::Dtype;
+use crate::variable::{Variable, VariableRef};
+
+// npy layout: the magic string, a version, the length of the header, then a python dict
+// literal giving the dtype, the memory order and the shape, padded with spaces and a newline
+// so that the data starts on a multiple of 64 bytes
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// values of the header dict
+#[derive(Debug, PartialEq)]
+enum Literal {
+    Str(String),
+    Bool(bool),
+    Tuple(Vec<usize>),
+}
+
+/// parse the `{'key': value, ...}` dict literal of a header
+fn parse_header(header: &str) -> Result<Vec<(String, Literal)>, String> {
+    let chars: Vec<char> = header.trim().chars().collect();
+    let mut pos = 0;
+    let skip = |pos: &mut usize| {
+        while *pos < chars.len() && (chars[*pos].is_whitespace() || chars[*pos] == ',') {
+            *pos += 1;
+        }
+    };
+    let string = |pos: &mut usize| -> Result<String, String> {
+        let quote = chars[*pos];
+        let end = chars[*pos + 1..]
+            .iter()
+            .position(|&c| c == quote)
+            .ok_or("unterminated string in npy header")?;
+        let s = chars[*pos + 1..*pos + 1 + end].iter().collect();
+        *pos += end + 2;
+        Ok(s)
+    };
+
+    if chars.first() != Some(&'{') || chars.last() != Some(&'}') {
+        return Err(format!("npy header is not a dict: {}", header));
+    }
+    pos += 1;
+    let mut entries = vec![];
+    loop {
+        skip(&mut pos);
+        if chars[pos] == '}' {
+            return Ok(entries);
+        }
+        if chars[pos] != '\'' && chars[pos] != '"' {
+            return Err(format!("invalid key in npy header: {}", header));
+        }
+        let key = string(&mut pos)?;
+        while pos < chars.len() && (chars[pos].is_whitespace() || chars[pos] == ':') {
+            pos += 1;
+        }
+        let value = match chars.get(pos) {
+            Some('\'') | Some('"') => Literal::Str(string(&mut pos)?),
+            Some('(') => {
+                let end = chars[pos..]
+                    .iter()
+                    .position(|&c| c == ')')
+                    .ok_or("unterminated tuple in npy header")?;
+                let inner: String = chars[pos + 1..pos + end].iter().collect();
+                pos += end + 1;
+                let dims = inner
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.trim_end_matches('L').parse::<usize>())
+                    .collect::<Result<Vec<usize>, _>>()
+                    .map_err(|_| format!("invalid shape in npy header: {}", header))?;
+                Literal::Tuple(dims)
+            }
+            _ => {
+                let rest: String = chars[pos..].iter().collect();
+                if rest.starts_with("True") {
+                    pos += 4;
+                    Literal::Bool(true)
+                } else if rest.starts_with("False") {
+                    pos += 5;
+                    Literal::Bool(false)
+                } else {
+                    return Err(format!("invalid value in npy header: {}", header));
+                }
+            }
+        };
+        entries.push((key, value));
+    }
+}
+
+fn parse_descr(descr: &str) -> Result<(Dtype, bool), String> {
+    let little_endian = match descr.chars().next() {
+        Some('<') | Some('=') | Some('|') => true,
+        Some('>') => false,
+        _ => return Err(format!("unsupported dtype {}", descr)),
+    };
+    match &descr[1..] {
+        "f4" => Ok((Dtype::F32, little_endian)),
+        "f8" => Ok((Dtype::F64, little_endian)),
+        _ => Err(format!("unsupported dtype {}", descr)),
+    }
+}
+
+/// npy bytes of an array, in C order and little endian
+pub fn write_npy<T: NdFloat>(array: &Array<T, IxDyn>) -> Vec<u8> {
+    let dtype = Dtype::of::<T>();
+    let descr = match dtype {
+        Dtype::F32 => "<f4",
+        Dtype::F64 => "<f8",
+    };
+    let shape = match array.shape() {
+        [len] => format!("({},)", len),
+        shape => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|len| len.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    // magic, version and u16 length take 10 bytes, the newline ends the header
+    while (MAGIC.len() + 4 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    out.extend(header.into_bytes());
+    out.extend(dtype.encode(array));
+    out
+}
+
+/// array stored in npy bytes of dtype `f4` or `f8` in any byte and memory order, converted
+/// to `T`
+pub fn read_npy<T: NdFloat>(bytes: &[u8]) -> Result<Array<T, IxDyn>, String> {
+    if bytes.len() < MAGIC.len() + 4 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("not a npy file".to_string());
+    }
+    let major = bytes[MAGIC.len()];
+    let (header_start, header_len) = match major {
+        1 => (10, u16::from_le_bytes([bytes[8], bytes[9]]) as usize),
+        2 | 3 if bytes.len() >= 12 => (
+            12,
+            u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+        ),
+        _ => return Err(format!("unsupported npy version {}", major)),
+    };
+    let data_start = header_start + header_len;
+    if data_start > bytes.len() {
+        return Err("npy header exceeds the file".to_string());
+    }
+    let header =
+        std::str::from_utf8(&bytes[header_start..data_start]).map_err(|e| e.to_string())?;
+    let entries = parse_header(header)?;
+    let get = |key: &str| {
+        entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .ok_or(format!("npy header has no {}", key))
+    };
+
+    let (dtype, little_endian) = match get("descr")? {
+        Literal::Str(descr) => parse_descr(descr)?,
+        _ => return Err("invalid descr in npy header".to_string()),
+    };
+    let fortran_order = match get("fortran_order")? {
+        Literal::Bool(b) => *b,
+        _ => return Err("invalid fortran_order in npy header".to_string()),
+    };
+    let shape = match get("shape")? {
+        Literal::Tuple(shape) => shape.clone(),
+        _ => return Err("invalid shape in npy header".to_string()),
+    };
+
+    let len = shape
+        .iter()
+        .try_fold(dtype.size(), |len, &dim| len.checked_mul(dim))
+        .ok_or_else(|| format!("npy shape {:?} overflows the address space", shape))?;
+    let data = &bytes[data_start..];
+    if data.len() < len {
+        return Err(format!("npy data is too short for the shape {:?}", shape));
+    }
+    let values = dtype.decode(&data[..len], little_endian);
+    let array = if fortran_order {
+        Array::from_shape_vec(IxDyn(&shape).f(), values)
+    } else {
+        Array::from_shape_vec(IxDyn(&shape), values)
+    };
+    Ok(array.unwrap())
+}
+
+pub fn save_npy<T: NdFloat, P: AsRef<Path>>(
+    path: P,
+    array: &Array<T, IxDyn>,
+) -> Result<(), String> {
+    fs::write(path, write_npy(array)).map_err(|e| e.to_string())
+}
+
+pub fn load_npy<T: NdFloat, P: AsRef<Path>>(path: P) -> Result<Array<T, IxDyn>, String> {
+    read_npy(&fs::read(path).map_err(|e| e.to_string())?)
+}
+
+/// npy file as a leaf variable
+pub fn load_variable<T: NdFloat, P: AsRef<Path>>(path: P) -> Result<VariableRef<T>, String> {
+    Ok(Variable::new(load_npy(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    /// bytes of `np.save(f, np.arange(6, dtype='<f4').reshape(2, 3))`: magic, version 1.0,
+    /// u16 header length, the header padded with spaces so that the data starts at byte 128,
+    /// then the little endian data
+    const NUMPY_FILE: &[u8] = b"\x93NUMPY\x01\x00\x76\x00\
+        {'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }\
+        \x20                            \
+        \x20                            \n\
+        \x00\x00\x00\x00\x00\x00\x80\x3f\x00\x00\x00\x40\
+        \x00\x00\x40\x40\x00\x00\x80\x40\x00\x00\xa0\x40";
+
+    #[test]
+    fn numpy_compatibility() {
+        let array = Array::from_shape_vec((2, 3), (0..6).map(|x| x as f32).collect())
+            .unwrap()
+            .into_dyn();
+
+        assert_eq!(write_npy(&array), NUMPY_FILE);
+        assert_eq!(read_npy::<f32>(NUMPY_FILE).unwrap(), array);
+    }
+
+    #[test]
+    fn read_orders_and_endianness() {
+        // `np.asfortranarray(np.arange(6, dtype='>f8').reshape(2, 3))` with a version 2 header
+        let header = "{'descr': '>f8', 'fortran_order': True, 'shape': (2, 3), }\n";
+        let mut bytes = b"\x93NUMPY\x02\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend(header.bytes());
+        for x in [0., 3., 1., 4., 2., 5.].iter() {
+            bytes.extend_from_slice(&f64::to_be_bytes(*x));
+        }
+
+        let array = read_npy::<f64>(&bytes).unwrap();
+        assert_eq!(array, array!([0., 1., 2.], [3., 4., 5.]).into_dyn());
+    }
+
+    #[test]
+    fn round_trip_shapes() {
+        let arrays = vec![
+            Array::from_elem(IxDyn(&[]), 1.5),
+            array!(1.0, 2.0, 3.0).into_dyn(),
+            Array::from_shape_fn((2, 1, 3, 2), |(a, b, c, d)| (a + b * 2 + c * 3 + d) as f64)
+                .into_dyn(),
+            Array::zeros(IxDyn(&[0, 4])),
+        ];
+        for array in arrays {
+            let bytes = write_npy(&array);
+            assert_eq!((bytes.len() - array.len() * 8) % 64, 0);
+            assert_eq!(read_npy::<f64>(&bytes).unwrap(), array);
+        }
+        assert!(String::from_utf8_lossy(&write_npy(&array!(1.0).into_dyn())).contains("(1,)"));
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(read_npy::<f32>(b"not numpy").is_err());
+        let bytes = NUMPY_FILE;
+        assert!(read_npy::<f32>(&bytes[..bytes.len() - 1]).is_err());
+        let mut bad = bytes.to_vec();
+        let pos = bad.windows(3).position(|w| w == b"<f4").unwrap();
+        bad[pos + 1] = b'i';
+        assert!(read_npy::<f32>(&bad).is_err());
+    }
+
+    #[test]
+    fn shape_overflow() {
+        let header =
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (4294967296, 4294967296), }\n";
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend(header.bytes());
+
+        let err = read_npy::<f64>(&bytes).unwrap_err();
+        assert!(err.contains("overflows"), "{}", err);
+    }
+}
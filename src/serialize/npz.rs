@@ -0,0 +1,325 @@
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+use crate::module::{LoadReport, Module, StateDict};
+use crate::serialize::npy::{read_npy, write_npy};
+use ndarray::NdFloat;
+
+// npz archives are zip files holding one `{name}.npy` entry per array. They are written
+// without compression, like `np.savez`, and only such archives can be read
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// 1980-01-01, the earliest date of the format
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// lookup table of the CRC-32 of the zip format, with the reversed polynomial `0xedb88320`
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            bit += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-32 checksum of the zip format
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &b in bytes {
+        crc = CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xffff_ffff
+}
+
+fn u16_at(bytes: &[u8], pos: usize) -> Result<u16, String> {
+    bytes
+        .get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "truncated zip archive".to_string())
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> Result<u32, String> {
+    bytes
+        .get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "truncated zip archive".to_string())
+}
+
+fn u64_at(bytes: &[u8], pos: usize) -> Result<u64, String> {
+    bytes
+        .get(pos..pos + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "truncated zip archive".to_string())
+}
+
+/// uncompressed zip archive of `(name, content)` files
+fn write_zip(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut central = vec![];
+
+    for (name, content) in files {
+        let offset = out.len() as u32;
+        let crc = crc32(content);
+        let size = content.len() as u32;
+
+        // fields shared by the local and central headers, from the version needed
+        let mut common = vec![];
+        common.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        common.extend_from_slice(&0u16.to_le_bytes()); // flags
+        common.extend_from_slice(&0u16.to_le_bytes()); // stored
+        common.extend_from_slice(&0u16.to_le_bytes()); // time
+        common.extend_from_slice(&DOS_DATE.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes()); // compressed
+        common.extend_from_slice(&size.to_le_bytes()); // uncompressed
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // extra field
+
+        out.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        out.extend_from_slice(&common);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(content);
+
+        central.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central.extend_from_slice(&common);
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // disk
+    out.extend_from_slice(&0u16.to_le_bytes()); // disk of the central directory
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // comment
+    out
+}
+
+/// files of an uncompressed zip archive, read from its central directory
+fn read_zip(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    // the end record is at least 22 bytes long and may be followed by a comment
+    let end = (0..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&pos| u32_at(bytes, pos) == Ok(END_OF_CENTRAL_DIRECTORY))
+        .ok_or("not a zip archive")?;
+    let count = u16_at(bytes, end + 10)? as usize;
+    let mut pos = u32_at(bytes, end + 16)? as usize;
+
+    let mut files = vec![];
+    for _ in 0..count {
+        if u32_at(bytes, pos)? != CENTRAL_HEADER {
+            return Err("invalid zip central directory".to_string());
+        }
+        let method = u16_at(bytes, pos + 10)?;
+        let crc = u32_at(bytes, pos + 16)?;
+        let mut size = u32_at(bytes, pos + 20)? as u64;
+        let name_len = u16_at(bytes, pos + 28)? as usize;
+        let extra_len = u16_at(bytes, pos + 30)? as usize;
+        let comment_len = u16_at(bytes, pos + 32)? as usize;
+        let mut offset = u32_at(bytes, pos + 42)? as u64;
+        let name = bytes
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or("truncated zip archive")?;
+        let name = String::from_utf8(name.to_vec()).map_err(|e| e.to_string())?;
+
+        // zip64 extra field, holding the values saturated in the header in this order
+        let extra_start = pos + 46 + name_len;
+        let mut extra = extra_start;
+        while extra + 4 <= extra_start + extra_len {
+            let (id, len) = (u16_at(bytes, extra)?, u16_at(bytes, extra + 2)? as usize);
+            if id == 1 {
+                let mut field = extra + 4;
+                if u32_at(bytes, pos + 24)? == u32::MAX {
+                    field += 8; // uncompressed size, equal to the size of stored data
+                }
+                if size == u32::MAX as u64 {
+                    size = u64_at(bytes, field)?;
+                    field += 8;
+                }
+                if offset == u32::MAX as u64 {
+                    offset = u64_at(bytes, field)?;
+                }
+            }
+            extra += 4 + len;
+        }
+        pos = extra_start + extra_len + comment_len;
+
+        if method != 0 {
+            return Err(format!(
+                "{} is compressed, only archives written by np.savez can be read",
+                name
+            ));
+        }
+        let offset = offset as usize;
+        if u32_at(bytes, offset)? != LOCAL_HEADER {
+            return Err(format!("invalid local header for {}", name));
+        }
+        let data_start = offset
+            + 30
+            + u16_at(bytes, offset + 26)? as usize
+            + u16_at(bytes, offset + 28)? as usize;
+        let content = bytes
+            .get(data_start..data_start + size as usize)
+            .ok_or("truncated zip archive")?;
+        if crc32(content) != crc {
+            return Err(format!("checksum mismatch for {}", name));
+        }
+        files.push((name, content.to_vec()));
+    }
+    Ok(files)
+}
+
+/// npz bytes holding one npy entry per array
+pub fn write_npz<T: NdFloat>(arrays: &StateDict<T>) -> Vec<u8> {
+    let files: Vec<(String, Vec<u8>)> = arrays
+        .iter()
+        .map(|(name, array)| (format!("{}.npy", name), write_npy(array)))
+        .collect();
+    write_zip(&files)
+}
+
+/// arrays of npz bytes keyed by their name without the `.npy` extension, like `np.load`
+pub fn read_npz<T: NdFloat>(bytes: &[u8]) -> Result<StateDict<T>, String> {
+    read_zip(bytes)?
+        .into_iter()
+        .map(|(name, content)| {
+            let key = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+            read_npy(&content)
+                .map(|array| (key, array))
+                .map_err(|e| format!("{}: {}", name, e))
+        })
+        .collect()
+}
+
+pub fn save_npz<T: NdFloat, P: AsRef<Path>>(path: P, arrays: &StateDict<T>) -> Result<(), String> {
+    fs::write(path, write_npz(arrays)).map_err(|e| e.to_string())
+}
+
+pub fn load_npz<T: NdFloat, P: AsRef<Path>>(path: P) -> Result<StateDict<T>, String> {
+    read_npz(&fs::read(path).map_err(|e| e.to_string())?)
+}
+
+/// write the `state_dict` of a module to a npz archive, `np.load(path)["layers.0.weight"]`
+pub fn save_module<T: NdFloat, M: Module<T> + ?Sized, P: AsRef<Path>>(
+    module: &M,
+    path: P,
+) -> Result<(), String> {
+    save_npz(path, &module.state_dict())
+}
+
+/// load a npz archive into a module with `load_state_dict`
+pub fn load_module<T: NdFloat, M: Module<T> + ?Sized, P: AsRef<Path>>(
+    module: &mut M,
+    path: P,
+) -> Result<LoadReport, String> {
+    Ok(module.load_state_dict(&load_npz(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::linear::Linear;
+    use crate::nn::norm::BatchNorm1d;
+    use crate::seq;
+    use ndarray::{array, Array};
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(CRC32_TABLE[1], 0x7707_3096);
+        assert_eq!(CRC32_TABLE[255], 0x2d02_ef8d);
+    }
+
+    #[test]
+    fn npz_round_trip() {
+        let mut arrays = StateDict::new();
+        arrays.insert("x".to_string(), array!([1.0, 2.0], [3.0, 4.0]).into_dyn());
+        arrays.insert("layers.0.bias".to_string(), Array::zeros(vec![3, 1]));
+
+        let bytes = write_npz(&arrays);
+        assert_eq!(read_npz::<f64>(&bytes).unwrap(), arrays);
+
+        let mut corrupted = bytes.clone();
+        let pos = corrupted.windows(3).position(|w| w == b"<f8").unwrap();
+        corrupted[pos + 200] ^= 1;
+        assert!(read_npz::<f64>(&corrupted).is_err());
+    }
+
+    #[test]
+    fn read_zip64_local_headers() {
+        // `np.savez` writes a zip64 extra field in the local headers, with saturated sizes
+        let content = write_npy(&array!(1.0f32, 2.0).into_dyn());
+        let mut bytes = LOCAL_HEADER.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[45, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+        bytes.extend_from_slice(&crc32(&content).to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&5u16.to_le_bytes());
+        bytes.extend_from_slice(&20u16.to_le_bytes());
+        bytes.extend_from_slice(b"a.npy");
+        bytes.extend_from_slice(&[1, 0, 16, 0]);
+        bytes.extend_from_slice(&(content.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(content.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&content);
+
+        let central_offset = bytes.len() as u32;
+        let mut central = CENTRAL_HEADER.to_le_bytes().to_vec();
+        central.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+        central.extend_from_slice(&crc32(&content).to_le_bytes());
+        central.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        central.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        central.extend_from_slice(&5u16.to_le_bytes());
+        central.extend_from_slice(&[0; 12]);
+        central.extend_from_slice(&0u32.to_le_bytes());
+        central.extend_from_slice(b"a.npy");
+        bytes.extend_from_slice(&central);
+        bytes.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+        bytes.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&central_offset.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+
+        let arrays = read_npz::<f32>(&bytes).unwrap();
+        assert_eq!(arrays["a"], array!(1.0, 2.0).into_dyn());
+    }
+
+    #[test]
+    fn save_and_load_module() {
+        let model = seq![Linear::new(2, 4), BatchNorm1d::new(4)];
+        let path = std::env::temp_dir().join(format!("rusty_grad_{}.npz", std::process::id()));
+
+        save_module(&model, &path).unwrap();
+        let mut fresh = seq![Linear::new(2, 4), BatchNorm1d::new(4)];
+        let report = load_module(&mut fresh, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(report.is_ok());
+        assert_eq!(fresh.state_dict(), model.state_dict());
+    }
+}
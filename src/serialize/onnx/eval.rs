@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use ndarray::{Array, Axis, Ix2, IxDyn, Zip};
+
+use crate::grad_fn::einsum::{einsum_arrays, EinsumSpec};
+use crate::grad_fn::trigo::erf;
+use crate::serialize::onnx::{Attribute, Model, Node};
+
+// reference interpreter for the operators written by the exporter, computing in double
+// precision and following the ONNX specification rather than the rusty_grad functions
+// so that it can check the exported models
+
+type Tensor = Array<f64, IxDyn>;
+
+/// evaluate the model on the named `inputs` and return its outputs by name
+pub fn run(model: &Model, inputs: &[(&str, Tensor)]) -> Result<HashMap<String, Tensor>, String> {
+    let graph = &model.graph;
+    let mut values: HashMap<String, Tensor> = graph
+        .initializers
+        .iter()
+        .map(|tensor| (tensor.name.clone(), tensor.to_array()))
+        .collect();
+
+    for info in graph.inputs.iter() {
+        let value = inputs
+            .iter()
+            .find(|(name, _)| *name == info.name)
+            .map(|(_, value)| value)
+            .or_else(|| values.get(&info.name))
+            .ok_or(format!("missing input {}", info.name))?;
+        if value.shape() != info.shape.as_slice() {
+            return Err(format!(
+                "input {} has shape {:?}, expected {:?}",
+                info.name,
+                value.shape(),
+                info.shape
+            ));
+        }
+        values.insert(info.name.clone(), value.clone());
+    }
+
+    for node in graph.nodes.iter() {
+        let args = node
+            .inputs
+            .iter()
+            .map(|name| match name.as_str() {
+                // omitted optional input
+                "" => Ok(None),
+                _ => values.get(name).map(Some).ok_or(format!(
+                    "node {} uses the unknown value {}",
+                    node.name, name
+                )),
+            })
+            .collect::<Result<Vec<Option<&Tensor>>, String>>()?;
+        let output = eval_node(node, &args)
+            .map_err(|e| format!("node {} ({}): {}", node.name, node.op_type, e))?;
+        values.insert(node.outputs[0].clone(), output);
+    }
+
+    graph
+        .outputs
+        .iter()
+        .map(|info| {
+            values
+                .remove(&info.name)
+                .map(|value| (info.name.clone(), value))
+                .ok_or(format!("output {} is never computed", info.name))
+        })
+        .collect()
+}
+
+fn arg<'a>(args: &[Option<&'a Tensor>], i: usize) -> Result<&'a Tensor, String> {
+    args.get(i)
+        .copied()
+        .flatten()
+        .ok_or(format!("missing input {}", i))
+}
+
+fn int_attribute(node: &Node, name: &str, default: i64) -> Result<i64, String> {
+    match node.attribute(name) {
+        None => Ok(default),
+        Some(Attribute::Int(i)) => Ok(*i),
+        Some(a) => Err(format!(
+            "attribute {} should be an int, found {:?}",
+            name, a
+        )),
+    }
+}
+
+/// axis counted from the end when negative
+fn axis(axis: i64, ndim: usize) -> Result<usize, String> {
+    let resolved = if axis < 0 { axis + ndim as i64 } else { axis };
+    if resolved < 0 || resolved >= ndim as i64 {
+        return Err(format!(
+            "axis {} out of range for {} dimensions",
+            axis, ndim
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+/// element-wise function of two tensors with multidirectional broadcasting
+fn broadcast(a: &Tensor, b: &Tensor, f: impl Fn(f64, f64) -> f64) -> Result<Tensor, String> {
+    let ndim = a.ndim().max(b.ndim());
+    let dim = |t: &Tensor, i: usize| {
+        if i + t.ndim() < ndim {
+            1
+        } else {
+            t.shape()[i + t.ndim() - ndim]
+        }
+    };
+    let shape = (0..ndim)
+        .map(|i| match (dim(a, i), dim(b, i)) {
+            (x, y) if x == y || y == 1 => Ok(x),
+            (1, y) => Ok(y),
+            _ => Err(format!(
+                "shapes {:?} and {:?} cannot be broadcast",
+                a.shape(),
+                b.shape()
+            )),
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+
+    let mut out = Tensor::zeros(shape);
+    Zip::from(&mut out)
+        .and_broadcast(a)
+        .and_broadcast(b)
+        .for_each(|o, &x, &y| *o = f(x, y));
+    Ok(out)
+}
+
+fn matrix(t: &Tensor) -> Result<ndarray::ArrayView2<'_, f64>, String> {
+    t.view()
+        .into_dimensionality::<Ix2>()
+        .map_err(|_| format!("only 2-D MatMul is supported, got {:?}", t.shape()))
+}
+
+fn sign(x: f64) -> f64 {
+    if x > 0. {
+        1.
+    } else if x < 0. {
+        -1.
+    } else {
+        0.
+    }
+}
+
+fn eval_node(node: &Node, args: &[Option<&Tensor>]) -> Result<Tensor, String> {
+    let unary = |f: fn(f64) -> f64| -> Result<Tensor, String> { Ok(arg(args, 0)?.mapv(f)) };
+    let binary = |f: fn(f64, f64) -> f64| broadcast(arg(args, 0)?, arg(args, 1)?, f);
+
+    match node.op_type.as_str() {
+        "Identity" => Ok(arg(args, 0)?.clone()),
+        "Add" => binary(|x, y| x + y),
+        "Sub" => binary(|x, y| x - y),
+        "Mul" => binary(|x, y| x * y),
+        "Div" => binary(|x, y| x / y),
+        "Pow" => binary(f64::powf),
+        "Max" => binary(f64::max),
+        "Min" => binary(f64::min),
+        "Relu" => unary(|x| x.max(0.)),
+        "Exp" => unary(f64::exp),
+        "Sigmoid" => unary(|x| 1. / (1. + (-x).exp())),
+        "Abs" => unary(f64::abs),
+        "Sign" => unary(sign),
+        "Erf" => unary(erf),
+        "Sin" => unary(f64::sin),
+        "Cos" => unary(f64::cos),
+        "Tan" => unary(f64::tan),
+        "Asin" => unary(f64::asin),
+        "Acos" => unary(f64::acos),
+        "Atan" => unary(f64::atan),
+        "Sinh" => unary(f64::sinh),
+        "Cosh" => unary(f64::cosh),
+        "Tanh" => unary(f64::tanh),
+        "Clip" => {
+            let bound = |i: usize, default: f64| match args.get(i).copied().flatten() {
+                Some(t) => t
+                    .iter()
+                    .next()
+                    .copied()
+                    .ok_or("empty clip bound".to_string()),
+                None => Ok(default),
+            };
+            let (min, max) = (bound(1, f64::NEG_INFINITY)?, bound(2, f64::INFINITY)?);
+            Ok(arg(args, 0)?.mapv(|x| x.max(min).min(max)))
+        }
+        "MatMul" => {
+            let (x, y) = (matrix(arg(args, 0)?)?, matrix(arg(args, 1)?)?);
+            if x.ncols() != y.nrows() {
+                return Err(format!(
+                    "cannot multiply {:?} by {:?}",
+                    x.shape(),
+                    y.shape()
+                ));
+            }
+            Ok(x.dot(&y).into_dyn())
+        }
+        "ReduceSum" => {
+            let x = arg(args, 0)?;
+            let keepdims = int_attribute(node, "keepdims", 1)? != 0;
+            let mut axes = match args.get(1).copied().flatten() {
+                Some(axes) => axes
+                    .iter()
+                    .map(|&a| axis(a as i64, x.ndim()))
+                    .collect::<Result<Vec<usize>, String>>()?,
+                None => vec![],
+            };
+            if axes.is_empty() {
+                axes = (0..x.ndim()).collect();
+            }
+            axes.sort_unstable();
+            let mut out = x.clone();
+            for &a in axes.iter().rev() {
+                out = out.sum_axis(Axis(a));
+                if keepdims {
+                    out.insert_axis_inplace(Axis(a));
+                }
+            }
+            Ok(out)
+        }
+        "Reshape" => {
+            let x = arg(args, 0)?;
+            let mut shape: Vec<i64> = arg(args, 1)?.iter().map(|&d| d as i64).collect();
+            for (i, len) in shape.iter_mut().enumerate() {
+                if *len == 0 {
+                    *len = *x.shape().get(i).ok_or("0 in the shape beyond the input")? as i64;
+                }
+            }
+            let known: i64 = shape.iter().filter(|&&d| d != -1).product();
+            let shape: Vec<usize> = shape
+                .iter()
+                .map(|&d| if d == -1 { x.len() as i64 / known } else { d } as usize)
+                .collect();
+            x.as_standard_layout()
+                .into_owned()
+                .into_shape(shape.clone())
+                .map_err(|_| format!("cannot reshape {:?} into {:?}", x.shape(), shape))
+        }
+        "Transpose" => {
+            let x = arg(args, 0)?;
+            let perm: Vec<usize> = match node.attribute("perm") {
+                Some(Attribute::Ints(perm)) => perm.iter().map(|&p| p as usize).collect(),
+                None => (0..x.ndim()).rev().collect(),
+                Some(a) => return Err(format!("invalid perm {:?}", a)),
+            };
+            Ok(x.view()
+                .permuted_axes(perm)
+                .as_standard_layout()
+                .into_owned())
+        }
+        "Softmax" => {
+            let mut out = arg(args, 0)?.clone();
+            let axis = axis(int_attribute(node, "axis", -1)?, out.ndim())?;
+            for mut lane in out.lanes_mut(Axis(axis)) {
+                let max = lane.fold(f64::NEG_INFINITY, |m, &a| m.max(a));
+                lane.mapv_inplace(|a| (a - max).exp());
+                let sum = lane.sum();
+                lane.mapv_inplace(|a| a / sum);
+            }
+            Ok(out)
+        }
+        "Einsum" => {
+            let equation = match node.attribute("equation") {
+                Some(Attribute::String(equation)) => equation,
+                _ => return Err("missing equation".to_string()),
+            };
+            let spec = EinsumSpec::parse(equation);
+            let operands = (0..spec.inputs.len())
+                .map(|i| arg(args, i))
+                .collect::<Result<Vec<&Tensor>, String>>()?;
+            let mut sizes = HashMap::new();
+            for (operand, subscripts) in operands.iter().zip(spec.inputs.iter()) {
+                for (&c, &len) in subscripts.iter().zip(operand.shape()) {
+                    sizes.insert(c, len);
+                }
+            }
+            let operands: Vec<(&Tensor, &[char])> = operands
+                .into_iter()
+                .zip(spec.inputs.iter().map(|s| s.as_slice()))
+                .collect();
+            Ok(einsum_arrays(&operands, &spec.output, &sizes))
+        }
+        op_type => Err(format!("unsupported operator {}", op_type)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialize::onnx::{DataType, Graph, Tensor as Constant, ValueInfo};
+    use ndarray::array;
+
+    fn node(op_type: &str, inputs: &[&str], attributes: Vec<(String, Attribute)>) -> Node {
+        Node {
+            name: op_type.to_lowercase(),
+            op_type: op_type.to_string(),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: vec!["output".to_string()],
+            attributes,
+        }
+    }
+
+    fn model(node: Node, initializers: Vec<Constant>, shape: &[usize]) -> Model {
+        let info = |name: &str| ValueInfo {
+            name: name.to_string(),
+            data_type: DataType::Double,
+            shape: shape.to_vec(),
+        };
+        Model {
+            ir_version: 7,
+            opset_version: 13,
+            producer_name: String::new(),
+            graph: Graph {
+                name: String::new(),
+                nodes: vec![node],
+                initializers,
+                inputs: vec![info("input")],
+                outputs: vec![info("output")],
+            },
+        }
+    }
+
+    fn eval(model: &Model, x: Tensor) -> Result<Tensor, String> {
+        run(model, &[("input", x)]).map(|mut outputs| outputs.remove("output").unwrap())
+    }
+
+    #[test]
+    fn reshape_special_dims() {
+        let reshape = model(
+            node("Reshape", &["input", "shape"], vec![]),
+            vec![Constant::int64("shape", &[0, -1])],
+            &[2, 3, 2],
+        );
+        let x = Tensor::zeros(vec![2, 3, 2]);
+        assert_eq!(eval(&reshape, x).unwrap().shape(), &[2, 6]);
+    }
+
+    #[test]
+    fn reduce_sum_axes() {
+        let x = array!([1., 2.], [3., 4.]).into_dyn();
+        let keepdims = vec![("keepdims".to_string(), Attribute::Int(1))];
+
+        let all = model(
+            node("ReduceSum", &["input"], keepdims.clone()),
+            vec![],
+            &[2, 2],
+        );
+        assert_eq!(eval(&all, x.clone()).unwrap(), array!([10.]).into_dyn());
+
+        let rows = model(
+            node("ReduceSum", &["input", "axes"], keepdims),
+            vec![Constant::int64("axes", &[-1])],
+            &[2, 2],
+        );
+        assert_eq!(eval(&rows, x).unwrap(), array!([3.], [7.]).into_dyn());
+    }
+
+    #[test]
+    fn invalid_models() {
+        let x = Tensor::zeros(vec![2]);
+        let unknown = model(node("Conv", &["input"], vec![]), vec![], &[2]);
+        assert!(eval(&unknown, x.clone()).unwrap_err().contains("Conv"));
+
+        let dangling = model(node("Add", &["input", "bias"], vec![]), vec![], &[2]);
+        assert!(eval(&dangling, x.clone()).is_err());
+
+        let add = model(node("Add", &["input", "input"], vec![]), vec![], &[2]);
+        assert!(eval(&add, Tensor::zeros(vec![3])).is_err());
+        assert_eq!(eval(&add, x).unwrap(), Tensor::zeros(vec![2]));
+    }
+}
@@ -0,0 +1,817 @@
+pub mod eval;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use ndarray::{Array, IxDyn, NdFloat};
+
+use crate::module::Module;
+use crate::serialize::protobuf::{fields, Writer};
+use crate::serialize::Dtype;
+use crate::variable::{Variable, VariableRef};
+
+// the subset of the ONNX protobuf schema used by the exporter, with the field numbers of
+// onnx.proto. Models are written for opset 13, the first one where `Softmax` works on a
+// single axis and `ReduceSum` takes its axes as an input
+
+pub const IR_VERSION: i64 = 7;
+pub const OPSET_VERSION: i64 = 13;
+
+/// element type of a tensor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataType {
+    Float,
+    Int64,
+    Double,
+}
+
+impl DataType {
+    /// type matching `T`, `f32` or `f64`
+    pub fn of<T: NdFloat>() -> DataType {
+        match Dtype::of::<T>() {
+            Dtype::F32 => DataType::Float,
+            Dtype::F64 => DataType::Double,
+        }
+    }
+
+    fn code(self) -> i64 {
+        match self {
+            DataType::Float => 1,
+            DataType::Int64 => 7,
+            DataType::Double => 11,
+        }
+    }
+
+    fn from_code(code: i64) -> Result<DataType, String> {
+        match code {
+            1 => Ok(DataType::Float),
+            7 => Ok(DataType::Int64),
+            11 => Ok(DataType::Double),
+            _ => Err(format!("unsupported tensor data type {}", code)),
+        }
+    }
+}
+
+/// constant tensor of the graph. The values are kept as `f64`, which holds exactly the
+/// floats and the small integers of shapes and axes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tensor {
+    pub name: String,
+    pub data_type: DataType,
+    pub dims: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl Tensor {
+    pub fn from_array<T: NdFloat>(name: &str, array: &Array<T, IxDyn>) -> Tensor {
+        Tensor {
+            name: name.to_string(),
+            data_type: DataType::of::<T>(),
+            dims: array.shape().to_vec(),
+            values: array
+                .as_standard_layout()
+                .iter()
+                .map(|x| x.to_f64().unwrap())
+                .collect(),
+        }
+    }
+
+    /// 1-D tensor of integers, like the shape of a `Reshape`
+    pub fn int64(name: &str, values: &[i64]) -> Tensor {
+        Tensor {
+            name: name.to_string(),
+            data_type: DataType::Int64,
+            dims: vec![values.len()],
+            values: values.iter().map(|&v| v as f64).collect(),
+        }
+    }
+
+    pub fn to_array<T: NdFloat>(&self) -> Array<T, IxDyn> {
+        let values = self.values.iter().map(|&v| T::from(v).unwrap()).collect();
+        Array::from_shape_vec(IxDyn(&self.dims), values).unwrap()
+    }
+
+    fn encode(&self) -> Writer {
+        let mut raw = Vec::with_capacity(self.values.len() * 8);
+        for &v in self.values.iter() {
+            match self.data_type {
+                DataType::Float => raw.extend_from_slice(&(v as f32).to_le_bytes()),
+                DataType::Int64 => raw.extend_from_slice(&(v as i64).to_le_bytes()),
+                DataType::Double => raw.extend_from_slice(&v.to_le_bytes()),
+            }
+        }
+        let dims: Vec<i64> = self.dims.iter().map(|&d| d as i64).collect();
+
+        let mut writer = Writer::new();
+        writer
+            .packed_ints(1, &dims)
+            .int(2, self.data_type.code())
+            .string(8, &self.name)
+            .bytes(9, &raw);
+        writer
+    }
+
+    /// tensors stored in `raw_data` or in the typed data fields
+    fn decode(bytes: &[u8]) -> Result<Tensor, String> {
+        let mut dims = vec![];
+        let mut data_type = None;
+        let mut name = String::new();
+        let mut raw = None;
+        let mut values = vec![];
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => dims.extend(value.as_ints()?.into_iter().map(|d| d as usize)),
+                2 => data_type = Some(DataType::from_code(value.as_int()?)?),
+                4 => values.extend(value.as_floats()?.into_iter().map(f64::from)),
+                7 => values.extend(value.as_ints()?.into_iter().map(|v| v as f64)),
+                8 => name = value.as_string()?,
+                9 => raw = Some(value.as_bytes()?),
+                10 => values.extend(value.as_doubles()?),
+                _ => {}
+            }
+        }
+        let data_type = data_type.ok_or(format!("tensor {} has no data type", name))?;
+        if let Some(raw) = raw {
+            values = match data_type {
+                DataType::Float => Dtype::F32.decode::<f64>(raw, true),
+                DataType::Double => Dtype::F64.decode::<f64>(raw, true),
+                DataType::Int64 => raw
+                    .chunks_exact(8)
+                    .map(|c| i64::from_le_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]))
+                    .map(|v| v as f64)
+                    .collect(),
+            };
+        }
+        if values.len() != dims.iter().product::<usize>() {
+            return Err(format!(
+                "tensor {} has {} values for the shape {:?}",
+                name,
+                values.len(),
+                dims
+            ));
+        }
+        Ok(Tensor {
+            name,
+            data_type,
+            dims,
+            values,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Attribute {
+    Float(f32),
+    Int(i64),
+    String(String),
+    Ints(Vec<i64>),
+}
+
+impl Attribute {
+    fn encode(&self, name: &str) -> Writer {
+        let mut writer = Writer::new();
+        writer.string(1, name);
+        match self {
+            Attribute::Float(f) => writer.float(2, *f).int(20, 1),
+            Attribute::Int(i) => writer.int(3, *i).int(20, 2),
+            Attribute::String(s) => writer.string(4, s).int(20, 3),
+            Attribute::Ints(ints) => writer.packed_ints(8, ints).int(20, 7),
+        };
+        writer
+    }
+
+    fn decode(bytes: &[u8]) -> Result<(String, Attribute), String> {
+        let mut name = String::new();
+        let mut kind = 0;
+        let (mut f, mut i, mut s, mut ints) = (0., 0, String::new(), vec![]);
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => name = value.as_string()?,
+                2 => f = value.as_float()?,
+                3 => i = value.as_int()?,
+                4 => s = value.as_string()?,
+                8 => ints.extend(value.as_ints()?),
+                20 => kind = value.as_int()?,
+                _ => {}
+            }
+        }
+        let attribute = match kind {
+            1 => Attribute::Float(f),
+            2 => Attribute::Int(i),
+            3 => Attribute::String(s),
+            7 => Attribute::Ints(ints),
+            _ => return Err(format!("unsupported type {} of attribute {}", kind, name)),
+        };
+        Ok((name, attribute))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub name: String,
+    pub op_type: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub attributes: Vec<(String, Attribute)>,
+}
+
+impl Node {
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, a)| a)
+    }
+
+    fn encode(&self) -> Writer {
+        let mut writer = Writer::new();
+        for input in self.inputs.iter() {
+            writer.string(1, input);
+        }
+        for output in self.outputs.iter() {
+            writer.string(2, output);
+        }
+        writer.string(3, &self.name).string(4, &self.op_type);
+        for (name, attribute) in self.attributes.iter() {
+            writer.message(5, &attribute.encode(name));
+        }
+        writer
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Node, String> {
+        let mut node = Node {
+            name: String::new(),
+            op_type: String::new(),
+            inputs: vec![],
+            outputs: vec![],
+            attributes: vec![],
+        };
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => node.inputs.push(value.as_string()?),
+                2 => node.outputs.push(value.as_string()?),
+                3 => node.name = value.as_string()?,
+                4 => node.op_type = value.as_string()?,
+                5 => node.attributes.push(Attribute::decode(value.as_bytes()?)?),
+                _ => {}
+            }
+        }
+        Ok(node)
+    }
+}
+
+/// type and static shape of an input or output of the graph
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueInfo {
+    pub name: String,
+    pub data_type: DataType,
+    pub shape: Vec<usize>,
+}
+
+impl ValueInfo {
+    fn encode(&self) -> Writer {
+        let mut shape = Writer::new();
+        for &len in self.shape.iter() {
+            let mut dim = Writer::new();
+            dim.int(1, len as i64);
+            shape.message(1, &dim);
+        }
+        let mut tensor_type = Writer::new();
+        tensor_type.int(1, self.data_type.code()).message(2, &shape);
+        let mut type_proto = Writer::new();
+        type_proto.message(1, &tensor_type);
+
+        let mut writer = Writer::new();
+        writer.string(1, &self.name).message(2, &type_proto);
+        writer
+    }
+
+    fn decode(bytes: &[u8]) -> Result<ValueInfo, String> {
+        let mut name = String::new();
+        let mut data_type = None;
+        let mut shape = vec![];
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => name = value.as_string()?,
+                2 => {
+                    let tensor_type = fields(value.as_bytes()?)?
+                        .into_iter()
+                        .find(|(field, _)| *field == 1)
+                        .ok_or(format!("value {} is not a tensor", name))?
+                        .1;
+                    for (field, value) in fields(tensor_type.as_bytes()?)? {
+                        match field {
+                            1 => data_type = Some(DataType::from_code(value.as_int()?)?),
+                            2 => {
+                                for (_, dim) in fields(value.as_bytes()?)? {
+                                    let len = fields(dim.as_bytes()?)?
+                                        .into_iter()
+                                        .find(|(field, _)| *field == 1)
+                                        .ok_or(format!("value {} has a symbolic shape", name))?
+                                        .1
+                                        .as_int()?;
+                                    shape.push(len as usize);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(ValueInfo {
+            data_type: data_type.ok_or(format!("value {} has no data type", name))?,
+            name,
+            shape,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Graph {
+    pub name: String,
+    /// in topological order
+    pub nodes: Vec<Node>,
+    pub initializers: Vec<Tensor>,
+    pub inputs: Vec<ValueInfo>,
+    pub outputs: Vec<ValueInfo>,
+}
+
+impl Graph {
+    fn encode(&self) -> Writer {
+        let mut writer = Writer::new();
+        for node in self.nodes.iter() {
+            writer.message(1, &node.encode());
+        }
+        writer.string(2, &self.name);
+        for tensor in self.initializers.iter() {
+            writer.message(5, &tensor.encode());
+        }
+        for input in self.inputs.iter() {
+            writer.message(11, &input.encode());
+        }
+        for output in self.outputs.iter() {
+            writer.message(12, &output.encode());
+        }
+        writer
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Graph, String> {
+        let mut graph = Graph {
+            name: String::new(),
+            nodes: vec![],
+            initializers: vec![],
+            inputs: vec![],
+            outputs: vec![],
+        };
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => graph.nodes.push(Node::decode(value.as_bytes()?)?),
+                2 => graph.name = value.as_string()?,
+                5 => graph.initializers.push(Tensor::decode(value.as_bytes()?)?),
+                11 => graph.inputs.push(ValueInfo::decode(value.as_bytes()?)?),
+                12 => graph.outputs.push(ValueInfo::decode(value.as_bytes()?)?),
+                _ => {}
+            }
+        }
+        Ok(graph)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Model {
+    pub ir_version: i64,
+    /// version of the default `ai.onnx` operator set
+    pub opset_version: i64,
+    pub producer_name: String,
+    pub graph: Graph,
+}
+
+impl Model {
+    /// bytes of the `ModelProto` message
+    pub fn encode(&self) -> Vec<u8> {
+        let mut opset = Writer::new();
+        opset.string(1, "").int(2, self.opset_version);
+
+        let mut writer = Writer::new();
+        writer
+            .int(1, self.ir_version)
+            .string(2, &self.producer_name)
+            .message(7, &self.graph.encode())
+            .message(8, &opset);
+        writer.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Model, String> {
+        let mut ir_version = 0;
+        let mut opset_version = None;
+        let mut producer_name = String::new();
+        let mut graph = None;
+        for (field, value) in fields(bytes)? {
+            match field {
+                1 => ir_version = value.as_int()?,
+                2 => producer_name = value.as_string()?,
+                7 => graph = Some(Graph::decode(value.as_bytes()?)?),
+                8 => {
+                    let mut domain = String::new();
+                    let mut version = 0;
+                    for (field, value) in fields(value.as_bytes()?)? {
+                        match field {
+                            1 => domain = value.as_string()?,
+                            2 => version = value.as_int()?,
+                            _ => {}
+                        }
+                    }
+                    if domain.is_empty() || domain == "ai.onnx" {
+                        opset_version = Some(version);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Model {
+            ir_version,
+            opset_version: opset_version.ok_or("model does not import the onnx opset")?,
+            producer_name,
+            graph: graph.ok_or("model has no graph")?,
+        })
+    }
+}
+
+/// ONNX operator computing a `GradFn`, see `GradFn::onnx`
+pub struct Op {
+    pub op_type: &'static str,
+    /// the operator takes only the left input of the function
+    pub unary: bool,
+    pub attributes: Vec<(&'static str, Attribute)>,
+    /// constant inputs following the ones of the function, like the shape of a `Reshape`
+    pub constants: Vec<Tensor>,
+}
+
+impl Op {
+    pub fn unary(op_type: &'static str) -> Op {
+        Op {
+            op_type,
+            unary: true,
+            attributes: vec![],
+            constants: vec![],
+        }
+    }
+
+    pub fn binary(op_type: &'static str) -> Op {
+        Op {
+            unary: false,
+            ..Op::unary(op_type)
+        }
+    }
+
+    pub fn with_attribute(mut self, name: &'static str, attribute: Attribute) -> Op {
+        self.attributes.push((name, attribute));
+        self
+    }
+
+    /// the name of the tensor is chosen by the exporter
+    pub fn with_constant(mut self, tensor: Tensor) -> Op {
+        self.constants.push(tensor);
+        self
+    }
+}
+
+struct Tracer {
+    /// names of the variables already in the graph
+    names: HashMap<usize, String>,
+    /// names of the parameters and buffers of the module
+    leaf_names: HashMap<usize, String>,
+    nodes: Vec<Node>,
+    initializers: Vec<Tensor>,
+}
+
+impl Tracer {
+    /// add the nodes computing `var` after the ones computing its inputs and return the
+    /// name of its value
+    fn visit<T: NdFloat>(&mut self, var: &VariableRef<T>) -> Result<String, String> {
+        let ptr = var.as_ptr() as usize;
+        if let Some(name) = self.names.get(&ptr) {
+            return Ok(name.clone());
+        }
+
+        let var = var.borrow();
+        let name = match (&var.grad_fn, &var.left_root, &var.right_root) {
+            (Some(grad_fn), Some(left), Some(right)) => {
+                let ops = grad_fn.onnx().ok_or(format!(
+                    "a function of the graph, computing a tensor of shape {:?}, has no ONNX \
+                     equivalent",
+                    var.data.shape()
+                ))?;
+                // `softmax` shifts the data of an identity node in place, a change the graph
+                // does not record and which the exported `Identity` would lose
+                if ops.len() == 1 && ops[0].op_type == "Identity" && var.data != left.borrow().data
+                {
+                    return Err(format!(
+                        "the data of an identity node of shape {:?} was modified in place, as \
+                         done by `softmax`, and can not be exported; use `softmax_axis` instead",
+                        var.data.shape()
+                    ));
+                }
+
+                let mut inputs = vec![self.visit(left)?];
+                if !ops[0].unary {
+                    inputs.push(self.visit(right)?);
+                }
+                for op in ops {
+                    let name = format!("{}_{}", op.op_type, self.nodes.len());
+                    for (i, mut constant) in op.constants.into_iter().enumerate() {
+                        constant.name = format!("{}.{}", name, i);
+                        inputs.push(constant.name.clone());
+                        self.initializers.push(constant);
+                    }
+                    let output = format!("{}_output", name);
+                    self.nodes.push(Node {
+                        name,
+                        op_type: op.op_type.to_string(),
+                        inputs,
+                        outputs: vec![output.clone()],
+                        attributes: op
+                            .attributes
+                            .into_iter()
+                            .map(|(name, attribute)| (name.to_string(), attribute))
+                            .collect(),
+                    });
+                    inputs = vec![output];
+                }
+                inputs.pop().unwrap()
+            }
+            _ => {
+                let name = match self.leaf_names.get(&ptr) {
+                    Some(name) => name.clone(),
+                    None => format!("constant_{}", self.initializers.len()),
+                };
+                self.initializers.push(Tensor::from_array(&name, &var.data));
+                name
+            }
+        };
+        self.names.insert(ptr, name.clone());
+        Ok(name)
+    }
+}
+
+/// trace `module.f` on `sample` and build the model computing it for inputs of the same
+/// shape, named `input` and `output`. Parameters and buffers become initializers named
+/// like in the state dict, the other leaves of the graph are exported as constants.
+/// Modules behaving differently in training should be traced in evaluation mode
+pub fn export<T: NdFloat, M: Module<T> + ?Sized>(
+    module: &mut M,
+    sample: &Array<T, IxDyn>,
+) -> Result<Model, String> {
+    let input = Variable::new_no_retain_grad(sample.clone());
+    let output = module.f(&input);
+
+    let mut tracer = Tracer {
+        names: HashMap::new(),
+        leaf_names: HashMap::new(),
+        nodes: vec![],
+        initializers: vec![],
+    };
+    tracer
+        .names
+        .insert(input.as_ptr() as usize, "input".to_string());
+    let named: Vec<String> = module
+        .named_parameters()
+        .into_iter()
+        .chain(module.named_buffers())
+        .map(|(name, var)| {
+            tracer
+                .leaf_names
+                .insert(var.as_ptr() as usize, name.clone());
+            name
+        })
+        .collect();
+
+    let name = tracer.visit(&output)?;
+    // parameters in the order of the state dict, then the constants in the order of use
+    tracer.initializers.sort_by_key(|tensor| {
+        named
+            .iter()
+            .position(|name| *name == tensor.name)
+            .unwrap_or(named.len())
+    });
+    match tracer.nodes.last_mut() {
+        Some(node) if node.outputs[0] == name => node.outputs[0] = "output".to_string(),
+        _ => tracer.nodes.push(Node {
+            name: format!("Identity_{}", tracer.nodes.len()),
+            op_type: "Identity".to_string(),
+            inputs: vec![name],
+            outputs: vec!["output".to_string()],
+            attributes: vec![],
+        }),
+    }
+
+    let output_shape = output.borrow().data.shape().to_vec();
+    let value_info = |name: &str, shape: &[usize]| ValueInfo {
+        name: name.to_string(),
+        data_type: DataType::of::<T>(),
+        shape: shape.to_vec(),
+    };
+    Ok(Model {
+        ir_version: IR_VERSION,
+        opset_version: OPSET_VERSION,
+        producer_name: "rusty_grad".to_string(),
+        graph: Graph {
+            name: "rusty_grad".to_string(),
+            nodes: tracer.nodes,
+            initializers: tracer.initializers,
+            inputs: vec![value_info("input", sample.shape())],
+            outputs: vec![value_info("output", &output_shape)],
+        },
+    })
+}
+
+/// export `module` traced on `sample` to an `.onnx` file
+pub fn save_file<T: NdFloat, M: Module<T> + ?Sized, P: AsRef<Path>>(
+    module: &mut M,
+    sample: &Array<T, IxDyn>,
+    path: P,
+) -> Result<(), String> {
+    let model = export(module, sample)?;
+    fs::write(path, model.encode()).map_err(|e| e.to_string())
+}
+
+pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Model, String> {
+    Model::decode(&fs::read(path).map_err(|e| e.to_string())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::eval::run;
+    use super::*;
+    use crate::nn::activation::{Softmax, GELU};
+    use crate::nn::dropout::Dropout;
+    use crate::nn::linear::{Linear, MLP};
+    use crate::seq;
+    use ndarray::Dimension;
+
+    fn input(shape: &[usize], offset: f32) -> Array<f32, IxDyn> {
+        Array::from_shape_fn(IxDyn(shape), |i| {
+            (i.as_array_view().iter().sum::<usize>() as f32 * 0.7 + offset).sin()
+        })
+    }
+
+    fn assert_close(a: &Array<f64, IxDyn>, b: &Array<f32, IxDyn>) {
+        assert_eq!(a.shape(), b.shape());
+        for (x, &y) in a.iter().zip(b.iter()) {
+            assert!((x - f64::from(y)).abs() < 1e-5, "{} != {}", x, y);
+        }
+    }
+
+    /// run the reparsed model and the module on a new input
+    fn check_export<M: Module<f32>>(module: &mut M, shape: &[usize]) -> Model {
+        let model = export(module, &input(shape, 0.)).unwrap();
+        let parsed = Model::decode(&model.encode()).unwrap();
+        assert_eq!(parsed, model);
+
+        let x = input(shape, 1.);
+        let outputs = run(&parsed, &[("input", x.mapv(f64::from))]).unwrap();
+        let expected = module.f(&Variable::new(x)).borrow().data.clone();
+        assert_close(&outputs["output"], &expected);
+        parsed
+    }
+
+    #[test]
+    fn export_mlp() {
+        let mut mlp = MLP {
+            layers: vec![Linear::new(3, 4), Linear::new(4, 2)],
+        };
+        let model = check_export(&mut mlp, &[3, 5]);
+
+        let ops: Vec<&str> = model
+            .graph
+            .nodes
+            .iter()
+            .map(|node| node.op_type.as_str())
+            .collect();
+        assert_eq!(ops, vec!["MatMul", "Add", "Relu", "MatMul", "Add"]);
+        assert_eq!(
+            model.graph.nodes[0].inputs,
+            vec!["layers.0.weight", "input"]
+        );
+        assert_eq!(model.graph.nodes[4].outputs, vec!["output"]);
+
+        let names: Vec<&str> = model
+            .graph
+            .initializers
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "layers.0.weight",
+                "layers.0.bias",
+                "layers.1.weight",
+                "layers.1.bias"
+            ]
+        );
+        assert_eq!(model.graph.initializers[0].data_type, DataType::Float);
+        assert_eq!(model.graph.inputs[0].shape, vec![3, 5]);
+        assert_eq!(model.graph.outputs[0].shape, vec![2, 5]);
+        assert_eq!(model.opset_version, OPSET_VERSION);
+    }
+
+    #[test]
+    fn export_sequential() {
+        let mut model = seq!(Linear::new(3, 4), GELU, Linear::new(4, 3), Softmax::new(0));
+        let onnx = check_export(&mut model, &[3, 2]);
+        assert!(onnx.graph.nodes.iter().any(|node| node.op_type == "Erf"));
+        assert_eq!(onnx.graph.nodes.last().unwrap().op_type, "Softmax");
+    }
+
+    struct Composite {
+        weight: VariableRef<f32>,
+    }
+
+    impl Module<f32> for Composite {
+        fn params(&self) -> Vec<VariableRef<f32>> {
+            vec![self.weight.clone()]
+        }
+
+        fn f(&mut self, input: &VariableRef<f32>) -> VariableRef<f32> {
+            let mut x = input.clone().reshape(&[2, 3, 2]).permute(&[2, 0, 1]);
+            let mut mixed = crate::einsum("abc,cd->abd", &[x.clone(), self.weight.clone()]);
+            let norm = mixed.powf(2.).sum().sqrt();
+            (x.softmax_axis(2) + mixed.exp().sigmoid()).tanh() / norm
+        }
+    }
+
+    #[test]
+    fn export_composite() {
+        let mut module = Composite {
+            weight: Variable::new(input(&[3, 3], 2.)),
+        };
+        let model = check_export(&mut module, &[6, 2]);
+        for op in [
+            "Reshape",
+            "Transpose",
+            "Einsum",
+            "Pow",
+            "ReduceSum",
+            "Softmax",
+        ]
+        .iter()
+        {
+            assert!(model.graph.nodes.iter().any(|node| node.op_type == *op));
+        }
+        // the default names of the parameters, then the constant inputs of the operators
+        assert_eq!(model.graph.initializers[0].name, "0");
+        assert_eq!(model.graph.initializers[1].name, "Reshape_0.0");
+    }
+
+    #[test]
+    fn export_input_as_output() {
+        let mut model = seq!(Dropout::new(0.5));
+        model.eval();
+        let model = check_export(&mut model, &[2, 2]);
+        assert_eq!(model.graph.nodes.len(), 1);
+        assert_eq!(model.graph.nodes[0].inputs, vec!["input"]);
+    }
+
+    #[test]
+    fn export_unsupported_function() {
+        // dropout in training has no ONNX equivalent
+        let mut model = seq!(Linear::new(2, 2), Dropout::new(0.5));
+        assert!(export(&mut model, &input(&[2, 3], 0.)).is_err());
+    }
+
+    struct GlobalSoftmax;
+
+    impl Module<f32> for GlobalSoftmax {
+        fn params(&self) -> Vec<VariableRef<f32>> {
+            vec![]
+        }
+
+        fn f(&mut self, input: &VariableRef<f32>) -> VariableRef<f32> {
+            input.clone().softmax()
+        }
+    }
+
+    #[test]
+    fn export_in_place_shift() {
+        // the maximum subtracted by `softmax` is not part of the graph
+        let err = export(&mut GlobalSoftmax, &input(&[2, 3], 1.)).unwrap_err();
+        assert!(err.contains("softmax_axis"), "{}", err);
+    }
+
+    #[test]
+    fn save_and_load_file() {
+        let path =
+            std::env::temp_dir().join(format!("rusty_grad_export_{}.onnx", std::process::id()));
+        let mut model = seq!(Linear::new(2, 3));
+        save_file(&mut model, &input(&[2, 1], 0.), &path).unwrap();
+        let loaded = load_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, export(&mut model, &input(&[2, 1], 0.)).unwrap());
+        assert!(load_file(std::env::temp_dir().join("rusty_grad_missing.onnx")).is_err());
+    }
+}
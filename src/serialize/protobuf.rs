@@ -0,0 +1,253 @@
+use std::convert::TryInto;
+
+// protobuf wire format: every field is a varint key `field << 3 | wire type` followed by a
+// varint, 8 bytes, a varint length and that many bytes, or 4 bytes
+
+/// encoder of the fields of one message, nested messages are encoded in their own writer
+#[derive(Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+fn push_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer::default()
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        push_varint(
+            &mut self.buf,
+            (u64::from(field) << 3) | u64::from(wire_type),
+        );
+    }
+
+    pub fn varint(&mut self, field: u32, value: u64) -> &mut Writer {
+        self.key(field, 0);
+        push_varint(&mut self.buf, value);
+        self
+    }
+
+    /// `int64` and `int32` fields, negative values take 10 bytes
+    pub fn int(&mut self, field: u32, value: i64) -> &mut Writer {
+        self.varint(field, value as u64)
+    }
+
+    pub fn float(&mut self, field: u32, value: f32) -> &mut Writer {
+        self.key(field, 5);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, field: u32, bytes: &[u8]) -> &mut Writer {
+        self.key(field, 2);
+        push_varint(&mut self.buf, bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn string(&mut self, field: u32, s: &str) -> &mut Writer {
+        self.bytes(field, s.as_bytes())
+    }
+
+    pub fn message(&mut self, field: u32, message: &Writer) -> &mut Writer {
+        self.bytes(field, &message.buf)
+    }
+
+    /// repeated integers as one packed field
+    pub fn packed_ints(&mut self, field: u32, values: &[i64]) -> &mut Writer {
+        let mut packed = vec![];
+        for &value in values {
+            push_varint(&mut packed, value as u64);
+        }
+        self.bytes(field, &packed)
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// value of a field as found on the wire
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Value<'a> {
+    pub fn as_int(&self) -> Result<i64, String> {
+        match self {
+            Value::Varint(v) => Ok(*v as i64),
+            _ => Err(format!("expected a varint, found {:?}", self)),
+        }
+    }
+
+    pub fn as_float(&self) -> Result<f32, String> {
+        match self {
+            Value::Fixed32(v) => Ok(f32::from_bits(*v)),
+            _ => Err(format!("expected a float, found {:?}", self)),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<&'a [u8], String> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => Err(format!(
+                "expected a length delimited field, found {:?}",
+                self
+            )),
+        }
+    }
+
+    pub fn as_string(&self) -> Result<String, String> {
+        String::from_utf8(self.as_bytes()?.to_vec()).map_err(|e| e.to_string())
+    }
+
+    /// the integers of a repeated field, which may be packed or not
+    pub fn as_ints(&self) -> Result<Vec<i64>, String> {
+        match self {
+            Value::Bytes(bytes) => {
+                let mut pos = 0;
+                let mut values = vec![];
+                while pos < bytes.len() {
+                    values.push(read_varint(bytes, &mut pos)? as i64);
+                }
+                Ok(values)
+            }
+            _ => Ok(vec![self.as_int()?]),
+        }
+    }
+
+    /// the floats of a repeated field, which may be packed or not
+    pub fn as_floats(&self) -> Result<Vec<f32>, String> {
+        match self {
+            Value::Bytes(bytes) if bytes.len() % 4 == 0 => Ok(bytes
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect()),
+            _ => Ok(vec![self.as_float()?]),
+        }
+    }
+
+    /// the doubles of a repeated field, which may be packed or not
+    pub fn as_doubles(&self) -> Result<Vec<f64>, String> {
+        match self {
+            Value::Bytes(bytes) if bytes.len() % 8 == 0 => Ok(bytes
+                .chunks_exact(8)
+                .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+                .collect()),
+            Value::Fixed64(v) => Ok(vec![f64::from_bits(*v)]),
+            _ => Err(format!("expected a double, found {:?}", self)),
+        }
+    }
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos).ok_or("truncated varint")?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("varint longer than 10 bytes".to_string())
+}
+
+fn read_fixed<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+    let end = *pos + len;
+    if end > bytes.len() {
+        return Err("truncated field".to_string());
+    }
+    let out = &bytes[*pos..end];
+    *pos = end;
+    Ok(out)
+}
+
+/// the fields of a message in the order they appear, repeated fields appear once per
+/// occurrence
+pub fn fields(bytes: &[u8]) -> Result<Vec<(u32, Value<'_>)>, String> {
+    let mut pos = 0;
+    let mut out = vec![];
+    while pos < bytes.len() {
+        let key = read_varint(bytes, &mut pos)?;
+        let field = (key >> 3) as u32;
+        let value = match key & 7 {
+            0 => Value::Varint(read_varint(bytes, &mut pos)?),
+            1 => Value::Fixed64(u64::from_le_bytes(
+                read_fixed(bytes, &mut pos, 8)?.try_into().unwrap(),
+            )),
+            2 => {
+                let len = read_varint(bytes, &mut pos)? as usize;
+                Value::Bytes(read_fixed(bytes, &mut pos, len)?)
+            }
+            5 => Value::Fixed32(u32::from_le_bytes(
+                read_fixed(bytes, &mut pos, 4)?.try_into().unwrap(),
+            )),
+            wire_type => return Err(format!("unsupported wire type {}", wire_type)),
+        };
+        out.push((field, value));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_fields() {
+        // examples of the protobuf encoding guide
+        let mut writer = Writer::new();
+        writer.varint(1, 150).string(2, "testing");
+        assert_eq!(
+            writer.into_bytes(),
+            vec![0x08, 0x96, 0x01, 0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g']
+        );
+
+        let mut writer = Writer::new();
+        writer.int(1, -2);
+        assert_eq!(writer.into_bytes().len(), 11);
+    }
+
+    #[test]
+    fn decode_fields() {
+        let mut inner = Writer::new();
+        inner.float(1, 1.5);
+        let mut writer = Writer::new();
+        writer
+            .int(1, -2)
+            .packed_ints(4, &[3, 270, -1])
+            .message(5, &inner)
+            .varint(4, 7);
+        let bytes = writer.into_bytes();
+
+        let fields = fields(&bytes).unwrap();
+        assert_eq!(fields.len(), 4);
+        assert_eq!(fields[0], (1, Value::Varint(-2i64 as u64)));
+        assert_eq!(fields[0].1.as_int().unwrap(), -2);
+        assert_eq!(fields[1].1.as_ints().unwrap(), vec![3, 270, -1]);
+        assert_eq!(fields[3].1.as_ints().unwrap(), vec![7]);
+
+        let inner = super::fields(fields[2].1.as_bytes().unwrap()).unwrap();
+        assert_eq!(inner[0].1.as_float().unwrap(), 1.5);
+    }
+
+    #[test]
+    fn truncated_messages() {
+        assert!(fields(&[0x08]).is_err());
+        assert!(fields(&[0x12, 0x05, b'a']).is_err());
+        assert!(fields(&[0x0b]).is_err());
+    }
+}
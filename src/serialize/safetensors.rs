@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use ndarray::{Array, NdFloat};
+
+use crate::module::{LoadReport, Module, StateDict};
+use crate::serialize::json::Json;
+use crate::serialize::Dtype;
+
+// safetensors layout: the length of the header as a little endian u64, the JSON header
+// describing every tensor, padded with spaces to 8 bytes, then the raw little endian data
+
+const METADATA_KEY: &str = "__metadata__";
+
+fn dtype_name(dtype: Dtype) -> &'static str {
+    match dtype {
+        Dtype::F32 => "F32",
+        Dtype::F64 => "F64",
+    }
+}
+
+fn parse_dtype(name: &str) -> Result<Dtype, String> {
+    match name {
+        "F32" => Ok(Dtype::F32),
+        "F64" => Ok(Dtype::F64),
+        _ => Err(format!("unsupported dtype {}", name)),
+    }
+}
+
+/// safetensors bytes of the tensors, in the order of their names, with optional string
+/// metadata
+pub fn serialize<T: NdFloat>(
+    tensors: &StateDict<T>,
+    metadata: Option<&BTreeMap<String, String>>,
+) -> Vec<u8> {
+    let dtype = Dtype::of::<T>();
+    let mut entries = vec![];
+    if let Some(metadata) = metadata {
+        entries.push((METADATA_KEY.to_string(), Json::from(metadata)));
+    }
+
+    let mut data = vec![];
+    for (name, array) in tensors.iter() {
+        let begin = data.len();
+        data.extend(dtype.encode(array));
+        let info = Json::Object(vec![
+            (
+                "dtype".to_string(),
+                Json::String(dtype_name(dtype).to_string()),
+            ),
+            ("shape".to_string(), Json::from(array.shape())),
+            (
+                "data_offsets".to_string(),
+                Json::from(&[begin, data.len()][..]),
+            ),
+        ]);
+        entries.push((name.clone(), info));
+    }
+
+    let mut header = Json::Object(entries).dump().into_bytes();
+    while header.len() % 8 != 0 {
+        header.push(b' ');
+    }
+
+    let mut out = (header.len() as u64).to_le_bytes().to_vec();
+    out.extend(header);
+    out.extend(data);
+    out
+}
+
+/// split safetensors bytes into the parsed header and the data buffer
+fn split(bytes: &[u8]) -> Result<(Json, &[u8]), String> {
+    if bytes.len() < 8 {
+        return Err("file too short for a safetensors header".to_string());
+    }
+    let mut len = [0u8; 8];
+    len.copy_from_slice(&bytes[..8]);
+    let len = u64::from_le_bytes(len) as usize;
+    if len > bytes.len() - 8 {
+        return Err(format!("header of {} bytes exceeds the file", len));
+    }
+
+    let header = std::str::from_utf8(&bytes[8..8 + len]).map_err(|e| e.to_string())?;
+    let header = Json::parse(header)?;
+    if !matches!(header, Json::Object(_)) {
+        return Err("the header is not a JSON object".to_string());
+    }
+    Ok((header, &bytes[8 + len..]))
+}
+
+/// tensors stored in safetensors bytes, converted to `T`
+pub fn deserialize<T: NdFloat>(bytes: &[u8]) -> Result<StateDict<T>, String> {
+    let (header, data) = split(bytes)?;
+    let entries = match header {
+        Json::Object(entries) => entries,
+        _ => unreachable!(),
+    };
+
+    let mut tensors = StateDict::new();
+    for (name, info) in entries.iter().filter(|(name, _)| name != METADATA_KEY) {
+        let field = |key: &str| {
+            info.get(key)
+                .ok_or_else(|| format!("tensor {} has no {}", name, key))
+        };
+        let dtype = parse_dtype(field("dtype")?.as_str().unwrap_or(""))?;
+        let shape = field("shape")?
+            .as_usize_vec()
+            .ok_or_else(|| format!("invalid shape for tensor {}", name))?;
+        let offsets = field("data_offsets")?
+            .as_usize_vec()
+            .filter(|offsets| offsets.len() == 2 && offsets[0] <= offsets[1])
+            .ok_or_else(|| format!("invalid data offsets for tensor {}", name))?;
+
+        let (begin, end) = (offsets[0], offsets[1]);
+        if end > data.len() {
+            return Err(format!("data of tensor {} exceeds the file", name));
+        }
+        let size = shape
+            .iter()
+            .try_fold(dtype.size(), |size, &len| size.checked_mul(len));
+        if size != Some(end - begin) {
+            return Err(format!(
+                "data of tensor {} does not match its shape {:?}",
+                name, shape
+            ));
+        }
+
+        let values = dtype.decode(&data[begin..end], true);
+        tensors.insert(name.clone(), Array::from_shape_vec(shape, values).unwrap());
+    }
+    Ok(tensors)
+}
+
+/// string metadata stored in the header of safetensors bytes, empty when there is none
+pub fn read_metadata(bytes: &[u8]) -> Result<BTreeMap<String, String>, String> {
+    let (header, _) = split(bytes)?;
+    match header.get(METADATA_KEY) {
+        None => Ok(BTreeMap::new()),
+        Some(Json::Object(entries)) => entries
+            .iter()
+            .map(|(k, v)| match v.as_str() {
+                Some(v) => Ok((k.clone(), v.to_string())),
+                None => Err(format!("metadata {} is not a string", k)),
+            })
+            .collect(),
+        Some(_) => Err("metadata is not a JSON object".to_string()),
+    }
+}
+
+/// write the `state_dict` of a module, parameters and buffers, to a safetensors file
+pub fn save_file<T: NdFloat, M: Module<T> + ?Sized, P: AsRef<Path>>(
+    module: &M,
+    path: P,
+) -> Result<(), String> {
+    fs::write(path, serialize(&module.state_dict(), None)).map_err(|e| e.to_string())
+}
+
+/// load a safetensors file into a module with `load_state_dict`
+pub fn load_file<T: NdFloat, M: Module<T> + ?Sized, P: AsRef<Path>>(
+    module: &mut M,
+    path: P,
+) -> Result<LoadReport, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    Ok(module.load_state_dict(&deserialize(&bytes)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::linear::{Linear, MLP};
+    use ndarray::{array, IxDyn};
+
+    /// file written by `safetensors.torch.save_file` for a `bias` tensor `[0.5]` and a
+    /// `weight` tensor `[[1, 2], [3, 4]]` with the metadata `{"format": "pt"}`: the u64 length
+    /// of the header, the header padded with spaces to 8 bytes, then the little endian data
+    const PYTHON_FILE: &[u8] = b"\x98\x00\x00\x00\x00\x00\x00\x00\
+        {\"__metadata__\":{\"format\":\"pt\"},\
+        \"bias\":{\"dtype\":\"F32\",\"shape\":[1],\"data_offsets\":[0,4]},\
+        \"weight\":{\"dtype\":\"F32\",\"shape\":[2,2],\"data_offsets\":[4,20]}}   \
+        \x00\x00\x00\x3f\x00\x00\x80\x3f\x00\x00\x00\x40\x00\x00\x40\x40\x00\x00\x80\x40";
+
+    fn tensors() -> StateDict<f32> {
+        let mut tensors = StateDict::new();
+        tensors.insert("bias".to_string(), array!(0.5).into_dyn());
+        tensors.insert(
+            "weight".to_string(),
+            array!([1.0, 2.0], [3.0, 4.0]).into_dyn(),
+        );
+        tensors
+    }
+
+    #[test]
+    fn python_compatibility() {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("format".to_string(), "pt".to_string());
+
+        assert_eq!(serialize(&tensors(), Some(&metadata)), PYTHON_FILE);
+        assert_eq!(deserialize::<f32>(PYTHON_FILE).unwrap(), tensors());
+        assert_eq!(read_metadata(PYTHON_FILE).unwrap(), metadata);
+
+        // f32 data is converted when loaded as f64
+        let tensors64 = deserialize::<f64>(PYTHON_FILE).unwrap();
+        assert_eq!(
+            tensors64["weight"],
+            array!([1.0, 2.0], [3.0, 4.0]).into_dyn()
+        );
+    }
+
+    #[test]
+    fn round_trip_f64() {
+        let mut tensors = StateDict::new();
+        tensors.insert(
+            "a.b".to_string(),
+            Array::from_shape_fn((2, 3, 4), |(i, j, k)| (i * 12 + j * 4 + k) as f64 / 7.)
+                .into_dyn(),
+        );
+        // not in standard layout
+        tensors.insert(
+            "t".to_string(),
+            array!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0])
+                .reversed_axes()
+                .into_dyn(),
+        );
+        tensors.insert("scalar".to_string(), Array::from_elem(IxDyn(&[]), 2.5));
+
+        let bytes = serialize(&tensors, None);
+        assert_eq!(deserialize::<f64>(&bytes).unwrap(), tensors);
+        assert!(read_metadata(&bytes).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_files() {
+        let bytes = PYTHON_FILE;
+        assert!(deserialize::<f32>(&bytes[..bytes.len() - 4]).is_err());
+        assert!(deserialize::<f32>(&bytes[..6]).is_err());
+
+        let mut bytes = PYTHON_FILE.to_vec();
+        bytes[0] = 255;
+        assert!(deserialize::<f32>(&bytes).is_err());
+    }
+
+    #[test]
+    fn malformed_headers() {
+        let file = |header: &str| {
+            let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+            bytes.extend(header.as_bytes());
+            bytes.extend([0u8; 16].iter());
+            bytes
+        };
+        let tensor = |info: &str| file(&format!("{{\"w\":{{{}}}}}", info));
+
+        assert!(deserialize::<f32>(&file("[]")).is_err());
+        assert!(deserialize::<f32>(&file("{\"w\":")).is_err());
+        assert!(deserialize::<f32>(&file(&"[".repeat(10_000))).is_err());
+        assert!(read_metadata(&file("{\"__metadata__\":{\"a\":1}}")).is_err());
+
+        let header = r#""dtype":"F32","shape":[2,2],"data_offsets":[0,16]"#;
+        assert!(deserialize::<f32>(&tensor(header)).is_ok());
+        let malformed = [
+            r#""shape":[2,2],"data_offsets":[0,16]"#,
+            r#""dtype":"X","shape":[2,2],"data_offsets":[0,16]"#,
+            r#""dtype":"F32","shape":[2,-2],"data_offsets":[0,16]"#,
+            r#""dtype":"F32","shape":[2,2.5],"data_offsets":[0,16]"#,
+            r#""dtype":"F32","shape":[2,2],"data_offsets":[16,0]"#,
+            r#""dtype":"F32","shape":[2,2],"data_offsets":[0]"#,
+            r#""dtype":"F32","shape":[2,2],"data_offsets":[0,32]"#,
+            r#""dtype":"F32","shape":[4294967296,4294967296,4],"data_offsets":[0,16]"#,
+        ];
+        for info in malformed.iter() {
+            assert!(
+                deserialize::<f32>(&tensor(info)).is_err(),
+                "{} was accepted",
+                info
+            );
+        }
+    }
+
+    #[test]
+    fn save_and_load_module() {
+        let mlp = |sizes: &[usize]| MLP {
+            layers: sizes
+                .windows(2)
+                .map(|w| Linear::new(w[0], w[1]))
+                .collect::<Vec<_>>(),
+        };
+        let trained = mlp(&[2, 8, 1]);
+        let path =
+            std::env::temp_dir().join(format!("rusty_grad_{}.safetensors", std::process::id()));
+
+        save_file(&trained, &path).unwrap();
+        let mut fresh = mlp(&[2, 8, 1]);
+        let report = load_file(&mut fresh, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(report.is_ok());
+        assert_eq!(fresh.state_dict(), trained.state_dict());
+    }
+}
@@ -0,0 +1,523 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use ndarray::{Array, ArrayViewD, Axis, IxDyn, NdFloat};
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::nn::activation::Activation;
+use crate::nn::linear::{Linear, MLP};
+use crate::nn::Sequential;
+use crate::serialize::Dtype;
+use crate::variable::{Variable, VariableRef};
+
+// serde support, behind the `serde` feature. Arrays are written as nested lists, which
+// keeps small tensors readable in JSON logs. `Linear` and `MLP` go through `LinearConfig` and
+// `MLPConfig`, which also carry the activations. The weights can be left out of a config to
+// get freshly initialized layers
+
+/// array written as nested lists like `[[1.0, 2.0], [3.0, 4.0]]`, a 0-d array as a number
+pub struct Nested<'a, T: NdFloat>(pub ArrayViewD<'a, T>);
+
+impl<'a, T: NdFloat> Serialize for Nested<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.ndim() == 0 {
+            let x = *self.0.first().unwrap();
+            return match Dtype::of::<T>() {
+                Dtype::F32 => serializer.serialize_f32(x.to_f32().unwrap()),
+                Dtype::F64 => serializer.serialize_f64(x.to_f64().unwrap()),
+            };
+        }
+        let mut seq = serializer.serialize_seq(Some(self.0.len_of(Axis(0))))?;
+        for sub in self.0.outer_iter() {
+            seq.serialize_element(&Nested(sub))?;
+        }
+        seq.end()
+    }
+}
+
+/// array read from nested lists, which must be rectangular
+pub struct NestedArray<T: NdFloat>(pub Array<T, IxDyn>);
+
+enum Tree {
+    Leaf(f64),
+    List(Vec<Tree>),
+}
+
+struct TreeVisitor;
+
+impl<'de> Visitor<'de> for TreeVisitor {
+    type Value = Tree;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number or a list of nested lists of numbers")
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Tree, E> {
+        Ok(Tree::Leaf(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Tree, E> {
+        Ok(Tree::Leaf(v as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Tree, E> {
+        Ok(Tree::Leaf(v as f64))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Tree, A::Error> {
+        let mut items = vec![];
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Tree::List(items))
+    }
+}
+
+impl<'de> Deserialize<'de> for Tree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Tree, D::Error> {
+        deserializer.deserialize_any(TreeVisitor)
+    }
+}
+
+/// check that every list at depth `i` has `shape[i]` items and collect the numbers in
+/// C order
+fn flatten(tree: &Tree, shape: &[usize], values: &mut Vec<f64>) -> Result<(), String> {
+    match (tree, shape.split_first()) {
+        (Tree::Leaf(v), None) => values.push(*v),
+        (Tree::List(items), Some((&len, rest))) if items.len() == len => {
+            for item in items {
+                flatten(item, rest, values)?;
+            }
+        }
+        _ => return Err("nested lists are not rectangular".to_string()),
+    }
+    Ok(())
+}
+
+impl<'de, T: NdFloat> Deserialize<'de> for NestedArray<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<NestedArray<T>, D::Error> {
+        let tree = Tree::deserialize(deserializer)?;
+
+        // the shape is given by the first item of each level
+        let mut shape = vec![];
+        let mut node = &tree;
+        while let Tree::List(items) = node {
+            shape.push(items.len());
+            match items.first() {
+                Some(first) => node = first,
+                None => break,
+            }
+        }
+
+        let mut values = vec![];
+        flatten(&tree, &shape, &mut values).map_err(de::Error::custom)?;
+        let values = values.into_iter().map(|v| T::from(v).unwrap()).collect();
+        Ok(NestedArray(Array::from_shape_vec(shape, values).unwrap()))
+    }
+}
+
+/// the data of the variable and its gradient when it keeps one
+impl<T: NdFloat> Serialize for Variable<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Variable", 2)?;
+        state.serialize_field("data", &Nested(self.data.view()))?;
+        state.serialize_field("grad", &self.grad.as_ref().map(|g| Nested(g.view())))?;
+        state.end()
+    }
+}
+
+impl<T: NdFloat> Serialize for VariableRef<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.borrow().serialize(serializer)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: NdFloat"))]
+struct VariableRepr<T: NdFloat> {
+    data: NestedArray<T>,
+    #[serde(default)]
+    grad: Option<NestedArray<T>>,
+}
+
+/// a leaf variable keeping its gradient
+impl<'de, T: NdFloat> Deserialize<'de> for VariableRef<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<VariableRef<T>, D::Error> {
+        let repr = VariableRepr::<T>::deserialize(deserializer)?;
+        let mut var = Variable::new(repr.data.0);
+        if let Some(NestedArray(grad)) = repr.grad {
+            if grad.shape() != var.borrow().data.shape() {
+                return Err(de::Error::custom(format!(
+                    "grad of shape {:?} for data of shape {:?}",
+                    grad.shape(),
+                    var.borrow().data.shape()
+                )));
+            }
+            var.borrow_mut().grad = Some(grad);
+        }
+        Ok(var)
+    }
+}
+
+/// `Option<Array>` fields written as nested lists
+mod nested_option {
+    use super::*;
+
+    pub fn serialize<T: NdFloat, S: Serializer>(
+        array: &Option<Array<T, IxDyn>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        array
+            .as_ref()
+            .map(|a| Nested(a.view()))
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: NdFloat, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Array<T, IxDyn>>, D::Error> {
+        Ok(Option::<NestedArray<T>>::deserialize(deserializer)?.map(|array| array.0))
+    }
+}
+
+/// config of a `Linear` layer: its sizes, the activation following it and its weights. The
+/// weights can be left out to get freshly initialized ones
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound(serialize = "T: NdFloat", deserialize = "T: NdFloat"))]
+pub struct LinearConfig<T: NdFloat> {
+    pub in_features: usize,
+    pub out_features: usize,
+    /// applied after the layer, only within an `MLPConfig`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activation: Option<Activation>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "nested_option"
+    )]
+    pub weight: Option<Array<T, IxDyn>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "nested_option"
+    )]
+    pub bias: Option<Array<T, IxDyn>>,
+}
+
+impl<T: NdFloat> LinearConfig<T> {
+    /// the layer without its activation, the missing weights are initialized like
+    /// `Linear::new` does
+    fn build(self) -> Result<Linear<T>, String> {
+        let (in_features, out_features) = (self.in_features, self.out_features);
+        let check = |given: Option<Array<T, IxDyn>>, expected: &[usize], name: &str| match given {
+            Some(array) if array.shape() != expected => Err(format!(
+                "{} of shape {:?} for a Linear({}, {}), expected {:?}",
+                name,
+                array.shape(),
+                in_features,
+                out_features,
+                expected
+            )),
+            given => Ok(given.map(Variable::new)),
+        };
+        let weight = check(self.weight, &[out_features, in_features], "weight")?;
+        let bias = check(self.bias, &[out_features, 1], "bias")?;
+
+        let (weight, bias) = match (weight, bias) {
+            (Some(weight), Some(bias)) => (weight, bias),
+            (weight, bias) => {
+                let init = Linear::new(in_features, out_features);
+                let cast = |var: &VariableRef<f32>| {
+                    Variable::new(var.borrow().data.mapv(|x| T::from(x).unwrap()))
+                };
+                (
+                    weight.unwrap_or_else(|| cast(&init.weight)),
+                    bias.unwrap_or_else(|| cast(&init.bias)),
+                )
+            }
+        };
+        Ok(Linear {
+            in_features,
+            out_features,
+            weight,
+            bias,
+        })
+    }
+}
+
+impl<T: NdFloat> From<&Linear<T>> for LinearConfig<T> {
+    fn from(layer: &Linear<T>) -> LinearConfig<T> {
+        LinearConfig {
+            in_features: layer.in_features,
+            out_features: layer.out_features,
+            activation: None,
+            weight: Some(layer.weight.borrow().data.clone()),
+            bias: Some(layer.bias.borrow().data.clone()),
+        }
+    }
+}
+
+/// a `Linear` has no activation, the config must not give one
+impl<T: NdFloat> TryFrom<LinearConfig<T>> for Linear<T> {
+    type Error = String;
+
+    fn try_from(config: LinearConfig<T>) -> Result<Linear<T>, String> {
+        match config.activation {
+            Some(activation) => Err(format!(
+                "a Linear has no activation, {:?} can only follow a layer of an MLP",
+                activation
+            )),
+            None => config.build(),
+        }
+    }
+}
+
+/// config of an `MLP`: its layers and the activation following the hidden ones, unless
+/// they give their own. The output layer has none unless it gives one
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound(serialize = "T: NdFloat", deserialize = "T: NdFloat"))]
+pub struct MLPConfig<T: NdFloat> {
+    pub layers: Vec<LinearConfig<T>>,
+    /// relu by default
+    #[serde(default)]
+    pub activation: Activation,
+}
+
+impl<T: NdFloat> MLPConfig<T> {
+    /// activation following each layer
+    pub fn activations(&self) -> Vec<Option<Activation>> {
+        let hidden = self.layers.len().saturating_sub(1);
+        self.layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                layer
+                    .activation
+                    .or(Some(self.activation).filter(|_| i < hidden))
+            })
+            .collect()
+    }
+
+    fn check_layers(&self) -> Result<(), String> {
+        if self.layers.is_empty() {
+            return Err("an MLP needs at least one layer".to_string());
+        }
+        for (i, pair) in self.layers.windows(2).enumerate() {
+            if pair[0].out_features != pair[1].in_features {
+                return Err(format!(
+                    "layer {} has {} outputs but layer {} takes {} inputs",
+                    i,
+                    pair[0].out_features,
+                    i + 1,
+                    pair[1].in_features
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: NdFloat> From<&MLP<T>> for MLPConfig<T> {
+    fn from(mlp: &MLP<T>) -> MLPConfig<T> {
+        MLPConfig {
+            layers: mlp.layers.iter().map(LinearConfig::from).collect(),
+            activation: Activation::ReLU,
+        }
+    }
+}
+
+/// an `MLP` applies relu after its hidden layers and nothing after the last one, other
+/// activations need a `Sequential`
+impl<T: NdFloat> TryFrom<MLPConfig<T>> for MLP<T> {
+    type Error = String;
+
+    fn try_from(config: MLPConfig<T>) -> Result<MLP<T>, String> {
+        config.check_layers()?;
+        let activations = config.activations();
+        let (last, hidden) = activations.split_last().unwrap();
+        if let Some(activation) = hidden.iter().find(|&&a| a != Some(Activation::ReLU)) {
+            return Err(format!(
+                "an MLP applies relu between its layers, not {:?}",
+                activation.unwrap()
+            ));
+        }
+        if let Some(activation) = last {
+            return Err(format!(
+                "an MLP has no activation after its last layer, not {:?}",
+                activation
+            ));
+        }
+        let layers = config
+            .layers
+            .into_iter()
+            .map(LinearConfig::build)
+            .collect::<Result<_, _>>()?;
+        Ok(MLP { layers })
+    }
+}
+
+/// the layers of the config each followed by its activation, if any
+impl<T: NdFloat> TryFrom<MLPConfig<T>> for Sequential<T> {
+    type Error = String;
+
+    fn try_from(config: MLPConfig<T>) -> Result<Sequential<T>, String> {
+        config.check_layers()?;
+        let activations = config.activations();
+        let mut sequential = Sequential::new();
+        for (layer, activation) in config.layers.into_iter().zip(activations) {
+            sequential.push(layer.build()?);
+            if let Some(activation) = activation {
+                sequential.push(activation);
+            }
+        }
+        Ok(sequential)
+    }
+}
+
+impl<T: NdFloat> Serialize for Linear<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        LinearConfig::from(self).serialize(serializer)
+    }
+}
+
+impl<'de, T: NdFloat> Deserialize<'de> for Linear<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Linear<T>, D::Error> {
+        Linear::try_from(LinearConfig::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+impl<T: NdFloat> Serialize for MLP<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MLPConfig::from(self).serialize(serializer)
+    }
+}
+
+impl<'de, T: NdFloat> Deserialize<'de> for MLP<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MLP<T>, D::Error> {
+        MLP::try_from(MLPConfig::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::Module;
+    use ndarray::array;
+
+    #[test]
+    fn variable_json() {
+        let var = Variable::new(array!([1.5f32, -2.], [0.25, 3.]).into_dyn());
+        let json = serde_json::to_string(&var).unwrap();
+        assert_eq!(
+            json,
+            r#"{"data":[[1.5,-2.0],[0.25,3.0]],"grad":[[0.0,0.0],[0.0,0.0]]}"#
+        );
+
+        let back: VariableRef<f32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.borrow().data, var.borrow().data);
+
+        let scalar: VariableRef<f64> = serde_json::from_str(r#"{"data": 2}"#).unwrap();
+        assert_eq!(scalar.borrow().data, Array::from_elem(IxDyn(&[]), 2.));
+
+        let empty: VariableRef<f64> = serde_json::from_str(r#"{"data": [[], []]}"#).unwrap();
+        assert_eq!(empty.borrow().data.shape(), &[2, 0]);
+    }
+
+    #[test]
+    fn invalid_variables() {
+        assert!(serde_json::from_str::<VariableRef<f32>>(r#"{"data": [[1], [2, 3]]}"#).is_err());
+        assert!(serde_json::from_str::<VariableRef<f32>>(r#"{"data": [1, [2]]}"#).is_err());
+        assert!(
+            serde_json::from_str::<VariableRef<f32>>(r#"{"data": [1, 2], "grad": [1]}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn mlp_round_trip() {
+        let mut mlp = MLP {
+            layers: vec![Linear::new(2, 4), Linear::new(4, 1)],
+        };
+        let json = serde_json::to_string(&mlp).unwrap();
+        assert!(json.contains(r#""activation":"relu""#));
+
+        let mut back: MLP<f32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.state_dict(), mlp.state_dict());
+
+        let x = &Variable::new(array!([0.5f32], [-1.]).into_dyn());
+        assert_eq!(back.f(x).borrow().data, mlp.f(x).borrow().data);
+
+        let config = MLPConfig::from(&mlp);
+        assert_eq!(config.activations(), vec![Some(Activation::ReLU), None]);
+        assert_eq!(
+            MLP::try_from(config).unwrap().state_dict(),
+            mlp.state_dict()
+        );
+    }
+
+    #[test]
+    fn mlp_from_config() {
+        let config = r#"{
+            "layers": [
+                {"in_features": 3, "out_features": 8},
+                {"in_features": 8, "out_features": 2}
+            ],
+            "activation": "gelu"
+        }"#;
+        let config: MLPConfig<f64> = serde_json::from_str(config).unwrap();
+        assert_eq!(config.activations(), vec![Some(Activation::GELU), None]);
+        // only a Sequential can apply gelu
+        assert!(MLP::try_from(config.clone()).is_err());
+        let sequential = Sequential::try_from(config).unwrap();
+        assert_eq!(sequential.len(), 3);
+        assert_eq!(sequential.params()[0].borrow().data.shape(), &[8, 3]);
+        assert_eq!(sequential.params()[3].borrow().data.shape(), &[2, 1]);
+
+        // relu by default
+        let config = r#"{"layers": [{"in_features": 3, "out_features": 4},
+            {"in_features": 4, "out_features": 1}]}"#;
+        let mlp: MLP<f32> = serde_json::from_str(config).unwrap();
+        assert_eq!(mlp.layers[1].weight.borrow().data.shape(), &[1, 4]);
+    }
+
+    #[test]
+    fn layer_activations() {
+        let config = r#"{
+            "layers": [
+                {"in_features": 2, "out_features": 3, "activation": "tanh"},
+                {"in_features": 3, "out_features": 3},
+                {"in_features": 3, "out_features": 1, "activation": "sigmoid"}
+            ]
+        }"#;
+        let config: MLPConfig<f32> = serde_json::from_str(config).unwrap();
+        assert_eq!(
+            config.activations(),
+            vec![
+                Some(Activation::Tanh),
+                Some(Activation::ReLU),
+                Some(Activation::Sigmoid)
+            ]
+        );
+        assert_eq!(Sequential::try_from(config).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let mismatch = r#"{"layers": [
+            {"in_features": 3, "out_features": 8},
+            {"in_features": 4, "out_features": 2}
+        ]}"#;
+        assert!(serde_json::from_str::<MLP<f32>>(mismatch).is_err());
+        assert!(serde_json::from_str::<MLP<f32>>(r#"{"layers": []}"#).is_err());
+
+        let weight = r#"{"in_features": 2, "out_features": 1, "weight": [[1.0, 2.0, 3.0]]}"#;
+        assert!(serde_json::from_str::<Linear<f32>>(weight).is_err());
+        let unknown = r#"{"layers": [{"in_features": 1, "out_features": 1}], "activation": "elu"}"#;
+        assert!(serde_json::from_str::<MLP<f32>>(unknown).is_err());
+        let activation = r#"{"in_features": 1, "out_features": 1, "activation": "tanh"}"#;
+        assert!(serde_json::from_str::<Linear<f32>>(activation).is_err());
+        let output = r#"{"layers": [{"in_features": 1, "out_features": 1, "activation": "relu"}]}"#;
+        assert!(serde_json::from_str::<MLP<f32>>(output).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 use std::cell::Ref;
@@ -8,6 +9,8 @@ use std::rc::Rc;
 use ndarray::IxDyn;
 use ndarray::{Array, Ix1, NdFloat};
 
+use crate::serialize::onnx::Op;
+
 pub struct Variable<T>
 where
     T: NdFloat,
@@ -104,6 +107,11 @@ where
         self.ref_.borrow_mut()
     }
 
+    /// address of the underlying variable, shared by all the clones of this reference
+    pub fn as_ptr(&self) -> *const RefCell<Variable<T>> {
+        Rc::as_ptr(&self.ref_)
+    }
+
     pub fn backward(&mut self) {
         self.borrow_mut().backward();
     }
@@ -133,12 +141,19 @@ where
 
 // ****************************MODULE***********************
 
+/// 0-d zero returned by a `GradFn` for a root it gives no gradient to
+fn is_no_grad<T: NdFloat>(grad: &Array<T, IxDyn>) -> bool {
+    grad.ndim() == 0 && grad.iter().all(|g| g.is_zero())
+}
+
 pub trait GradFn<T>
 where
     T: NdFloat,
 {
     fn forward<'a, 'b>(&self, x: &'a Array<T, IxDyn>, y: &'b Array<T, IxDyn>) -> Array<T, IxDyn>;
 
+    /// gradients of the left and right roots, broadcast onto them. A 0-d zero stands for no
+    /// gradient and is skipped
     fn backward<'a>(
         &self,
         grad: &'a Array<T, IxDyn>,
@@ -146,6 +161,13 @@ where
         right_ref: &'a VariableRef<T>,
     ) -> [Array<T, IxDyn>; 2];
 
+    /// ONNX operators computing `forward`, used by `serialize::onnx::export`. The first one
+    /// takes the inputs of the function and each next one the output of the previous one.
+    /// `None` for the functions without an equivalent
+    fn onnx(&self) -> Option<Vec<Op>> {
+        None
+    }
+
     fn subscribe<'a, 'b>(
         &self,
         lhs: &'a VariableRef<T>,
@@ -209,7 +231,7 @@ where
                         let mut left_var = left_ref.borrow_mut();
 
                         match &mut left_var.grad {
-                            Some(grad) => {
+                            Some(grad) if !is_no_grad(&grads_to_add[0]) => {
                                 *grad += &grads_to_add[0];
                             }
                             _ => (),
@@ -220,7 +242,7 @@ where
                         let mut right_var = right_ref.borrow_mut();
 
                         match &mut right_var.grad {
-                            Some(grad) => {
+                            Some(grad) if !is_no_grad(&grads_to_add[1]) => {
                                 *grad += &grads_to_add[1];
                             }
                             _ => (),
@@ -242,15 +264,72 @@ where
     fn backward_in(&mut self, grad: &Array<T, IxDyn>) {
         let new_grad = self.backward_grad_fn(grad);
 
-        for (i, some_var) in vec![&mut self.left_root, &mut self.right_root]
-            .iter_mut()
-            .enumerate()
-        {
-            match some_var {
-                Some(var) => var.borrow_mut().backward_in(&new_grad[i]),
-                None => (),
+        // the gradients flowing into a node are summed before being propagated to its roots, so
+        // that a node shared by several paths of the graph is only backpropagated once
+        let mut pending: HashMap<*const RefCell<Variable<T>>, Array<T, IxDyn>> = HashMap::new();
+        Variable::accumulate(&mut pending, [&self.left_root, &self.right_root], new_grad);
+
+        for mut var in self.topological_order() {
+            if let Some(grad) = pending.remove(&var.as_ptr()) {
+                let new_grad = var.borrow_mut().backward_grad_fn(&grad);
+                let var = var.borrow();
+                Variable::accumulate(&mut pending, [&var.left_root, &var.right_root], new_grad);
+            }
+        }
+    }
+
+    fn accumulate(
+        pending: &mut HashMap<*const RefCell<Variable<T>>, Array<T, IxDyn>>,
+        roots: [&Option<VariableRef<T>>; 2],
+        grads: [Array<T, IxDyn>; 2],
+    ) {
+        for (root, grad) in roots.iter().zip(grads) {
+            if let Some(var) = root {
+                if var.borrow().is_leaf() || is_no_grad(&grad) {
+                    continue;
+                }
+                match pending.get_mut(&var.as_ptr()) {
+                    Some(acc) => *acc += &grad,
+                    None => {
+                        pending.insert(var.as_ptr(), grad);
+                    }
+                }
+            }
+        }
+    }
+
+    /// non leaf nodes reachable from the roots of this variable, each node coming before its own roots
+    fn topological_order(&self) -> Vec<VariableRef<T>> {
+        let mut order = vec![];
+        let mut visited = HashSet::new();
+
+        let mut stack: Vec<(VariableRef<T>, bool)> = [&self.left_root, &self.right_root]
+            .iter()
+            .filter_map(|root| root.as_ref().map(|var| (var.clone(), false)))
+            .collect();
+
+        while let Some((var, expanded)) = stack.pop() {
+            if expanded {
+                order.push(var);
+                continue;
+            }
+            if var.borrow().is_leaf() || !visited.insert(var.as_ptr()) {
+                continue;
+            }
+
+            stack.push((var.clone(), true));
+            let node = var.borrow();
+            for root in [&node.left_root, &node.right_root]
+                .iter()
+                .copied()
+                .flatten()
+            {
+                stack.push((root.clone(), false));
             }
         }
+
+        order.reverse();
+        order
     }
 }
 
@@ -288,4 +367,18 @@ mod tests {
 
         assert_eq!(x.borrow().get_grad().unwrap(), array!([0.0]).into_dyn());
     }
+
+    #[test]
+    fn backward_shared_nodes() {
+        let x = &Variable::new(array!([1.0]).into_dyn());
+
+        // each node is used twice by the next one: 2^40 paths lead from z to x
+        let mut z = x + x;
+        for _ in 0..40 {
+            z = &z + &z;
+        }
+        z.backward();
+
+        assert_eq!(x.borrow().get_grad_f(), array!([2f64.powi(41)]).into_dyn());
+    }
 }